@@ -0,0 +1,19 @@
+//! Screen shared by the CPU and GPU benchmarks, so both draw the same input
+
+use rustty::Terminal;
+
+pub const COLS: usize = 200;
+pub const ROWS: usize = 60;
+
+/// A screen with every cell holding colored text, like a busy log
+pub fn full_screen() -> Terminal {
+    let mut terminal = Terminal::new(COLS, ROWS);
+    for row in 0..ROWS {
+        let line: String = (0..COLS)
+            .map(|col| char::from(b'!' + ((row * COLS + col) % 94) as u8))
+            .collect();
+        let sgr = 31 + row % 7;
+        terminal.process_bytes(format!("\x1b[{};1H\x1b[{}m{}", row + 1, sgr, line).as_bytes());
+    }
+    terminal
+}
@@ -0,0 +1,54 @@
+//! Frame times of the CPU renderer on a full 200x60 screen
+//!
+//! Draws into the renderer's offscreen canvas, so no window is needed. Run with
+//! `cargo bench --bench cpu_render`.
+
+mod common;
+
+use common::{COLS, ROWS, full_screen};
+use criterion::{Criterion, criterion_group, criterion_main};
+use rustty::renderer::cpu::Canvas;
+use rustty::renderer::{DEFAULT_FONT_SIZE, FontCollection};
+
+fn canvas() -> Canvas {
+    let fonts = FontCollection::load(&FontCollection::default_families())
+        .expect("benchmark needs a monospace font");
+    let mut canvas = Canvas::new(fonts, DEFAULT_FONT_SIZE, 1, 1);
+    let metrics = *canvas.metrics();
+    canvas.set_padding(0.0, 0.0);
+    canvas.resize(
+        (COLS as f32 * metrics.width).ceil() as u32,
+        (ROWS as f32 * metrics.height).ceil() as u32,
+    );
+    canvas
+}
+
+fn bench_full_frame(c: &mut Criterion) {
+    let mut canvas = canvas();
+    let mut terminal = full_screen();
+
+    c.bench_function("cpu_full_frame_200x60", |b| {
+        b.iter(|| {
+            terminal.state_mut().grid.damage_all();
+            canvas.draw(terminal.state(), true, true)
+        })
+    });
+}
+
+fn bench_one_line(c: &mut Criterion) {
+    let mut canvas = canvas();
+    let mut terminal = full_screen();
+    canvas.draw(terminal.state(), true, true);
+
+    // Typing on one line redraws that line and the cursor row only
+    c.bench_function("cpu_one_line_200x60", |b| {
+        b.iter(|| {
+            terminal.state_mut().grid.clear_damage();
+            terminal.process_bytes(b"\x1b[30;1Hx");
+            canvas.draw(terminal.state(), true, true)
+        })
+    });
+}
+
+criterion_group!(benches, bench_full_frame, bench_one_line);
+criterion_main!(benches);
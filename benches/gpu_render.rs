@@ -0,0 +1,92 @@
+//! Frame times of the GPU renderer on a full 200x60 screen
+//!
+//! Draws into an offscreen texture, so no window is needed, and waits for the
+//! GPU to finish each frame. Compare with `cpu_render` for the same screens.
+//! Run with `cargo bench --features ui-gpu --bench gpu_render`.
+
+mod common;
+
+use common::{COLS, ROWS, full_screen};
+use criterion::{Criterion, criterion_group, criterion_main};
+use rustty::Terminal;
+use rustty::renderer::gpu::{Canvas, request_device};
+use rustty::renderer::{DEFAULT_FONT_SIZE, FontCollection};
+
+/// A canvas sized to the screen and the texture it draws into
+fn canvas() -> (Canvas, wgpu::TextureView) {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let (device, queue) =
+        pollster::block_on(request_device(&instance, None)).expect("benchmark needs a GPU adapter");
+    let fonts = FontCollection::load(&FontCollection::default_families())
+        .expect("benchmark needs a monospace font");
+    let metrics = fonts.cell_metrics(DEFAULT_FONT_SIZE);
+    let (width, height) = (
+        (COLS as f32 * metrics.width).ceil() as u32,
+        (ROWS as f32 * metrics.height).ceil() as u32,
+    );
+
+    let format = wgpu::TextureFormat::Bgra8Unorm;
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Benchmark Target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut canvas = Canvas::new(
+        device,
+        queue,
+        format,
+        fonts,
+        DEFAULT_FONT_SIZE,
+        width,
+        height,
+    )
+    .expect("failed to create canvas");
+    canvas.set_padding(0.0, 0.0);
+    (canvas, view)
+}
+
+fn draw(canvas: &mut Canvas, view: &wgpu::TextureView, terminal: &Terminal) {
+    canvas.draw(view, terminal.state(), true, true).unwrap();
+    canvas.device().poll(wgpu::Maintain::Wait);
+}
+
+fn bench_full_frame(c: &mut Criterion) {
+    let (mut canvas, view) = canvas();
+    let mut terminal = full_screen();
+
+    c.bench_function("gpu_full_frame_200x60", |b| {
+        b.iter(|| {
+            terminal.state_mut().grid.damage_all();
+            draw(&mut canvas, &view, &terminal)
+        })
+    });
+}
+
+fn bench_one_line(c: &mut Criterion) {
+    let (mut canvas, view) = canvas();
+    let mut terminal = full_screen();
+    draw(&mut canvas, &view, &terminal);
+
+    // Typing on one line rebuilds and uploads that row and the cursor row only
+    c.bench_function("gpu_one_line_200x60", |b| {
+        b.iter(|| {
+            terminal.state_mut().grid.clear_damage();
+            terminal.process_bytes(b"\x1b[30;1Hx");
+            draw(&mut canvas, &view, &terminal)
+        })
+    });
+}
+
+criterion_group!(benches, bench_full_frame, bench_one_line);
+criterion_main!(benches);
@@ -4,7 +4,64 @@
 //! implementation (CPU or GPU).
 
 use crate::renderer::Renderer;
+use crate::renderer::font::{DEFAULT_FONT_SIZE, MAX_FONT_SIZE, MIN_FONT_SIZE};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Font size change per Ctrl+= / Ctrl+- keypress, in points
+const ZOOM_STEP: f32 = 1.0;
+
+/// Seconds skipped by Left / Right while playing a recording
+const PLAYBACK_SEEK: f64 = 5.0;
+
+/// Time between toggles of the cursor and text blink phases
+const BLINK_INTERVAL: Duration = Duration::from_millis(530);
+
+/// Events sent to the winit event loop from outside it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppEvent {
+    /// The shell wrote output, or exited
+    ShellOutput,
+}
+
+/// Window appearance: padding around the grid, background and decorations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowConfig {
+    /// Minimum space between the window edges and the grid, in logical pixels
+    pub padding: (f32, f32),
+    /// Split the space left over after fitting whole cells evenly between
+    /// both sides, centering the grid in the window
+    pub center_grid: bool,
+    /// Opacity of the default background, from 0.0 (transparent) to 1.0;
+    /// translucency needs a compositor and the GPU renderer
+    pub background_opacity: f32,
+    /// Show the title bar and borders; `false` for a borderless window
+    pub decorations: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            padding: (10.0, 20.0),
+            center_grid: false,
+            background_opacity: 1.0,
+            decorations: true,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// Add the decorations and, for a translucent background, transparency
+    /// to the attributes of the window about to be created
+    pub fn window_attributes(
+        &self,
+        attributes: winit::window::WindowAttributes,
+    ) -> winit::window::WindowAttributes {
+        attributes
+            .with_decorations(self.decorations)
+            .with_transparent(self.background_opacity < 1.0)
+    }
+}
 
 /// Common application state shared between CPU and GPU renderers
 ///
@@ -17,6 +74,8 @@ pub struct AppBase {
     pub modifiers: winit::keyboard::ModifiersState,
     /// Current cursor blink phase (true = visible, false = hidden)
     pub cursor_visible_phase: bool,
+    /// Current text blink phase for SGR 5/6 cells (true = visible, false = hidden)
+    pub text_blink_phase: bool,
     /// Last time the cursor blink state was toggled
     pub last_blink_toggle: std::time::Instant,
     /// System clipboard for copy/paste operations
@@ -25,6 +84,17 @@ pub struct AppBase {
     pub last_mouse_position: Option<(usize, usize)>,
     /// Bitmask of currently pressed mouse buttons
     pub mouse_buttons_pressed: u8,
+    /// Window DPI scale factor (physical pixels per logical pixel)
+    pub scale_factor: f64,
+    /// Font size in points, scaled by `scale_factor` for rendering
+    pub font_size: f32,
+    /// Render programming ligatures (e.g. `->`, `!=`) in fonts that have them
+    pub ligatures: bool,
+    /// Padding, background opacity and decorations, read when the window is created
+    pub window_config: WindowConfig,
+    /// Distance from the window edges to the grid in physical pixels, as last
+    /// fitted to the window; more than the padding when the grid is centered
+    pub grid_offset: (f32, f32),
 }
 
 impl AppBase {
@@ -32,16 +102,26 @@ impl AppBase {
     pub fn new(cols: usize, rows: usize) -> Result<Self, String> {
         let session = crate::TerminalSession::new(cols, rows)
             .map_err(|e| format!("Failed to create terminal session: {}", e))?;
+        Ok(Self::with_session(session))
+    }
 
-        Ok(Self {
+    /// Create an AppBase around an existing session, e.g. one playing a recording
+    pub fn with_session(session: crate::TerminalSession) -> Self {
+        Self {
             session,
             modifiers: winit::keyboard::ModifiersState::empty(),
             cursor_visible_phase: true,
+            text_blink_phase: true,
             last_blink_toggle: std::time::Instant::now(),
             clipboard: arboard::Clipboard::new().ok(),
             last_mouse_position: None,
             mouse_buttons_pressed: 0,
-        })
+            scale_factor: 1.0,
+            font_size: DEFAULT_FONT_SIZE,
+            ligatures: true,
+            window_config: WindowConfig::default(),
+            grid_offset: WindowConfig::default().padding,
+        }
     }
 
     /// Process shell output from the PTY
@@ -51,28 +131,68 @@ impl AppBase {
         self.session.process_output()
     }
 
+    /// Grid padding in physical pixels at the current scale factor
+    pub fn padding(&self) -> (f32, f32) {
+        let scale = self.scale_factor as f32;
+        let (x, y) = self.window_config.padding;
+        (x * scale, y * scale)
+    }
+
+    /// Font size in physical pixels at the current scale factor
+    pub fn scaled_font_size(&self) -> f32 {
+        self.font_size * self.scale_factor as f32
+    }
+
     /// Calculate grid dimensions based on window size and character dimensions
+    ///
+    /// All sizes are in physical pixels; the padding is applied on both sides.
     pub fn calculate_grid_size(
         window_width: u32,
         window_height: u32,
         char_width: f32,
         char_height: f32,
+        padding: (f32, f32),
     ) -> (usize, usize) {
-        let cols = ((window_width as f32 - 20.0) / char_width).floor() as usize;
-        let rows = ((window_height as f32 - 40.0) / char_height).floor() as usize;
+        let (padding_x, padding_y) = padding;
+        let cols = ((window_width as f32 - padding_x * 2.0) / char_width).floor() as usize;
+        let rows = ((window_height as f32 - padding_y * 2.0) / char_height).floor() as usize;
         (cols.max(10), rows.max(3))
     }
 
+    /// Distance from the window edges to a `cols` x `rows` grid
+    ///
+    /// All sizes are in physical pixels. With `center`, the space not taken by
+    /// the grid is split evenly between both sides, but never below the padding.
+    pub fn grid_offset(
+        window_width: u32,
+        window_height: u32,
+        (cols, rows): (usize, usize),
+        (char_width, char_height): (f32, f32),
+        padding: (f32, f32),
+        center: bool,
+    ) -> (f32, f32) {
+        if !center {
+            return padding;
+        }
+        let x = (window_width as f32 - cols as f32 * char_width) / 2.0;
+        let y = (window_height as f32 - rows as f32 * char_height) / 2.0;
+        // Whole pixels keep glyphs crisp
+        (x.floor().max(padding.0), y.floor().max(padding.1))
+    }
+
     /// Convert window coordinates to grid coordinates
+    ///
+    /// All values are in physical pixels, as reported by winit cursor events.
     pub fn window_to_grid_coords(
         x: f64,
         y: f64,
         char_width: f32,
         char_height: f32,
+        padding: (f32, f32),
     ) -> Option<(usize, usize)> {
-        // Account for rendering offset (10px horizontal, 20px vertical)
-        let grid_x = (x - 10.0) / char_width as f64;
-        let grid_y = (y - 20.0) / char_height as f64;
+        // Account for the grid padding
+        let grid_x = (x - padding.0 as f64) / char_width as f64;
+        let grid_y = (y - padding.1 as f64) / char_height as f64;
 
         if grid_x >= 0.0 && grid_y >= 0.0 {
             Some((grid_x.floor() as usize, grid_y.floor() as usize))
@@ -93,6 +213,8 @@ pub struct App<R: Renderer> {
     pub window: Option<Arc<winit::window::Window>>,
     /// Renderer implementation (CPU or GPU)
     pub renderer: Option<R>,
+    /// Output changed the screen but hasn't been drawn yet
+    frame_pending: bool,
 }
 
 impl<R: Renderer> Default for App<R> {
@@ -106,10 +228,20 @@ impl<R: Renderer> App<R> {
     pub fn new() -> Self {
         let base = AppBase::new(80, 24).expect("Failed to create AppBase");
 
+        Self::with_base(base)
+    }
+
+    /// Create an App around an existing session, e.g. one playing a recording
+    pub fn with_session(session: crate::TerminalSession) -> Self {
+        Self::with_base(AppBase::with_session(session))
+    }
+
+    fn with_base(base: AppBase) -> Self {
         Self {
             base,
             window: None,
             renderer: None,
+            frame_pending: false,
         }
     }
 
@@ -117,45 +249,168 @@ impl<R: Renderer> App<R> {
     pub fn calculate_grid_size(&self, window_width: u32, window_height: u32) -> (usize, usize) {
         if let Some(renderer) = &self.renderer {
             let (char_width, char_height) = renderer.char_dimensions();
-            let cols = ((window_width as f32 - 20.0) / char_width).floor() as usize;
-            let rows = ((window_height as f32 - 40.0) / char_height).floor() as usize;
-            (cols.max(10), rows.max(3))
+            AppBase::calculate_grid_size(
+                window_width,
+                window_height,
+                char_width,
+                char_height,
+                self.base.padding(),
+            )
         } else {
             (80, 24) // Default fallback
         }
     }
 
     /// Process shell output from PTY and request redraw if needed
+    ///
+    /// A redraw is only requested when the output damaged the grid or moved,
+    /// showed or hid the cursor, and not while a synchronized update holds
+    /// the frame back.
     pub fn process_shell_output(&mut self) -> bool {
+        let state = self.base.session.state();
+        let cursor_before = (state.cursor, state.show_cursor);
+
         let still_running = self.base.process_shell_output();
 
-        if let Some(window) = &self.window {
+        let state = self.base.session.state();
+        let changed = state.grid.has_damage() || (state.cursor, state.show_cursor) != cursor_before;
+        self.frame_pending |= changed;
+        if self.frame_pending
+            && self.base.session.frame_ready()
+            && let Some(window) = &self.window
+        {
             window.request_redraw();
         }
 
         still_running
     }
 
+    /// Wake the event loop through `proxy` when the shell has output
+    ///
+    /// The loop then runs `process_shell_output` from `about_to_wait`.
+    pub fn set_event_proxy(&mut self, proxy: winit::event_loop::EventLoopProxy<AppEvent>) {
+        self.base.session.set_output_notifier(move || {
+            // Fails only once the event loop is gone
+            let _ = proxy.send_event(AppEvent::ShellOutput);
+        });
+    }
+
+    /// How long the event loop may sleep when nothing else wakes it
+    ///
+    /// Shell output, input and redraws wake it by themselves; only a visible
+    /// blink, a frame held back by a synchronized update and the next event
+    /// of a recording being played need a timer.
+    pub fn control_flow(&self) -> winit::event_loop::ControlFlow {
+        use winit::event_loop::ControlFlow;
+
+        let state = self.base.session.state();
+        let blink = (state.cursor_blink || state.grid.has_blinking_cells())
+            .then(|| self.base.last_blink_toggle + BLINK_INTERVAL);
+        let frame = self
+            .base
+            .session
+            .frame_deadline()
+            .filter(|_| self.frame_pending);
+        let playback = self.base.session.playback_deadline();
+        match blink.into_iter().chain(frame).chain(playback).min() {
+            Some(deadline) => ControlFlow::WaitUntil(deadline),
+            None => ControlFlow::Wait,
+        }
+    }
+
     /// Render terminal state to screen
     pub fn render(&mut self) -> anyhow::Result<()> {
         use anyhow::Context;
 
         let renderer = self.renderer.as_mut().context("No renderer available")?;
+
+        // Keep showing the last frame until the synchronized update is done;
+        // the damage is kept for when it is
+        if !self.base.session.frame_ready() {
+            self.frame_pending = true;
+            return Ok(());
+        }
+        self.frame_pending = false;
         let state = self.base.session.state();
 
         // Calculate cursor visibility based on blink phase
         let cursor_visible =
             state.show_cursor && (!state.cursor_blink || self.base.cursor_visible_phase);
 
+        // Let the compositor pace frames to the display refresh
+        if let Some(window) = &self.window {
+            window.pre_present_notify();
+        }
+
         // Delegate to renderer's render_with_blink method
-        renderer.render_with_blink(state, cursor_visible)?;
+        renderer.render_with_blink(state, cursor_visible, self.base.text_blink_phase)?;
+
+        // The renderer has drawn the damaged rows
+        self.base.session.state_mut().grid.clear_damage();
+
+        // Keep drawing frames until the scroll animation settles
+        if renderer.is_animating()
+            && let Some(window) = &self.window
+        {
+            window.request_redraw();
+        }
         Ok(())
     }
 
+    /// Advance the blink timer shared by the cursor and blinking text
+    ///
+    /// Requests a redraw when the toggle affects something on screen.
+    pub fn update_blink(&mut self) {
+        let elapsed = self.base.last_blink_toggle.elapsed();
+        if elapsed < BLINK_INTERVAL {
+            return;
+        }
+
+        self.base.last_blink_toggle = Instant::now();
+        self.base.text_blink_phase = !self.base.text_blink_phase;
+
+        let state = self.base.session.state();
+        if state.cursor_blink {
+            self.base.cursor_visible_phase = !self.base.cursor_visible_phase;
+        }
+
+        if (state.cursor_blink || state.grid.has_blinking_cells())
+            && let Some(window) = &self.window
+        {
+            window.request_redraw();
+        }
+    }
+
     /// Handle keyboard input events
     pub fn handle_keyboard_input(&mut self, key: &winit::keyboard::Key, text: Option<&str>) {
         use winit::keyboard::{Key, NamedKey};
 
+        // While playing a recording, Left / Right seek and other keys do nothing
+        if self.base.session.is_playback() {
+            match key {
+                Key::Named(NamedKey::ArrowLeft) => self.base.session.seek_playback(-PLAYBACK_SEEK),
+                Key::Named(NamedKey::ArrowRight) => self.base.session.seek_playback(PLAYBACK_SEEK),
+                _ => return,
+            }
+            if let Some(window) = &self.window {
+                window.request_redraw();
+            }
+            return;
+        }
+
+        // Shift+PageUp / Shift+PageDown page through the scrollback
+        if self.base.modifiers.shift_key()
+            && let Key::Named(named @ (NamedKey::PageUp | NamedKey::PageDown)) = key
+        {
+            let page = self.base.session.state().grid.viewport_height as isize;
+            let delta = if *named == NamedKey::PageUp {
+                -page
+            } else {
+                page
+            };
+            return self.scroll_viewport(delta);
+        }
+
         let bytes = match key {
             Key::Named(named) => match named {
                 NamedKey::Enter => Some(b"\r".to_vec()),
@@ -204,6 +459,16 @@ impl<R: Renderer> App<R> {
                 if chars.len() == 1 {
                     let ch = chars[0];
 
+                    // Ctrl+= / Ctrl+- / Ctrl+0 zoom the font instead of reaching the shell
+                    if self.base.modifiers.control_key() {
+                        match ch {
+                            '=' | '+' => return self.zoom(ZOOM_STEP),
+                            '-' => return self.zoom(-ZOOM_STEP),
+                            '0' => return self.set_font_size(DEFAULT_FONT_SIZE),
+                            _ => {}
+                        }
+                    }
+
                     // Check if Ctrl modifier is pressed
                     if self.base.modifiers.control_key() && ch.is_ascii_alphabetic() {
                         let lower = ch.to_ascii_lowercase();
@@ -213,6 +478,11 @@ impl<R: Renderer> App<R> {
                             return self.handle_paste();
                         }
 
+                        // Ctrl+Shift+E exports the scrollback to files
+                        if lower == 'e' && self.base.modifiers.shift_key() {
+                            return self.export_scrollback();
+                        }
+
                         // Ctrl+letter produces control codes 1-26
                         let ctrl_code = (lower as u8) - b'a' + 1;
                         Some(vec![ctrl_code])
@@ -231,6 +501,8 @@ impl<R: Renderer> App<R> {
         };
 
         if let Some(data) = bytes {
+            // Typing returns to the live screen
+            self.scroll_viewport_to_end();
             if let Err(e) = self.base.session.write_input(&data) {
                 eprintln!("Failed to write to shell: {}", e);
             }
@@ -240,6 +512,119 @@ impl<R: Renderer> App<R> {
         self.reset_cursor_blink();
     }
 
+    /// Move the viewport `delta` lines through the scrollback; negative is up
+    pub fn scroll_viewport(&mut self, delta: isize) {
+        let grid = &mut self.base.session.state_mut().grid;
+        let viewport_start = grid.viewport_start;
+        grid.scroll_viewport(delta);
+        if grid.viewport_start != viewport_start
+            && let Some(window) = &self.window
+        {
+            window.request_redraw();
+        }
+    }
+
+    /// Move the viewport back to the live screen below the scrollback
+    pub fn scroll_viewport_to_end(&mut self) {
+        let grid = &self.base.session.state().grid;
+        let remaining = grid.screen_start().saturating_sub(grid.viewport_start);
+        if remaining > 0 {
+            self.scroll_viewport(remaining as isize);
+        }
+    }
+
+    /// Change the font size by `delta` points
+    pub fn zoom(&mut self, delta: f32) {
+        self.set_font_size(self.base.font_size + delta);
+    }
+
+    /// Change the font size (in points) and refit the grid to the window
+    ///
+    /// The size is clamped to the supported range.
+    pub fn set_font_size(&mut self, font_size: f32) {
+        let font_size = font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        if self.base.font_size == font_size {
+            return;
+        }
+        self.base.font_size = font_size;
+        self.apply_scaling();
+    }
+
+    /// Enable or disable ligature shaping
+    pub fn set_ligatures(&mut self, enabled: bool) {
+        self.base.ligatures = enabled;
+        if let Some(renderer) = &mut self.renderer {
+            renderer.set_ligatures(enabled);
+        }
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+
+    /// Change the DPI scale factor (on startup and `ScaleFactorChanged`)
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.base.scale_factor = scale_factor;
+        self.apply_scaling();
+    }
+
+    /// Push the scaled font size and padding to the renderer and refit the grid
+    ///
+    /// The renderer recomputes its cell metrics and flushes its glyph cache,
+    /// then the PTY is resized to the new grid.
+    fn apply_scaling(&mut self) {
+        let font_size = self.base.scaled_font_size();
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+
+        if renderer.font_size() != font_size
+            && let Err(e) = renderer.set_font_size(font_size)
+        {
+            eprintln!("Failed to change font size: {}", e);
+            return;
+        }
+
+        if let Some(window) = self.window.clone() {
+            let size = window.inner_size();
+            self.fit_grid(size.width, size.height);
+            window.request_redraw();
+        }
+    }
+
+    /// Fit the grid to a window of `width` x `height` physical pixels
+    ///
+    /// Resizes the session to the grid and places the grid in the window,
+    /// centered if so configured. Returns the grid size as (cols, rows).
+    pub fn fit_grid(&mut self, width: u32, height: u32) -> (usize, usize) {
+        let Some(renderer) = &mut self.renderer else {
+            return (80, 24);
+        };
+        let char_size = renderer.char_dimensions();
+        let padding = self.base.padding();
+        let grid_size =
+            AppBase::calculate_grid_size(width, height, char_size.0, char_size.1, padding);
+        let center = self.base.window_config.center_grid;
+        let offset = AppBase::grid_offset(width, height, grid_size, char_size, padding, center);
+
+        renderer.set_padding(offset.0, offset.1);
+        self.base.grid_offset = offset;
+        self.base.session.resize(grid_size.0, grid_size.1);
+        grid_size
+    }
+
+    /// Change the opacity of the default background
+    ///
+    /// The window must have been created transparent for the desktop to show through.
+    pub fn set_background_opacity(&mut self, opacity: f32) {
+        self.base.window_config.background_opacity = opacity;
+        if let Some(renderer) = &mut self.renderer {
+            renderer.set_background_opacity(opacity);
+        }
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+
     /// Handle clipboard paste operation
     pub fn handle_paste(&mut self) {
         if let Some(clipboard) = &mut self.base.clipboard {
@@ -267,6 +652,24 @@ impl<R: Renderer> App<R> {
         }
     }
 
+    /// Write the scrollback as HTML, ANSI text and SVG to
+    /// `rustty-<unix time>.{html,ans,svg}` in the working directory
+    pub fn export_scrollback(&self) {
+        use crate::{ExportFormat, ExportScope};
+
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let state = self.base.session.state();
+        for format in [ExportFormat::Html, ExportFormat::Ansi, ExportFormat::Svg] {
+            let path = format!("rustty-{}.{}", stamp, format.extension());
+            match std::fs::write(&path, format.export(state, ExportScope::Scrollback)) {
+                Ok(()) => println!("Exported scrollback to {}", path),
+                Err(e) => eprintln!("Failed to write {}: {}", path, e),
+            }
+        }
+    }
+
     /// Reset cursor blink state to visible
     fn reset_cursor_blink(&mut self) {
         self.base.cursor_visible_phase = true;
@@ -274,7 +677,16 @@ impl<R: Renderer> App<R> {
     }
 
     /// Handle focus events (focus in/out)
+    ///
+    /// The renderer switches between a solid and a hollow block cursor.
     pub fn handle_focus_event(&mut self, focused: bool) {
+        if let Some(renderer) = &mut self.renderer {
+            renderer.set_focused(focused);
+        }
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+
         if self.base.session.state().focus_events {
             let sequence = if focused { b"\x1b[I" } else { b"\x1b[O" };
             if let Err(e) = self.base.session.write_input(sequence) {
@@ -314,18 +726,18 @@ impl<R: Renderer> App<R> {
         let prev_position = self.base.last_mouse_position;
         let mouse_buttons_pressed = self.base.mouse_buttons_pressed;
 
-        if term_state.mouse_cell_motion || term_state.mouse_sgr {
-            if mouse_buttons_pressed != 0 && prev_position != Some((col, row)) {
-                let button_code = mouse_buttons_pressed.trailing_zeros() as u8;
-                let sequence =
-                    Self::generate_mouse_sequence(term_state, button_code, col, row, true);
-                if !sequence.is_empty() {
-                    if let Err(e) = self.base.session.write_input(&sequence) {
-                        eprintln!("Failed to write mouse motion: {}", e);
-                        return false;
-                    }
-                    return true;
+        if (term_state.mouse_cell_motion || term_state.mouse_sgr)
+            && mouse_buttons_pressed != 0
+            && prev_position != Some((col, row))
+        {
+            let button_code = mouse_buttons_pressed.trailing_zeros() as u8;
+            let sequence = Self::generate_mouse_sequence(term_state, button_code, col, row, true);
+            if !sequence.is_empty() {
+                if let Err(e) = self.base.session.write_input(&sequence) {
+                    eprintln!("Failed to write mouse motion: {}", e);
+                    return false;
                 }
+                return true;
             }
         }
         false
@@ -354,7 +766,7 @@ impl<R: Renderer> App<R> {
         } else if state.mouse_tracking || state.mouse_cell_motion {
             // X10/X11 mouse protocol: ESC[MCbCxCy
             // Coordinates are encoded as value + 32 + 1 (1-indexed)
-            let encoded_button = (cb + 32) as u8;
+            let encoded_button = cb + 32;
             let encoded_col = (col + 1 + 32).min(255) as u8;
             let encoded_row = (row + 1 + 32).min(255) as u8;
             vec![0x1b, b'[', b'M', encoded_button, encoded_col, encoded_row]
@@ -367,9 +779,64 @@ impl<R: Renderer> App<R> {
     pub fn window_to_grid_coords(&self, x: f64, y: f64) -> Option<(usize, usize)> {
         if let Some(renderer) = &self.renderer {
             let (char_width, char_height) = renderer.char_dimensions();
-            AppBase::window_to_grid_coords(x, y, char_width, char_height)
+            AppBase::window_to_grid_coords(x, y, char_width, char_height, self.base.grid_offset)
         } else {
             None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_size_with_scaled_padding() {
+        // 800x600 logical at 1x with 10x20 cells
+        assert_eq!(
+            AppBase::calculate_grid_size(800, 600, 10.0, 20.0, (10.0, 20.0)),
+            (78, 28)
+        );
+        // Same window at 2x: physical pixels, cells and padding all double
+        assert_eq!(
+            AppBase::calculate_grid_size(1600, 1200, 20.0, 40.0, (20.0, 40.0)),
+            (78, 28)
+        );
+    }
+
+    #[test]
+    fn test_centered_grid_splits_leftover_space() {
+        // 78x28 cells of 10x20 leave 20x40 pixels around the grid
+        let grid = (78, 28);
+        assert_eq!(
+            AppBase::grid_offset(800, 600, grid, (10.0, 20.0), (5.0, 5.0), false),
+            (5.0, 5.0)
+        );
+        assert_eq!(
+            AppBase::grid_offset(800, 600, grid, (10.0, 20.0), (5.0, 5.0), true),
+            (10.0, 20.0)
+        );
+        // Odd leftovers round down, and the padding is kept when the grid overflows
+        assert_eq!(
+            AppBase::grid_offset(805, 500, grid, (10.0, 20.0), (5.0, 5.0), true),
+            (12.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn test_window_to_grid_coords_with_scaled_padding() {
+        let padding = (20.0, 40.0);
+        assert_eq!(
+            AppBase::window_to_grid_coords(20.0, 40.0, 20.0, 40.0, padding),
+            Some((0, 0))
+        );
+        assert_eq!(
+            AppBase::window_to_grid_coords(65.0, 125.0, 20.0, 40.0, padding),
+            Some((2, 2))
+        );
+        assert_eq!(
+            AppBase::window_to_grid_coords(15.0, 50.0, 20.0, 40.0, padding),
+            None
+        );
+    }
+}
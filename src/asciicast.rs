@@ -0,0 +1,596 @@
+//! Session recording and playback in the asciicast v2 format
+//!
+//! A recording is a JSON header line followed by one JSON array per event:
+//! `[seconds, "o", text]` for output, `"i"` for input and `"r"` for resizes
+//! with `"COLSxROWS"` as the text. Such files play in asciinema as well, and
+//! recordings from asciinema play here. See
+//! <https://docs.asciinema.org/manual/asciicast/v2/>.
+
+use crate::Terminal;
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Recording time between the terminal copies a `Player` keeps for seeking back
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Writes events to an asciicast v2 file as they happen
+pub struct Recorder {
+    writer: Box<dyn Write + Send>,
+    start: Instant,
+    output: Utf8Stream,
+    input: Utf8Stream,
+}
+
+impl Recorder {
+    /// Start a recording of a `cols` x `rows` terminal, writing its header
+    pub fn new(writer: impl Write + Send + 'static, cols: usize, rows: usize) -> Result<Self> {
+        let mut writer: Box<dyn Write + Send> = Box::new(writer);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let mut env = serde_json::Map::new();
+        for name in ["SHELL", "TERM"] {
+            if let Ok(value) = std::env::var(name) {
+                env.insert(name.to_string(), Value::String(value));
+            }
+        }
+        let header = json!({
+            "version": 2,
+            "width": cols,
+            "height": rows,
+            "timestamp": timestamp,
+            "env": env,
+        });
+        writeln!(writer, "{}", header).context("Failed to write recording header")?;
+
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+            output: Utf8Stream::default(),
+            input: Utf8Stream::default(),
+        })
+    }
+
+    /// Start a recording in a new file at `path`
+    pub fn create(path: impl AsRef<Path>, cols: usize, rows: usize) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording {}", path.display()))?;
+        Self::new(BufWriter::new(file), cols, rows)
+    }
+
+    /// Record output of the shell
+    ///
+    /// A UTF-8 sequence split between calls is written once complete.
+    pub fn output(&mut self, bytes: &[u8]) -> Result<()> {
+        let text = self.output.decode(bytes);
+        self.event("o", &text)
+    }
+
+    /// Record input sent to the shell
+    pub fn input(&mut self, bytes: &[u8]) -> Result<()> {
+        let text = self.input.decode(bytes);
+        self.event("i", &text)
+    }
+
+    /// Record a resize of the terminal
+    pub fn resize(&mut self, cols: usize, rows: usize) -> Result<()> {
+        self.event("r", &format!("{}x{}", cols, rows))
+    }
+
+    /// Write buffered events to the file
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().context("Failed to write recording")
+    }
+
+    fn event(&mut self, code: &str, text: &str) -> Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        let time = self.start.elapsed().as_secs_f64();
+        let event = json!([(time * 1e6).round() / 1e6, code, text]);
+        writeln!(self.writer, "{}", event).context("Failed to write recording")
+    }
+}
+
+/// Decodes bytes arriving in chunks that may split UTF-8 sequences
+#[derive(Default)]
+struct Utf8Stream {
+    /// Start of a sequence cut off at the end of the last chunk
+    pending: Vec<u8>,
+}
+
+impl Utf8Stream {
+    /// Text of the complete characters so far; invalid bytes become U+FFFD
+    fn decode(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        let mut rest = &self.pending[..];
+        while !rest.is_empty() {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &invalid[len..];
+                        }
+                        // Incomplete sequence at the end: wait for the rest
+                        None => {
+                            rest = invalid;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending = rest.to_vec();
+        text
+    }
+}
+
+/// Something that happened during a recording
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    /// Text the shell wrote to the terminal
+    Output(String),
+    /// Text typed into the terminal
+    Input(String),
+    /// The terminal was resized to columns x rows
+    Resize(usize, usize),
+}
+
+/// An event and its time since the start of the recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub time: Duration,
+    pub kind: EventKind,
+}
+
+/// A parsed asciicast v2 recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// Terminal size when the recording started
+    pub width: usize,
+    pub height: usize,
+    /// Events in time order; markers and unknown event types are left out
+    pub events: Vec<Event>,
+}
+
+impl Recording {
+    /// Read a recording from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recording {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid recording {}", path.display()))
+    }
+
+    /// Parse the contents of an asciicast v2 file
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        let (_, header) = lines.next().context("Empty recording")?;
+        let header: Value = serde_json::from_str(header).context("Invalid header")?;
+        if header["version"] != 2 {
+            bail!("Unsupported asciicast version {}", header["version"]);
+        }
+        let size = |key: &str| {
+            header[key]
+                .as_u64()
+                .filter(|&value| value > 0)
+                .map(|value| value as usize)
+                .with_context(|| format!("Header has no positive {}", key))
+        };
+        let (width, height) = (size("width")?, size("height")?);
+
+        let mut events = Vec::new();
+        for (index, line) in lines {
+            let event = Self::parse_event(line).with_context(|| format!("Line {}", index + 1))?;
+            events.extend(event);
+        }
+        events.sort_by_key(|event| event.time);
+
+        Ok(Self {
+            width,
+            height,
+            events,
+        })
+    }
+
+    /// Parse an event line; `None` for event types without meaning here
+    fn parse_event(line: &str) -> Result<Option<Event>> {
+        let value: Value = serde_json::from_str(line)?;
+        let (Some(time), Some(code), Some(data)) =
+            (value[0].as_f64(), value[1].as_str(), value[2].as_str())
+        else {
+            bail!("Expected [time, type, data], found {}", value);
+        };
+        let time = Duration::try_from_secs_f64(time).context("Invalid event time")?;
+
+        let kind = match code {
+            "o" => EventKind::Output(data.to_string()),
+            "i" => EventKind::Input(data.to_string()),
+            "r" => {
+                let size = data
+                    .split_once('x')
+                    .and_then(|(cols, rows)| Some((cols.parse().ok()?, rows.parse().ok()?)))
+                    .filter(|&(cols, rows)| cols > 0 && rows > 0);
+                let Some((cols, rows)) = size else {
+                    bail!("Invalid resize {:?}", data);
+                };
+                EventKind::Resize(cols, rows)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(Event { time, kind }))
+    }
+
+    /// Time of the last event
+    pub fn duration(&self) -> Duration {
+        self.events
+            .last()
+            .map_or(Duration::ZERO, |event| event.time)
+    }
+}
+
+/// A copy of the terminal to seek back from
+struct Checkpoint {
+    /// Index of the first event not played into `terminal`
+    next: usize,
+    time: Duration,
+    terminal: Terminal,
+}
+
+/// Feeds a recording into a terminal
+///
+/// Playing forward is cheap. Seeking back replays from the closest of the
+/// terminal copies taken every `CHECKPOINT_INTERVAL` while playing, or from
+/// the start.
+pub struct Player {
+    recording: Recording,
+    /// Index of the next event to play
+    next: usize,
+    /// Recording time played up to
+    position: Duration,
+    /// Ordered by event index
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl Player {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            next: 0,
+            position: Duration::ZERO,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// A terminal of the size the recording started at, to play into
+    pub fn new_terminal(&self) -> Terminal {
+        Terminal::new(self.recording.width, self.recording.height)
+    }
+
+    /// Recording time played up to
+    pub fn position(&self) -> Duration {
+        self.position
+    }
+
+    /// Time of the next event to play, `None` at the end
+    pub fn next_event_time(&self) -> Option<Duration> {
+        self.recording.events.get(self.next).map(|event| event.time)
+    }
+
+    /// Check whether every event has been played
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.recording.events.len()
+    }
+
+    /// Play the events up to `time` into `terminal`
+    ///
+    /// Returns whether the terminal changed. Times before the position are
+    /// ignored; use `seek` to go back.
+    pub fn play_to(&mut self, terminal: &mut Terminal, time: Duration) -> bool {
+        let mut changed = false;
+        while let Some(event) = self.recording.events.get(self.next)
+            && event.time <= time
+        {
+            match &event.kind {
+                EventKind::Output(text) => {
+                    terminal.process_bytes(text.as_bytes());
+                    // Nobody reads the answers to queries in the recording
+                    terminal.drain_responses();
+                }
+                EventKind::Input(_) => {}
+                EventKind::Resize(cols, rows) => terminal.resize(*cols, *rows),
+            }
+            self.next += 1;
+            changed = true;
+            self.take_checkpoint(terminal);
+        }
+        self.position = self.position.max(time);
+        changed
+    }
+
+    /// Set `terminal` to how it was at `time`
+    pub fn seek(&mut self, terminal: &mut Terminal, time: Duration) {
+        if time < self.position {
+            let checkpoint = self
+                .checkpoints
+                .iter()
+                .rev()
+                .find(|checkpoint| checkpoint.time <= time)
+                .and_then(|checkpoint| Some((checkpoint.next, checkpoint.terminal.try_clone()?)));
+            (self.next, *terminal) = checkpoint.unwrap_or_else(|| (0, self.new_terminal()));
+            self.position = Duration::ZERO;
+        }
+        self.play_to(terminal, time);
+        self.position = time;
+    }
+
+    /// Keep a copy of the terminal if the last one is long enough ago
+    fn take_checkpoint(&mut self, terminal: &Terminal) {
+        let time = self.recording.events[self.next - 1].time;
+        let due = match self.checkpoints.last() {
+            Some(last) => self.next > last.next && time >= last.time + CHECKPOINT_INTERVAL,
+            None => time >= CHECKPOINT_INTERVAL,
+        };
+        if due && let Some(terminal) = terminal.try_clone() {
+            self.checkpoints.push(Checkpoint {
+                next: self.next,
+                time,
+                terminal,
+            });
+        }
+    }
+}
+
+/// Plays a recording in real time, or faster or slower
+pub struct Playback {
+    player: Player,
+    speed: f64,
+    /// Wall clock time when the recording was at `offset`
+    started: Instant,
+    offset: Duration,
+}
+
+impl Playback {
+    /// Start playing at `speed` times the original speed
+    ///
+    /// Fails unless `speed` is finite and positive.
+    pub fn new(recording: Recording, speed: f64) -> Result<Self> {
+        if !(speed.is_finite() && speed > 0.0) {
+            bail!("Playback speed must be finite and positive, not {}", speed);
+        }
+        Ok(Self {
+            player: Player::new(recording),
+            speed,
+            started: Instant::now(),
+            offset: Duration::ZERO,
+        })
+    }
+
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
+    /// Recording time at wall clock time `now`
+    pub fn time_at(&self, now: Instant) -> Duration {
+        self.offset
+            + now
+                .saturating_duration_since(self.started)
+                .mul_f64(self.speed)
+    }
+
+    /// Play into `terminal` the events due at `now`; returns whether it changed
+    pub fn update(&mut self, terminal: &mut Terminal, now: Instant) -> bool {
+        let time = self.time_at(now);
+        self.player.play_to(terminal, time)
+    }
+
+    /// Jump by `delta` seconds of recording time, back when negative
+    pub fn seek_by(&mut self, terminal: &mut Terminal, delta: f64, now: Instant) {
+        let time = self.time_at(now).as_secs_f64() + delta;
+        let time = Duration::from_secs_f64(
+            time.clamp(0.0, self.player.recording.duration().as_secs_f64()),
+        );
+        self.player.seek(terminal, time);
+        self.started = now;
+        self.offset = time;
+    }
+
+    /// Wall clock time when the next event is due
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let time = self.player.next_event_time()?;
+        let wait = time.saturating_sub(self.offset).div_f64(self.speed);
+        Some(self.started + wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TextFormat;
+    use std::sync::{Arc, Mutex};
+
+    /// A writer whose contents stay readable after the recorder takes it
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const CAST: &str = r#"{"version": 2, "width": 10, "height": 3, "timestamp": 1700000000}
+[0.5, "o", "hello"]
+[1.0, "i", "x"]
+[1.5, "m", "marker"]
+[2.0, "o", "\r\nworld"]
+[3.0, "r", "20x4"]
+"#;
+
+    fn screen(terminal: &Terminal) -> String {
+        terminal.state().grid.screen_text(TextFormat::Plain)
+    }
+
+    #[test]
+    fn test_parse_recording() {
+        let recording = Recording::parse(CAST).unwrap();
+        assert_eq!((recording.width, recording.height), (10, 3));
+        assert_eq!(
+            recording.events,
+            vec![
+                Event {
+                    time: Duration::from_millis(500),
+                    kind: EventKind::Output("hello".to_string()),
+                },
+                Event {
+                    time: Duration::from_secs(1),
+                    kind: EventKind::Input("x".to_string()),
+                },
+                Event {
+                    time: Duration::from_secs(2),
+                    kind: EventKind::Output("\r\nworld".to_string()),
+                },
+                Event {
+                    time: Duration::from_secs(3),
+                    kind: EventKind::Resize(20, 4),
+                },
+            ]
+        );
+        assert_eq!(recording.duration(), Duration::from_secs(3));
+
+        assert!(Recording::parse(r#"{"version": 1, "width": 10, "height": 3}"#).is_err());
+        assert!(Recording::parse(&format!("{}[1.0, \"o\"]", CAST)).is_err());
+        // Zero sizes would leave the terminal without cells
+        assert!(Recording::parse(r#"{"version": 2, "width": 0, "height": 3}"#).is_err());
+        assert!(Recording::parse(r#"{"version": 2, "width": 10, "height": 0}"#).is_err());
+        assert!(Recording::parse(&format!("{}[4.0, \"r\", \"0x3\"]", CAST)).is_err());
+        assert!(Recording::parse(&format!("{}[4.0, \"r\", \"20x0\"]", CAST)).is_err());
+    }
+
+    #[test]
+    fn test_record_and_parse() {
+        let buffer = SharedBuffer::default();
+        let mut recorder = Recorder::new(buffer.clone(), 80, 24).unwrap();
+        // A character split between two reads is recorded whole
+        recorder.output(b"caf\xc3").unwrap();
+        recorder.output(b"\xa9 \x1b[1m\"ok\"").unwrap();
+        recorder.input(b"ls\r").unwrap();
+        recorder.resize(100, 30).unwrap();
+
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let recording = Recording::parse(&text).unwrap();
+        assert_eq!((recording.width, recording.height), (80, 24));
+        let kinds: Vec<_> = recording
+            .events
+            .into_iter()
+            .map(|event| event.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EventKind::Output("caf".to_string()),
+                EventKind::Output("\u{e9} \x1b[1m\"ok\"".to_string()),
+                EventKind::Input("ls\r".to_string()),
+                EventKind::Resize(100, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_play_and_seek() {
+        let mut player = Player::new(Recording::parse(CAST).unwrap());
+        let mut terminal = player.new_terminal();
+
+        assert!(!player.play_to(&mut terminal, Duration::from_millis(400)));
+        assert!(player.play_to(&mut terminal, Duration::from_secs(2)));
+        assert_eq!(screen(&terminal), "hello\nworld");
+
+        // Seeking back replays from the start
+        player.seek(&mut terminal, Duration::from_secs(1));
+        assert_eq!(screen(&terminal), "hello");
+        player.seek(&mut terminal, Duration::from_secs(5));
+        assert!(player.is_finished());
+        assert_eq!(terminal.state().grid.width, 20);
+    }
+
+    #[test]
+    fn test_seek_back_from_checkpoints() {
+        // A line every 5 seconds, with an escape sequence split between events
+        let mut cast = String::from(r#"{"version": 2, "width": 10, "height": 3}"#);
+        for second in 1..=6 {
+            cast.push_str(&format!(
+                "\n[{}, \"o\", \"\\r\\n{}\\u001b[\"]",
+                second * 5,
+                second
+            ));
+            cast.push_str(&format!("\n[{}.5, \"o\", \"1m\"]", second * 5));
+        }
+        let mut player = Player::new(Recording::parse(&cast).unwrap());
+        let mut terminal = player.new_terminal();
+        player.play_to(&mut terminal, Duration::from_secs(60));
+
+        // Copies are only taken between sequences
+        let times: Vec<_> = player
+            .checkpoints
+            .iter()
+            .map(|c| c.time.as_secs_f64())
+            .collect();
+        assert_eq!(times, vec![10.5, 20.5, 30.5]);
+
+        player.seek(&mut terminal, Duration::from_secs(26));
+        assert_eq!(screen(&terminal), "3\n4\n5");
+        assert!(terminal.state().bold);
+        player.seek(&mut terminal, Duration::from_secs(6));
+        assert_eq!(screen(&terminal), "\n1");
+    }
+
+    #[test]
+    fn test_playback_speed() {
+        let mut playback = Playback::new(Recording::parse(CAST).unwrap(), 2.0).unwrap();
+        let start = playback.started;
+        let mut terminal = playback.player().new_terminal();
+
+        assert_eq!(
+            playback.next_deadline(),
+            Some(start + Duration::from_millis(250))
+        );
+        assert!(playback.update(&mut terminal, start + Duration::from_secs(1)));
+        assert_eq!(screen(&terminal), "hello\nworld");
+
+        playback.seek_by(&mut terminal, -1.5, start + Duration::from_secs(1));
+        assert_eq!(playback.player().position(), Duration::from_millis(500));
+        assert_eq!(screen(&terminal), "hello");
+    }
+
+    #[test]
+    fn test_playback_rejects_invalid_speed() {
+        for speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(Playback::new(Recording::parse(CAST).unwrap(), speed).is_err());
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use anyhow::{Context as _, Result};
+use anyhow::{Context as _, Result, bail};
+use rustty::asciicast::Recording;
+use rustty::{AppEvent, TerminalSession};
 use winit::event_loop::EventLoop;
 
 #[cfg(not(feature = "ui-gpu"))]
@@ -13,9 +15,62 @@ mod gpu_ui;
 #[cfg(feature = "ui-gpu")]
 use gpu_ui::App;
 
+/// Command line options
+#[derive(Default)]
+struct Options {
+    /// Record the session to this asciicast file
+    record: Option<String>,
+    /// Play this asciicast file instead of running a shell
+    play: Option<String>,
+    /// Playback speed, 1.0 for the original speed
+    speed: f64,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Options {
+            speed: 1.0,
+            ..Options::default()
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--record" => options.record = Some(value()?),
+                "--play" => options.play = Some(value()?),
+                "--speed" => {
+                    options.speed = value()?.parse().context("Invalid --speed")?;
+                    if !(options.speed.is_finite() && options.speed > 0.0) {
+                        bail!("--speed must be a finite positive number");
+                    }
+                }
+                _ => bail!(
+                    "Unknown argument {}\nUsage: rustty [--record FILE] [--play FILE [--speed N]]",
+                    arg
+                ),
+            }
+        }
+        Ok(options)
+    }
+}
+
 fn main() -> Result<()> {
-    let event_loop = EventLoop::new().context("Failed to create event loop")?;
-    let mut app = App::new();
+    let options = Options::parse(std::env::args().skip(1))?;
+    let recording = options.play.as_ref().map(Recording::load).transpose()?;
+
+    let event_loop = EventLoop::<AppEvent>::with_user_event()
+        .build()
+        .context("Failed to create event loop")?;
+    let mut app = match recording {
+        Some(recording) => App::with_session(TerminalSession::playback(recording, options.speed)?),
+        None => App::new(),
+    };
+    if let Some(path) = &options.record {
+        app.0.base.session.start_recording(path)?;
+    }
+    app.0.set_event_proxy(event_loop.create_proxy());
     event_loop.run_app(&mut app)?;
     Ok(())
 }
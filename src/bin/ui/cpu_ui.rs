@@ -1,14 +1,11 @@
-use rustty::renderer::{CpuRenderer, Renderer};
+use rustty::AppEvent;
+use rustty::renderer::{CpuRenderer, DEFAULT_FONT_SIZE, FontCollection, Renderer};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
 use winit::application::ApplicationHandler;
 use winit::event::{ElementState, WindowEvent};
-use winit::event_loop::{ActiveEventLoop, ControlFlow};
+use winit::event_loop::ActiveEventLoop;
 use winit::window::{Window, WindowId};
 
-use font_kit::family_name::FamilyName;
-use font_kit::properties::Properties;
-use font_kit::source::SystemSource;
 use softbuffer::{Context, Surface};
 
 pub(crate) type AppInner = rustty::App<CpuRenderer>;
@@ -20,9 +17,13 @@ impl App {
     pub fn new() -> Self {
         App(AppInner::new())
     }
+
+    pub fn with_session(session: rustty::TerminalSession) -> Self {
+        App(AppInner::with_session(session))
+    }
 }
 
-impl ApplicationHandler for App {
+impl ApplicationHandler<AppEvent> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         // Helper macro to handle errors and exit on failure
         macro_rules! unwrap_or_die {
@@ -53,6 +54,7 @@ impl ApplicationHandler for App {
             let window_attrs = Window::default_attributes()
                 .with_title("Rustty Terminal")
                 .with_inner_size(winit::dpi::LogicalSize::new(800, 600));
+            let window_attrs = self.0.base.window_config.window_attributes(window_attrs);
 
             let window = Arc::new(unwrap_or_die!(
                 event_loop.create_window(window_attrs),
@@ -67,43 +69,26 @@ impl ApplicationHandler for App {
             );
             println!("Surface created");
 
-            // Load font
-            let font = SystemSource::new()
-                .select_best_match(
-                    &[
-                        FamilyName::Title("CaskaydiaCove Nerd Font Mono".to_string()),
-                        FamilyName::Title("CaskaydiaCove NF Mono".to_string()),
-                        FamilyName::Monospace,
-                    ],
-                    &Properties::new(),
-                )
-                .ok()
-                .and_then(|handle| handle.load().ok());
-
-            let font = unwrap_or_die!(option font, "Failed to load font");
-
-            // Calculate character dimensions
-            let font_size = 16.0;
-            let char_width = 9.0;
-            let char_height = 20.0;
-
-            // Create renderer
-            let renderer = CpuRenderer::new(surface, font, char_width, char_height, font_size);
-
-            // Calculate initial grid size
-            let size = window.inner_size();
-            let (cols, rows) = {
-                let (char_width, char_height) = renderer.char_dimensions();
-                let cols = ((size.width as f32 - 20.0) / char_width).floor() as usize;
-                let rows = ((size.height as f32 - 40.0) / char_height).floor() as usize;
-                (cols.max(10), rows.max(3))
-            };
-            println!("Calculated grid size: {}x{}", cols, rows);
+            // Load regular, bold, italic and bold-italic faces
+            let fonts = unwrap_or_die!(
+                FontCollection::load(&FontCollection::default_families()),
+                "Failed to load font"
+            );
+
+            // Create renderer, cell size comes from the font metrics
+            let renderer = CpuRenderer::new(surface, fonts, DEFAULT_FONT_SIZE);
+
+            let scale_factor = window.scale_factor();
+            println!("Scale factor: {}", scale_factor);
 
             self.0.window = Some(window);
             self.0.renderer = Some(renderer);
-            self.0.base.session.resize(cols, rows);
 
+            // Scale font and padding to physical pixels and fit the grid
+            self.0.set_scale_factor(scale_factor);
+            self.0.set_ligatures(self.0.base.ligatures);
+            self.0
+                .set_background_opacity(self.0.base.window_config.background_opacity);
             println!("Rendering initial frame...");
             if let Err(e) = self.0.render() {
                 eprintln!("Initial render error: {}", e);
@@ -119,21 +104,18 @@ impl ApplicationHandler for App {
             return;
         }
 
-        // Handle cursor blink animation
-        if self.0.base.session.state().cursor_blink {
-            let elapsed = self.0.base.last_blink_toggle.elapsed();
-            if elapsed >= Duration::from_millis(530) {
-                self.0.base.cursor_visible_phase = !self.0.base.cursor_visible_phase;
-                self.0.base.last_blink_toggle = Instant::now();
-                if let Some(window) = &self.0.window {
-                    window.request_redraw();
-                }
-            }
-        }
+        // Handle cursor and text blink animation
+        self.0.update_blink();
 
-        event_loop.set_control_flow(ControlFlow::WaitUntil(
-            Instant::now() + Duration::from_millis(16),
-        ));
+        // Sleep until the next event, or the next blink
+        event_loop.set_control_flow(self.0.control_flow());
+    }
+
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: AppEvent) {
+        match event {
+            // The output is processed in about_to_wait, which follows
+            AppEvent::ShellOutput => {}
+        }
     }
 
     fn window_event(
@@ -154,23 +136,29 @@ impl ApplicationHandler for App {
             WindowEvent::ModifiersChanged(new_modifiers) => {
                 self.0.base.modifiers = new_modifiers.state();
             }
-            WindowEvent::KeyboardInput { event, .. } => {
-                if event.state == ElementState::Pressed {
-                    let text = event.text.as_ref().map(|s| s.as_str());
-                    self.0.handle_keyboard_input(&event.logical_key, text);
-                }
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                let text = event.text.as_ref().map(|s| s.as_str());
+                self.0.handle_keyboard_input(&event.logical_key, text);
             }
             WindowEvent::Resized(new_size) => {
-                let (cols, rows) = self.0.calculate_grid_size(new_size.width, new_size.height);
+                let (cols, rows) = self.0.fit_grid(new_size.width, new_size.height);
                 println!(
                     "Window resized to: {}x{} -> grid: {}x{}",
                     new_size.width, new_size.height, cols, rows
                 );
-                self.0.base.session.resize(cols, rows);
+                if let Some(renderer) = &mut self.0.renderer
+                    && let Err(e) = renderer.resize(new_size.width, new_size.height)
+                {
+                    eprintln!("Failed to resize renderer: {}", e);
+                }
                 if let Some(window) = &self.0.window {
                     window.request_redraw();
                 }
             }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                println!("Scale factor changed to {}", scale_factor);
+                self.0.set_scale_factor(scale_factor);
+            }
             WindowEvent::Focused(focused) => {
                 self.0.handle_focus_event(focused);
             }
@@ -1,9 +1,9 @@
+use rustty::AppEvent;
 use rustty::renderer::GpuRenderer;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
 use winit::application::ApplicationHandler;
 use winit::event::{ElementState, WindowEvent};
-use winit::event_loop::{ActiveEventLoop, ControlFlow};
+use winit::event_loop::ActiveEventLoop;
 use winit::window::{Window, WindowId};
 
 pub(crate) type AppInner = rustty::App<GpuRenderer>;
@@ -15,9 +15,13 @@ impl App {
     pub fn new() -> Self {
         App(AppInner::new())
     }
+
+    pub fn with_session(session: rustty::TerminalSession) -> Self {
+        App(AppInner::with_session(session))
+    }
 }
 
-impl ApplicationHandler for App {
+impl ApplicationHandler<AppEvent> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         // Helper macro to handle errors and exit on failure
         macro_rules! unwrap_or_die {
@@ -38,6 +42,7 @@ impl ApplicationHandler for App {
             let window_attrs = Window::default_attributes()
                 .with_title("Rustty Terminal (GPU)")
                 .with_inner_size(winit::dpi::LogicalSize::new(800, 600));
+            let window_attrs = self.0.base.window_config.window_attributes(window_attrs);
 
             let window = Arc::new(unwrap_or_die!(
                 event_loop.create_window(window_attrs),
@@ -52,23 +57,24 @@ impl ApplicationHandler for App {
             );
             println!("GPU renderer initialized");
 
-            let size = window.inner_size();
-            let (cols, rows) = {
+            let scale_factor = window.scale_factor();
+            println!("Scale factor: {}", scale_factor);
+
+            self.0.window = Some(window);
+            self.0.renderer = Some(renderer);
+
+            // Scale font and padding to physical pixels and fit the grid
+            self.0.set_scale_factor(scale_factor);
+            self.0.set_ligatures(self.0.base.ligatures);
+            self.0
+                .set_background_opacity(self.0.base.window_config.background_opacity);
+            if let Some(renderer) = &self.0.renderer {
                 let (char_width, char_height) = renderer.char_dimensions();
                 println!(
                     "Character dimensions: {}x{} pixels",
                     char_width, char_height
                 );
-                let cols = ((size.width as f32 - 20.0) / char_width).floor() as usize;
-                let rows = ((size.height as f32 - 40.0) / char_height).floor() as usize;
-                (cols.max(10), rows.max(3))
-            };
-            println!("Calculated grid size: {}x{}", cols, rows);
-
-            self.0.window = Some(window);
-            self.0.renderer = Some(renderer);
-            self.0.base.session.resize(cols, rows);
-
+            }
             println!("Rendering initial frame...");
             if let Err(e) = self.0.render() {
                 eprintln!("Initial render error: {}", e);
@@ -84,21 +90,18 @@ impl ApplicationHandler for App {
             return;
         }
 
-        // Handle cursor blink animation
-        if self.0.base.session.state().cursor_blink {
-            let elapsed = self.0.base.last_blink_toggle.elapsed();
-            if elapsed >= Duration::from_millis(530) {
-                self.0.base.cursor_visible_phase = !self.0.base.cursor_visible_phase;
-                self.0.base.last_blink_toggle = Instant::now();
-                if let Some(window) = &self.0.window {
-                    window.request_redraw();
-                }
-            }
-        }
+        // Handle cursor and text blink animation
+        self.0.update_blink();
+
+        // Sleep until the next event, or the next blink
+        event_loop.set_control_flow(self.0.control_flow());
+    }
 
-        event_loop.set_control_flow(ControlFlow::WaitUntil(
-            Instant::now() + Duration::from_millis(16),
-        ));
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: AppEvent) {
+        match event {
+            // The output is processed in about_to_wait, which follows
+            AppEvent::ShellOutput => {}
+        }
     }
 
     fn window_event(
@@ -119,31 +122,32 @@ impl ApplicationHandler for App {
             WindowEvent::ModifiersChanged(new_modifiers) => {
                 self.0.base.modifiers = new_modifiers.state();
             }
-            WindowEvent::KeyboardInput { event, .. } => {
-                if event.state == ElementState::Pressed {
-                    let text = event.text.as_ref().map(|s| s.as_str());
-                    self.0.handle_keyboard_input(&event.logical_key, text);
-                }
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                let text = event.text.as_ref().map(|s| s.as_str());
+                self.0.handle_keyboard_input(&event.logical_key, text);
             }
             WindowEvent::Resized(new_size) => {
-                let (cols, rows) = self.0.calculate_grid_size(new_size.width, new_size.height);
+                let (cols, rows) = self.0.fit_grid(new_size.width, new_size.height);
                 println!(
                     "Window resized to: {}x{} -> grid: {}x{}",
                     new_size.width, new_size.height, cols, rows
                 );
-                self.0.base.session.resize(cols, rows);
 
                 // Resize GPU surface
-                if let Some(renderer) = &mut self.0.renderer {
-                    if let Err(e) = renderer.resize(new_size.width, new_size.height) {
-                        eprintln!("Failed to resize renderer: {}", e);
-                    }
+                if let Some(renderer) = &mut self.0.renderer
+                    && let Err(e) = renderer.resize(new_size.width, new_size.height)
+                {
+                    eprintln!("Failed to resize renderer: {}", e);
                 }
 
                 if let Some(window) = &self.0.window {
                     window.request_redraw();
                 }
             }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                println!("Scale factor changed to {}", scale_factor);
+                self.0.set_scale_factor(scale_factor);
+            }
             WindowEvent::Focused(focused) => {
                 self.0.handle_focus_event(focused);
             }
@@ -64,11 +64,15 @@ pub mod app;
 // Terminal session management
 pub mod session;
 
+// Session recording and playback (asciicast v2)
+pub mod asciicast;
+
 // Re-export commonly used types
-pub use app::{App, AppBase};
+pub use app::{App, AppBase, AppEvent, WindowConfig};
 pub use session::TerminalSession;
 pub use shell::Shell;
 pub use terminal::{
     AnsiParseError, Cell, Color, CsiCommand, Cursor, CursorStyle, DecPrivateMode, EraseMode,
-    SgrParameter, Terminal, TerminalGrid, TerminalState,
+    ExportFormat, ExportScope, SgrParameter, Terminal, TerminalGrid, TerminalState, TextFormat,
+    TextRange, UnderlineStyle,
 };
@@ -0,0 +1,587 @@
+//! Glyph atlas space management, independent of any graphics API
+//!
+//! Glyphs of varying size are packed into shelves (rows) of square texture
+//! pages. When no page has room the newest page doubles in size up to a
+//! maximum, then further pages are added, and once the page limit is reached
+//! the least recently used glyphs are evicted to make room. Glyphs used in the
+//! current frame are never evicted, since quads referencing them may already
+//! have been built.
+//!
+//! The allocator only hands out rectangles; the renderer owns the textures and
+//! reacts to the `PageChange` reported by `insert`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+use crate::renderer::font::FaceId;
+
+/// Shelf heights are rounded up to a multiple of this so that glyphs of
+/// slightly different heights can share shelves
+const SHELF_HEIGHT_STEP: u32 = 4;
+
+/// Identifies one rasterized glyph image in an atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    /// Face the glyph comes from, `None` for procedurally drawn glyphs
+    pub face: Option<FaceId>,
+    /// Glyph id within the face, or the character for procedural glyphs
+    pub glyph_id: u32,
+    /// Pixel size the glyph is rasterized at, as `f32` bits
+    pub size: u32,
+    /// Horizontal subpixel offset, in steps of `1 / SUBPIXEL_STEPS` pixel
+    pub subpixel: u8,
+    pub synthetic_bold: bool,
+    pub synthetic_italic: bool,
+}
+
+/// Number of horizontal subpixel positions glyphs are rasterized at
+pub const SUBPIXEL_STEPS: u8 = 4;
+
+/// Split a horizontal pixel position into a whole pixel and a subpixel step
+///
+/// Positions are rounded to the nearest step; rounding up to a whole pixel
+/// carries into the pixel.
+pub fn snap_subpixel(x: f32) -> (f32, u8) {
+    let pixel = x.floor();
+    let step = ((x - pixel) * SUBPIXEL_STEPS as f32).round() as u8;
+    if step >= SUBPIXEL_STEPS {
+        (pixel + 1.0, 0)
+    } else {
+        (pixel, step)
+    }
+}
+
+/// Location of a glyph image in the atlas, in pixels of its page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasSlot {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Page layout change the texture owner has to mirror after an insert
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageChange {
+    /// A new, empty page was appended
+    Added { page: usize, size: u32 },
+    /// An existing page grew; its old contents stay at the same positions
+    Grown { page: usize, size: u32 },
+}
+
+/// Counters describing atlas usage, for debugging
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AtlasStats {
+    pub pages: usize,
+    pub glyphs: usize,
+    /// Pixels covered by glyph images
+    pub used_pixels: u64,
+    /// Total pixels of all pages
+    pub capacity_pixels: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl AtlasStats {
+    /// Combine the stats of several atlases
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            pages: self.pages + other.pages,
+            glyphs: self.glyphs + other.glyphs,
+            used_pixels: self.used_pixels + other.used_pixels,
+            capacity_pixels: self.capacity_pixels + other.capacity_pixels,
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            evictions: self.evictions + other.evictions,
+        }
+    }
+}
+
+impl fmt::Display for AtlasStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = if self.capacity_pixels > 0 {
+            self.used_pixels as f64 * 100.0 / self.capacity_pixels as f64
+        } else {
+            0.0
+        };
+        write!(
+            f,
+            "{} glyphs in {} pages ({:.1}% used), {} hits, {} misses, {} evictions",
+            self.glyphs, self.pages, used, self.hits, self.misses, self.evictions
+        )
+    }
+}
+
+/// A row of glyphs sharing one height, with its free horizontal spans
+#[derive(Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Free `(x, width)` spans, sorted by `x` and never adjacent
+    free: Vec<(u32, u32)>,
+}
+
+impl Shelf {
+    fn is_empty(&self, page_size: u32) -> bool {
+        self.free == [(0, page_size)]
+    }
+}
+
+/// Shelf packing within one square page
+#[derive(Debug)]
+struct ShelfPacker {
+    size: u32,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    fn new(size: u32) -> Self {
+        Self {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Top of the unused area below the last shelf
+    fn shelves_bottom(&self) -> u32 {
+        self.shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height)
+    }
+
+    /// Reserve a `width` x `height` rectangle, returning its top-left corner
+    ///
+    /// Prefers the shortest existing shelf the glyph fits in, without using
+    /// shelves more than twice its height while a new shelf can still be opened.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.size || height > self.size {
+            return None;
+        }
+
+        let new_height = height
+            .div_ceil(SHELF_HEIGHT_STEP)
+            .saturating_mul(SHELF_HEIGHT_STEP)
+            .min(self.size);
+        let can_open = self.shelves_bottom() + new_height <= self.size;
+
+        let best = self
+            .shelves
+            .iter()
+            .enumerate()
+            .filter(|(_, shelf)| {
+                shelf.height >= height && (!can_open || shelf.height <= height * 2)
+            })
+            .filter_map(|(index, shelf)| {
+                let span = shelf.free.iter().position(|&(_, free)| free >= width)?;
+                Some((shelf.height, index, span))
+            })
+            .min();
+
+        let (index, span) = match best {
+            Some((_, index, span)) => (index, span),
+            None if can_open => {
+                self.shelves.push(Shelf {
+                    y: self.shelves_bottom(),
+                    height: new_height,
+                    free: vec![(0, self.size)],
+                });
+                (self.shelves.len() - 1, 0)
+            }
+            None => return None,
+        };
+
+        let shelf = &mut self.shelves[index];
+        let (x, free) = shelf.free[span];
+        if free == width {
+            shelf.free.remove(span);
+        } else {
+            shelf.free[span] = (x + width, free - width);
+        }
+        Some((x, shelf.y))
+    }
+
+    /// Return a rectangle previously handed out by `allocate`
+    fn free(&mut self, x: u32, y: u32, width: u32) {
+        let Some(index) = self.shelves.iter().position(|shelf| shelf.y == y) else {
+            return;
+        };
+        let free = &mut self.shelves[index].free;
+        let at = free.partition_point(|&(span_x, _)| span_x < x);
+        free.insert(at, (x, width));
+
+        // Merge with the following and preceding spans
+        if at + 1 < free.len() && free[at].0 + free[at].1 == free[at + 1].0 {
+            free[at].1 += free[at + 1].1;
+            free.remove(at + 1);
+        }
+        if at > 0 && free[at - 1].0 + free[at - 1].1 == free[at].0 {
+            free[at - 1].1 += free[at].1;
+            free.remove(at);
+        }
+
+        // Empty shelves at the bottom give their rows back to the page
+        while self
+            .shelves
+            .last()
+            .is_some_and(|shelf| shelf.is_empty(self.size))
+        {
+            self.shelves.pop();
+        }
+    }
+
+    /// Enlarge the page, extending every shelf to the new width
+    fn grow(&mut self, size: u32) {
+        let old_size = self.size;
+        for shelf in &mut self.shelves {
+            match shelf.free.last_mut() {
+                Some((x, width)) if *x + *width == old_size => *width += size - old_size,
+                _ => shelf.free.push((old_size, size - old_size)),
+            }
+        }
+        self.size = size;
+    }
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    slot: AtlasSlot,
+    value: V,
+    last_used: u64,
+}
+
+/// Space allocation for a paged glyph atlas with LRU eviction
+///
+/// Each entry carries a small `value` for the renderer, such as the offset of
+/// the glyph image from the pen position.
+#[derive(Debug)]
+pub struct AtlasAllocator<K, V> {
+    initial_size: u32,
+    max_size: u32,
+    max_pages: usize,
+    pages: Vec<ShelfPacker>,
+    entries: HashMap<K, Entry<V>>,
+    frame: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl<K: Clone + Eq + Hash, V: Copy> AtlasAllocator<K, V> {
+    /// Create an allocator whose pages start at `initial_size` pixels square
+    /// and grow up to `max_size`, with at most `max_pages` pages
+    pub fn new(initial_size: u32, max_size: u32, max_pages: usize) -> Self {
+        Self {
+            initial_size: initial_size.min(max_size),
+            max_size,
+            max_pages: max_pages.max(1),
+            pages: Vec::new(),
+            entries: HashMap::new(),
+            frame: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Start a new frame; glyphs looked up from now on are protected from eviction
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Size of a page in pixels
+    pub fn page_size(&self, page: usize) -> u32 {
+        self.pages[page].size
+    }
+
+    /// Look up a glyph, marking it as used in this frame
+    pub fn get(&mut self, key: &K) -> Option<(AtlasSlot, V)> {
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = self.frame;
+                self.hits += 1;
+                Some((entry.slot, entry.value))
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Reserve space for a new glyph image
+    ///
+    /// Returns the slot and any page change the texture owner must apply
+    /// before uploading into it, or `None` if the glyph cannot be placed even
+    /// after evicting every glyph not used in this frame. Empty images get an
+    /// empty slot without taking any space.
+    pub fn insert(
+        &mut self,
+        key: K,
+        width: u32,
+        height: u32,
+        value: V,
+    ) -> Option<(AtlasSlot, Option<PageChange>)> {
+        let (slot, change) = if width == 0 || height == 0 {
+            let slot = AtlasSlot {
+                page: 0,
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            };
+            (slot, None)
+        } else {
+            self.allocate(width, height)?
+        };
+
+        if let Some(old) = self.entries.insert(
+            key,
+            Entry {
+                slot,
+                value,
+                last_used: self.frame,
+            },
+        ) {
+            self.release(&old.slot);
+        }
+        Some((slot, change))
+    }
+
+    /// Drop every glyph, keeping the pages
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        for page in &mut self.pages {
+            *page = ShelfPacker::new(page.size);
+        }
+    }
+
+    pub fn stats(&self) -> AtlasStats {
+        AtlasStats {
+            pages: self.pages.len(),
+            glyphs: self.entries.len(),
+            used_pixels: self
+                .entries
+                .values()
+                .map(|entry| entry.slot.width as u64 * entry.slot.height as u64)
+                .sum(),
+            capacity_pixels: self
+                .pages
+                .iter()
+                .map(|page| page.size as u64 * page.size as u64)
+                .sum(),
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+        }
+    }
+
+    fn slot_in(&mut self, page: usize, width: u32, height: u32) -> Option<AtlasSlot> {
+        let (x, y) = self.pages[page].allocate(width, height)?;
+        Some(AtlasSlot {
+            page,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(AtlasSlot, Option<PageChange>)> {
+        if width > self.max_size || height > self.max_size {
+            return None;
+        }
+
+        for page in 0..self.pages.len() {
+            if let Some(slot) = self.slot_in(page, width, height) {
+                return Some((slot, None));
+            }
+        }
+
+        // Grow the newest page; earlier pages are already at the maximum size
+        if let Some(page) = self.pages.len().checked_sub(1) {
+            while self.pages[page].size < self.max_size {
+                let size = (self.pages[page].size * 2).min(self.max_size);
+                self.pages[page].grow(size);
+                if let Some(slot) = self.slot_in(page, width, height) {
+                    return Some((slot, Some(PageChange::Grown { page, size })));
+                }
+            }
+        }
+
+        if self.pages.len() < self.max_pages {
+            let page = self.pages.len();
+            let mut size = self.initial_size;
+            while size < width.max(height) {
+                size = (size * 2).min(self.max_size);
+            }
+            self.pages.push(ShelfPacker::new(size));
+            let slot = self.slot_in(page, width, height)?;
+            return Some((slot, Some(PageChange::Added { page, size })));
+        }
+
+        self.evict_for(width, height).map(|slot| (slot, None))
+    }
+
+    /// Evict glyphs, least recently used first, until the image fits
+    fn evict_for(&mut self, width: u32, height: u32) -> Option<AtlasSlot> {
+        let mut candidates: Vec<(u64, K)> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.last_used < self.frame && entry.slot.width > 0)
+            .map(|(key, entry)| (entry.last_used, key.clone()))
+            .collect();
+        candidates.sort_by_key(|(last_used, _)| *last_used);
+
+        for (_, key) in candidates {
+            let Some(entry) = self.entries.remove(&key) else {
+                continue;
+            };
+            self.release(&entry.slot);
+            self.evictions += 1;
+            if let Some(slot) = self.slot_in(entry.slot.page, width, height) {
+                return Some(slot);
+            }
+        }
+        None
+    }
+
+    fn release(&mut self, slot: &AtlasSlot) {
+        if slot.width > 0 && slot.height > 0 {
+            self.pages[slot.page].free(slot.x, slot.y, slot.width);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocator(max_pages: usize) -> AtlasAllocator<u32, ()> {
+        AtlasAllocator::new(64, 128, max_pages)
+    }
+
+    #[test]
+    fn test_snap_subpixel() {
+        assert_eq!(snap_subpixel(10.0), (10.0, 0));
+        assert_eq!(snap_subpixel(10.3), (10.0, 1));
+        assert_eq!(snap_subpixel(10.5), (10.0, 2));
+        assert_eq!(snap_subpixel(10.9), (11.0, 0));
+    }
+
+    #[test]
+    fn test_shelf_packing_shares_rows() {
+        let mut atlas = allocator(1);
+        let (a, change) = atlas.insert(1, 10, 14, ()).unwrap();
+        assert_eq!(change, Some(PageChange::Added { page: 0, size: 64 }));
+        let (b, _) = atlas.insert(2, 10, 16, ()).unwrap();
+        let (c, _) = atlas.insert(3, 10, 30, ()).unwrap();
+
+        // 14 and 16 pixel glyphs share a 16 pixel shelf, the tall one starts a new shelf
+        assert_eq!((a.x, a.y), (0, 0));
+        assert_eq!((b.x, b.y), (10, 0));
+        assert_eq!((c.x, c.y), (0, 16));
+        assert_eq!(atlas.get(&2), Some((b, ())));
+        assert_eq!(atlas.get(&4), None);
+    }
+
+    #[test]
+    fn test_page_grows_then_pages_are_added() {
+        let mut atlas = allocator(2);
+        atlas.insert(1, 64, 64, ()).unwrap();
+
+        // The full 64px page doubles to the 128px maximum
+        let (slot, change) = atlas.insert(2, 64, 64, ()).unwrap();
+        assert_eq!(change, Some(PageChange::Grown { page: 0, size: 128 }));
+        assert_eq!((slot.page, slot.x, slot.y), (0, 64, 0));
+        atlas.insert(3, 128, 64, ()).unwrap();
+
+        let (slot, change) = atlas.insert(4, 32, 32, ()).unwrap();
+        assert_eq!(change, Some(PageChange::Added { page: 1, size: 64 }));
+        assert_eq!(slot.page, 1);
+        assert_eq!(atlas.stats().pages, 2);
+    }
+
+    #[test]
+    fn test_least_recently_used_glyphs_are_evicted() {
+        let mut atlas = AtlasAllocator::new(32, 32, 1);
+        for key in 0..4 {
+            atlas.begin_frame();
+            atlas.insert(key, 16, 16, ()).unwrap();
+        }
+
+        // Touch glyph 0 so that glyph 1 is the least recently used
+        atlas.begin_frame();
+        assert!(atlas.get(&0).is_some());
+        let (slot, change) = atlas.insert(4, 16, 16, ()).unwrap();
+        assert_eq!(change, None);
+        assert_eq!((slot.x, slot.y), (16, 0));
+        assert_eq!(atlas.get(&1), None);
+        assert!(atlas.get(&0).is_some());
+        assert_eq!(atlas.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_glyphs_used_this_frame_are_kept() {
+        let mut atlas = AtlasAllocator::new(32, 32, 1);
+        atlas.begin_frame();
+        for key in 0..4 {
+            atlas.insert(key, 16, 16, ()).unwrap();
+        }
+
+        // Everything on the page is in use, so nothing can be evicted
+        assert_eq!(atlas.insert(4, 16, 16, ()), None);
+        assert_eq!(atlas.stats().glyphs, 4);
+
+        // Next frame the oldest glyphs can go
+        atlas.begin_frame();
+        assert!(atlas.insert(4, 16, 16, ()).is_some());
+    }
+
+    #[test]
+    fn test_freed_space_is_reused() {
+        let mut atlas = AtlasAllocator::new(32, 32, 1);
+        atlas.begin_frame();
+        atlas.insert(0, 32, 16, ()).unwrap();
+        atlas.insert(1, 32, 16, ()).unwrap();
+
+        // Evicting the 32x16 glyph makes room for two 16x16 glyphs
+        atlas.begin_frame();
+        atlas.get(&1);
+        let (a, _) = atlas.insert(2, 16, 16, ()).unwrap();
+        let (b, _) = atlas.insert(3, 16, 16, ()).unwrap();
+        assert_eq!((a.x, a.y), (0, 0));
+        assert_eq!((b.x, b.y), (16, 0));
+        assert!(atlas.get(&1).is_some());
+    }
+
+    #[test]
+    fn test_empty_glyphs_take_no_space() {
+        let mut atlas = allocator(1);
+        let (slot, change) = atlas.insert(1, 0, 0, ()).unwrap();
+        assert_eq!((slot.width, slot.height, change), (0, 0, None));
+        assert_eq!(atlas.stats().pages, 0);
+        assert!(atlas.get(&1).is_some());
+    }
+
+    #[test]
+    fn test_oversized_glyphs_are_rejected() {
+        let mut atlas = allocator(4);
+        assert_eq!(atlas.insert(1, 129, 10, ()), None);
+    }
+
+    #[test]
+    fn test_stats_display() {
+        let mut atlas = allocator(1);
+        atlas.insert(1, 32, 32, ()).unwrap();
+        atlas.get(&1);
+        let stats = atlas.stats();
+        assert_eq!(stats.used_pixels, 32 * 32);
+        assert_eq!(
+            stats.to_string(),
+            "1 glyphs in 1 pages (25.0% used), 1 hits, 0 misses, 0 evictions"
+        );
+    }
+}
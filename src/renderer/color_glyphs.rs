@@ -0,0 +1,199 @@
+//! Color glyph (emoji) rasterization shared by the CPU and GPU renderers
+//!
+//! font-kit only produces coverage masks, so glyphs from color fonts (COLR
+//! layered outlines, CBDT and sbix bitmaps) are rendered with swash instead and
+//! scaled to fit the cells the character occupies.
+
+use font_kit::font::Font;
+use swash::FontRef;
+use swash::scale::image::Content;
+use swash::scale::{Render, ScaleContext, Source, StrikeWith};
+
+use crate::renderer::font::{CellMetrics, font_data};
+
+/// Font tables that carry color glyphs
+const COLOR_TABLES: [&[u8; 4]; 3] = [b"COLR", b"CBDT", b"sbix"];
+
+/// A premultiplied RGBA bitmap covering the cells of one color glyph
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorBitmap {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major premultiplied RGBA, 4 bytes per pixel
+    pub pixels: Vec<u8>,
+}
+
+impl ColorBitmap {
+    /// Premultiplied RGBA at (x, y)
+    pub fn get(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+}
+
+/// Whether a font has color glyphs (COLR, CBDT or sbix tables)
+pub fn is_color_font(font: &Font) -> bool {
+    COLOR_TABLES
+        .iter()
+        .any(|tag| font.load_font_table(u32::from_be_bytes(**tag)).is_some())
+}
+
+/// Render a color glyph into a box `cells` cells wide and one cell high
+///
+/// Returns `None` if the font has no color data for the glyph, in which case
+/// it should be drawn as an ordinary monochrome glyph.
+pub fn rasterize(
+    font: &Font,
+    glyph_id: u32,
+    metrics: &CellMetrics,
+    cells: usize,
+) -> Option<ColorBitmap> {
+    let (data, index) = font_data(font)?;
+    let font_ref = FontRef::from_index(&data, index as usize)?;
+
+    let mut context = ScaleContext::new();
+    let mut scaler = context
+        .builder(font_ref)
+        .size(metrics.font_size)
+        .hint(false)
+        .build();
+    let image = Render::new(&[
+        Source::ColorOutline(0),
+        Source::ColorBitmap(StrikeWith::BestFit),
+    ])
+    .render(&mut scaler, glyph_id as u16)?;
+    if image.content != Content::Color {
+        return None;
+    }
+
+    Some(fit_to_cells(
+        &image.data,
+        image.placement.width as usize,
+        image.placement.height as usize,
+        metrics,
+        cells,
+    ))
+}
+
+/// Fit a straight-alpha RGBA image into the cell box, premultiplying it
+///
+/// The image keeps its aspect ratio, is shrunk (never enlarged) to fit and
+/// centered. Shrinking averages each output pixel over its source area so
+/// large bitmap strikes stay smooth.
+fn fit_to_cells(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    metrics: &CellMetrics,
+    cells: usize,
+) -> ColorBitmap {
+    let box_width = (metrics.width * cells as f32).max(1.0) as usize;
+    let box_height = metrics.height.max(1.0) as usize;
+    let mut bitmap = ColorBitmap {
+        width: box_width,
+        height: box_height,
+        pixels: vec![0; box_width * box_height * 4],
+    };
+    if width == 0 || height == 0 || data.len() < width * height * 4 {
+        return bitmap;
+    }
+
+    let scale = (box_width as f32 / width as f32)
+        .min(box_height as f32 / height as f32)
+        .min(1.0);
+    let out_width = ((width as f32 * scale).round() as usize).clamp(1, box_width);
+    let out_height = ((height as f32 * scale).round() as usize).clamp(1, box_height);
+    let offset_x = (box_width - out_width) / 2;
+    let offset_y = (box_height - out_height) / 2;
+
+    for dy in 0..out_height {
+        let sy0 = dy * height / out_height;
+        let sy1 = ((dy + 1) * height / out_height).max(sy0 + 1);
+        for dx in 0..out_width {
+            let sx0 = dx * width / out_width;
+            let sx1 = ((dx + 1) * width / out_width).max(sx0 + 1);
+
+            let mut sum = [0u32; 4];
+            for sy in sy0..sy1 {
+                for sx in sx0..sx1 {
+                    let i = (sy * width + sx) * 4;
+                    let alpha = data[i + 3] as u32;
+                    sum[0] += data[i] as u32 * alpha / 255;
+                    sum[1] += data[i + 1] as u32 * alpha / 255;
+                    sum[2] += data[i + 2] as u32 * alpha / 255;
+                    sum[3] += alpha;
+                }
+            }
+
+            let count = ((sy1 - sy0) * (sx1 - sx0)) as u32;
+            let o = ((offset_y + dy) * box_width + offset_x + dx) * 4;
+            for (channel, total) in sum.iter().enumerate() {
+                bitmap.pixels[o + channel] = (total / count) as u8;
+            }
+        }
+    }
+    bitmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> CellMetrics {
+        CellMetrics {
+            font_size: 16.0,
+            width: 10.0,
+            height: 19.0,
+            baseline: 15.0,
+            underline_position: 16.0,
+            underline_thickness: 1.0,
+            strikeout_position: 10.0,
+        }
+    }
+
+    /// A solid straight-alpha RGBA image
+    fn solid(width: usize, height: usize, rgba: [u8; 4]) -> Vec<u8> {
+        rgba.iter()
+            .copied()
+            .cycle()
+            .take(width * height * 4)
+            .collect()
+    }
+
+    #[test]
+    fn test_color_glyph_spans_its_cells() {
+        let bitmap = fit_to_cells(&solid(16, 16, [255, 0, 0, 255]), 16, 16, &metrics(), 2);
+        assert_eq!((bitmap.width, bitmap.height), (20, 19));
+
+        // Centered: 16x16 fits unscaled, leaving 2px either side horizontally
+        assert_eq!(bitmap.get(1, 9), [0, 0, 0, 0]);
+        assert_eq!(bitmap.get(2, 9), [255, 0, 0, 255]);
+        assert_eq!(bitmap.get(17, 9), [255, 0, 0, 255]);
+        assert_eq!(bitmap.get(18, 9), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_large_bitmap_is_shrunk_to_fit() {
+        // A tall bitmap strike, much larger than the cells
+        let bitmap = fit_to_cells(&solid(128, 160, [0, 255, 0, 255]), 128, 160, &metrics(), 2);
+        let lit = bitmap.pixels.chunks(4).filter(|pixel| pixel[3] > 0).count();
+
+        // Limited by the 19px cell height, width keeps the aspect ratio
+        assert_eq!(lit, 15 * 19);
+        assert_eq!(bitmap.get(10, 9), [0, 255, 0, 255]);
+        assert_eq!(bitmap.get(0, 9), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_color_bitmap_is_premultiplied() {
+        let bitmap = fit_to_cells(&solid(4, 4, [200, 100, 50, 128]), 4, 4, &metrics(), 1);
+        let [r, g, b, a] = bitmap.get(4, 8);
+        assert_eq!(a, 128);
+        assert_eq!((r, g, b), (100, 50, 25));
+    }
+}
@@ -0,0 +1,546 @@
+//! In-memory framebuffer for the CPU renderer
+//!
+//! `Canvas` draws terminal state with raqote into a persistent draw target,
+//! independent of any window, and reports which pixel rows changed so the
+//! caller only has to copy and present those.
+
+use raqote::{DrawTarget, IntPoint, IntRect};
+use std::collections::HashMap;
+
+use super::drawing;
+use super::glyph_cache::GlyphCache;
+use crate::renderer::atlas::snap_subpixel;
+use crate::renderer::color_glyphs::{self, ColorBitmap};
+use crate::renderer::font::{CellMetrics, FontCollection, FontStyle};
+use crate::renderer::shaping::Shaper;
+use crate::terminal::{Cell, Color, UnderlineStyle};
+
+/// Persistent framebuffer the CPU renderer draws into
+///
+/// Needs no window, so it can also be drawn offscreen, e.g. in benchmarks.
+pub struct Canvas {
+    fonts: FontCollection,
+    shaper: Shaper,
+    metrics: CellMetrics,
+    /// Distance from the window edge to the grid, in physical pixels
+    padding_x: f32,
+    padding_y: f32,
+    /// Rasterized coverage masks of font and generated glyphs
+    glyph_cache: GlyphCache,
+    /// Rendered color glyphs (emoji), `None` for chars with no color data
+    color_glyphs: HashMap<char, Option<ColorBitmap>>,
+    /// Draw target matching the surface size, redrawn row by row as the grid is damaged
+    target: DrawTarget,
+    /// Premultiplied ARGB behind cells with the default background
+    background: u32,
+    /// Redraw every row on the next frame, e.g. after font or padding changes
+    full_redraw: bool,
+    /// Whether the window has keyboard focus; unfocused, the block cursor is hollow
+    focused: bool,
+    /// Viewport row the cursor was drawn on in the last frame
+    last_cursor_row: Option<usize>,
+    /// Blink phase the blinking text was drawn in in the last frame
+    last_text_blink_visible: bool,
+}
+
+impl Canvas {
+    /// Create a `width` x `height` canvas with cells sized for `font_size`
+    pub fn new(fonts: FontCollection, font_size: f32, width: u32, height: u32) -> Self {
+        let metrics = fonts.cell_metrics(font_size);
+        let shaper = Shaper::new(&fonts);
+        Self {
+            fonts,
+            shaper,
+            metrics,
+            padding_x: 10.0,
+            padding_y: 20.0,
+            glyph_cache: GlyphCache::default(),
+            color_glyphs: HashMap::new(),
+            target: DrawTarget::new(width as i32, height as i32),
+            background: background_pixel(1.0),
+            full_redraw: true,
+            focused: true,
+            last_cursor_row: None,
+            last_text_blink_visible: true,
+        }
+    }
+
+    pub fn metrics(&self) -> &CellMetrics {
+        &self.metrics
+    }
+
+    /// Canvas size in pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.target.width() as u32, self.target.height() as u32)
+    }
+
+    /// Pixels as premultiplied ARGB, row by row
+    pub fn data(&self) -> &[u32] {
+        self.target.get_data()
+    }
+
+    /// Match the surface size
+    ///
+    /// The draw target is only reallocated (and fully redrawn) when the size
+    /// actually changes.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if self.size() != (width, height) {
+            self.target = DrawTarget::new(width as i32, height as i32);
+            self.full_redraw = true;
+        }
+    }
+
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.metrics = self.fonts.cell_metrics(font_size);
+        self.color_glyphs.clear();
+        self.full_redraw = true;
+    }
+
+    /// Distance from the edges to the grid, in physical pixels
+    pub fn padding(&self) -> (f32, f32) {
+        (self.padding_x, self.padding_y)
+    }
+
+    pub fn set_padding(&mut self, x: f32, y: f32) {
+        self.padding_x = x;
+        self.padding_y = y;
+        self.full_redraw = true;
+    }
+
+    pub fn set_ligatures(&mut self, enabled: bool) {
+        self.shaper.set_enabled(enabled);
+        self.full_redraw = true;
+    }
+
+    /// Set the opacity of the default background, from 0.0 (transparent) to 1.0
+    ///
+    /// Cells with another background color stay opaque.
+    pub fn set_background_opacity(&mut self, opacity: f32) {
+        self.background = background_pixel(opacity);
+        self.full_redraw = true;
+    }
+
+    /// Follow the window's focus
+    ///
+    /// The cursor row is redrawn every frame, so the change shows on the next one.
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Bring the canvas up to date with `state`
+    ///
+    /// Only rows damaged in the grid, the rows the cursor left and entered, and
+    /// rows whose blinking text changed phase are redrawn. Returns the redrawn
+    /// bands of pixel rows as (top, bottom), merged where adjacent; after a
+    /// full redraw that is the whole canvas.
+    pub fn draw(
+        &mut self,
+        state: &crate::TerminalState,
+        cursor_visible: bool,
+        text_blink_visible: bool,
+    ) -> Vec<(u32, u32)> {
+        let (width, height) = (self.target.width(), self.target.height());
+        let full_redraw = std::mem::take(&mut self.full_redraw);
+        if full_redraw {
+            self.target.get_data_mut().fill(self.background);
+        }
+
+        // Cursor rows are screen-relative; hide the cursor when scrolled out of view
+        let cursor_viewport_row = (state.grid.screen_start() + state.cursor.row)
+            .checked_sub(state.grid.viewport_start)
+            .unwrap_or(usize::MAX);
+        let blink_changed = text_blink_visible != self.last_text_blink_visible;
+
+        let mut damage: Vec<(u32, u32)> = Vec::new();
+
+        let viewport = state.grid.get_viewport();
+        for (row, line) in viewport.iter().enumerate() {
+            let redraw = full_redraw
+                || state.grid.is_row_damaged(row)
+                || row == cursor_viewport_row
+                || Some(row) == self.last_cursor_row
+                || (blink_changed && line.iter().any(|cell| cell.blink));
+            if !redraw {
+                continue;
+            }
+
+            let y = self.padding_y + row as f32 * self.metrics.height;
+            let top = (y.round() as i32).clamp(0, height);
+            let bottom = ((y + self.metrics.height).round() as i32).clamp(0, height);
+            if top >= bottom {
+                continue;
+            }
+
+            // Keep glyphs that overhang the row from painting into rows not
+            // redrawn. The band is cleared directly: raqote clears a clip by
+            // filling a path over the whole target.
+            self.target.push_clip_rect(IntRect::new(
+                IntPoint::new(0, top),
+                IntPoint::new(width, bottom),
+            ));
+            self.target.get_data_mut()[(top * width) as usize..(bottom * width) as usize]
+                .fill(self.background);
+
+            // Ligatures never form across the cursor, even while it blinks off.
+            // A focused block cursor is drawn as its cell in inverted colors.
+            let on_cursor_row = row == cursor_viewport_row;
+            let cursor_col = (state.show_cursor && on_cursor_row).then_some(state.cursor.col);
+            let inverted_col = (cursor_visible
+                && on_cursor_row
+                && self.focused
+                && state.cursor.style == crate::CursorStyle::Block)
+                .then_some(state.cursor.col);
+            self.draw_row(
+                line,
+                y,
+                (top, bottom),
+                cursor_col,
+                inverted_col,
+                text_blink_visible,
+            );
+
+            if cursor_visible && row == cursor_viewport_row {
+                self.draw_cursor(state.cursor.col, y, state.cursor.style);
+            }
+            self.target.pop_clip();
+
+            let (top, bottom) = (top as u32, bottom as u32);
+            match damage.last_mut() {
+                Some((_, last_bottom)) if *last_bottom >= top => *last_bottom = bottom,
+                _ => damage.push((top, bottom)),
+            }
+        }
+
+        self.last_cursor_row =
+            (cursor_viewport_row < viewport.len()).then_some(cursor_viewport_row);
+        self.last_text_blink_visible = text_blink_visible;
+
+        if full_redraw {
+            vec![(0, height as u32)]
+        } else {
+            damage
+        }
+    }
+
+    /// Draw one viewport row into the frame at `y`
+    ///
+    /// Backgrounds go first, so glyphs reaching into neighbouring cells stay visible.
+    /// The cell in `inverted_col` is drawn with its colors swapped: its text in
+    /// the cell's background color on a block of its foreground color.
+    fn draw_row(
+        &mut self,
+        line: &[Cell],
+        y: f32,
+        clip: (i32, i32),
+        cursor_col: Option<usize>,
+        inverted_col: Option<usize>,
+        text_blink_visible: bool,
+    ) {
+        let offset_x = self.padding_x;
+        let dt = &mut self.target;
+
+        for (col, cell) in line.iter().enumerate() {
+            let x = offset_x + col as f32 * self.metrics.width;
+
+            // The second half of a double-width character is drawn with its first half
+            let covered = cell.wide_spacer && col > 0 && line[col - 1].wide;
+            let cells = if cell.wide && col + 1 < line.len() {
+                2
+            } else {
+                1
+            };
+
+            if inverted_col == Some(col) {
+                let cells = if covered { 1 } else { cells };
+                let Color { r, g, b } = cell.fg;
+                drawing::draw_background(dt, x, y, &self.metrics, cells, r, g, b);
+            } else if !covered && !cell.default_bg {
+                drawing::draw_background(
+                    dt,
+                    x,
+                    y,
+                    &self.metrics,
+                    cells,
+                    cell.bg.r,
+                    cell.bg.g,
+                    cell.bg.b,
+                );
+            }
+        }
+
+        let shaped = self
+            .shaper
+            .shape_line(line, cursor_col, &self.fonts, self.metrics.font_size);
+
+        for (col, cell) in line.iter().enumerate() {
+            let x = offset_x + col as f32 * self.metrics.width;
+            let covered = cell.wide_spacer && col > 0 && line[col - 1].wide;
+            let cells = if cell.wide && col + 1 < line.len() {
+                2
+            } else {
+                1
+            };
+
+            // Bold and italic select a font face; faint dims the color
+            let (mut r, mut g, mut b) = (cell.fg.r, cell.fg.g, cell.fg.b);
+            if inverted_col == Some(col) {
+                (r, g, b) = (cell.bg.r, cell.bg.g, cell.bg.b);
+            } else if cell.faint {
+                (r, g, b) = drawing::apply_faint(r, g, b);
+            }
+
+            // Concealed cells and blinking cells in their off phase show no text
+            let text_hidden = cell.conceal || (cell.blink && !text_blink_visible);
+
+            // Draw character: ligature runs are drawn from their shaped glyphs, box
+            // drawing and block glyphs are generated to fit the cell, others come
+            // from the fonts through the fallback chain. Glyph masks are cached and
+            // placed on whole pixels, rasterized at the pen's subpixel offset
+            if !covered && !text_hidden && cell.ch != ' ' && !cell.ch.is_control() {
+                if let Some(glyphs) = shaped.glyphs_at(col) {
+                    let style = FontStyle::from_attributes(cell.bold, cell.italic);
+                    for shaped_glyph in glyphs {
+                        let glyph = self.fonts.styled_glyph(style, shaped_glyph.glyph_id);
+                        let (pen_x, subpixel) = snap_subpixel(x + shaped_glyph.x);
+                        let pen_y = (y + shaped_glyph.y).round() as i32;
+                        let mask = self.glyph_cache.glyph(&glyph, &self.metrics, subpixel);
+                        let (mask_x, mask_y) = (pen_x as i32 + mask.left, pen_y + mask.top);
+                        drawing::draw_mask(dt, mask, mask_x, mask_y, clip, r, g, b);
+                    }
+                } else if let Some(mask) = self.glyph_cache.custom(cell.ch, &self.metrics) {
+                    let (mask_x, mask_y) = (x.round() as i32, y.round() as i32);
+                    drawing::draw_mask(dt, mask, mask_x, mask_y, clip, r, g, b);
+                } else {
+                    let style = FontStyle::from_attributes(cell.bold, cell.italic);
+                    let metrics = &self.metrics;
+                    let glyph = self.fonts.resolve(cell.ch, style, metrics);
+
+                    // Emoji are drawn in their own colors across their cells
+                    let color_bitmap = match &glyph {
+                        Some(glyph) if glyph.color => self
+                            .color_glyphs
+                            .entry(cell.ch)
+                            .or_insert_with(|| {
+                                color_glyphs::rasterize(glyph.font, glyph.glyph_id, metrics, cells)
+                            })
+                            .as_ref(),
+                        _ => None,
+                    };
+
+                    if let Some(bitmap) = color_bitmap {
+                        drawing::draw_color_bitmap(dt, bitmap, x, y);
+                    } else if let Some(glyph) = glyph {
+                        let (pen_x, subpixel) = snap_subpixel(x);
+                        let mask = self.glyph_cache.glyph(&glyph, metrics, subpixel);
+                        let (mask_x, mask_y) =
+                            (pen_x as i32 + mask.left, y.round() as i32 + mask.top);
+                        drawing::draw_mask(dt, mask, mask_x, mask_y, clip, r, g, b);
+                    }
+                }
+            }
+
+            // Draw decorations (also on blank cells, e.g. underlined spaces)
+            if !text_hidden {
+                // Underlines use the SGR 58 color when set
+                let (ur, ug, ub) = cell
+                    .underline_color
+                    .map_or((r, g, b), |color| (color.r, color.g, color.b));
+                let metrics = &self.metrics;
+                match cell.underline {
+                    UnderlineStyle::None => {}
+                    UnderlineStyle::Single => {
+                        drawing::draw_underline(dt, x, y, metrics, ur, ug, ub);
+                    }
+                    UnderlineStyle::Double => {
+                        drawing::draw_double_underline(dt, x, y, metrics, ur, ug, ub);
+                    }
+                    UnderlineStyle::Curly => {
+                        drawing::draw_curly_underline(dt, x, y, metrics, ur, ug, ub);
+                    }
+                    UnderlineStyle::Dotted => {
+                        drawing::draw_dotted_underline(dt, x, y, metrics, ur, ug, ub);
+                    }
+                    UnderlineStyle::Dashed => {
+                        drawing::draw_dashed_underline(dt, x, y, metrics, ur, ug, ub);
+                    }
+                }
+                if cell.strikethrough {
+                    drawing::draw_strikethrough(dt, x, y, metrics, r, g, b);
+                }
+                if cell.overline {
+                    drawing::draw_overline(dt, x, y, metrics, r, g, b);
+                }
+            }
+        }
+    }
+
+    /// Draw the cursor in column `col` of the row at `y`
+    ///
+    /// A focused block cursor has already been drawn with its row.
+    fn draw_cursor(&mut self, col: usize, y: f32, style: crate::CursorStyle) {
+        use crate::CursorStyle;
+
+        let x = self.padding_x + col as f32 * self.metrics.width;
+        match style {
+            CursorStyle::Block if self.focused => {}
+            CursorStyle::Block => {
+                drawing::draw_hollow_cursor(&mut self.target, x, y, &self.metrics)
+            }
+            CursorStyle::Underline => {
+                drawing::draw_underline_cursor(&mut self.target, x, y, &self.metrics)
+            }
+            CursorStyle::Bar => drawing::draw_bar_cursor(&mut self.target, x, y, &self.metrics),
+        }
+    }
+}
+
+/// The default background color at `opacity`, as premultiplied ARGB
+fn background_pixel(opacity: f32) -> u32 {
+    let alpha = opacity.clamp(0.0, 1.0);
+    let Color { r, g, b } = Color::default_background();
+    let channel = |c: u8| (c as f32 * alpha).round() as u32;
+    ((alpha * 255.0).round() as u32) << 24 | channel(r) << 16 | channel(g) << 8 | channel(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Terminal;
+
+    /// A canvas without padding, or `None` when no monospace font is installed
+    fn canvas(width: u32, height: u32) -> Option<Canvas> {
+        let fonts = match FontCollection::load(&FontCollection::default_families()) {
+            Ok(fonts) => fonts,
+            Err(e) => {
+                eprintln!("Skipping canvas test, no font available: {}", e);
+                return None;
+            }
+        };
+        let mut canvas = Canvas::new(fonts, 16.0, width, height);
+        canvas.set_padding(0.0, 0.0);
+        Some(canvas)
+    }
+
+    fn pixel(canvas: &Canvas, x: u32, y: u32) -> u32 {
+        canvas.data()[(y * canvas.size().0 + x) as usize]
+    }
+
+    #[test]
+    fn test_canvas_follows_surface_size() {
+        let Some(mut canvas) = canvas(800, 600) else {
+            return;
+        };
+        canvas.resize(1600, 900);
+        assert_eq!(canvas.size(), (1600, 900));
+        assert_eq!(canvas.data().len(), 1600 * 900);
+
+        // A red cell well past the old hardcoded 800px width
+        let metrics = *canvas.metrics();
+        let col = (1000.0 / metrics.width) as usize;
+        let mut terminal = Terminal::new(col + 2, 4);
+        terminal.process_bytes(format!("\x1b[2;{}H\x1b[41m \x1b[0m", col + 1).as_bytes());
+
+        let damage = canvas.draw(terminal.state(), false, true);
+        assert_eq!(damage, vec![(0, 900)]);
+
+        let x = (col as f32 * metrics.width + metrics.width / 2.0) as u32;
+        let y = (metrics.height * 1.5) as u32;
+        assert_ne!(pixel(&canvas, x, y) & 0x00ff_ffff, 0);
+        assert_eq!(
+            pixel(&canvas, x, y - metrics.height as u32) & 0x00ff_ffff,
+            0
+        );
+    }
+
+    #[test]
+    fn test_canvas_reallocates_only_on_size_change() {
+        let Some(mut canvas) = canvas(640, 480) else {
+            return;
+        };
+        let mut terminal = Terminal::new(10, 4);
+        canvas.draw(terminal.state(), false, true);
+        terminal.state_mut().grid.clear_damage();
+        let data = canvas.data().as_ptr();
+
+        canvas.resize(640, 480);
+        assert_eq!(canvas.data().as_ptr(), data);
+
+        // An unchanged size keeps the frame, so only the cursor row is redrawn
+        let damage = canvas.draw(terminal.state(), true, true);
+        let height = canvas.metrics().height as u32;
+        assert_eq!(damage, vec![(0, height)]);
+
+        canvas.resize(320, 240);
+        assert_eq!(canvas.size(), (320, 240));
+        assert_eq!(canvas.draw(terminal.state(), true, true), vec![(0, 240)]);
+    }
+
+    #[test]
+    fn test_block_cursor_inverts_its_cell() {
+        let Some(mut canvas) = canvas(200, 100) else {
+            return;
+        };
+        let mut terminal = Terminal::new(10, 4);
+        // Left half block under the cursor
+        terminal.process_bytes("\u{258c}\r".as_bytes());
+        canvas.draw(terminal.state(), true, true);
+
+        let metrics = *canvas.metrics();
+        let y = (metrics.height / 2.0) as u32;
+        assert_eq!(
+            pixel(&canvas, (metrics.width * 0.25) as u32, y),
+            0xff00_0000
+        );
+        assert_eq!(
+            pixel(&canvas, (metrics.width * 0.75) as u32, y),
+            0xffff_ffff
+        );
+    }
+
+    #[test]
+    fn test_block_cursor_swaps_cell_colors() {
+        let Some(mut canvas) = canvas(200, 100) else {
+            return;
+        };
+        let metrics = *canvas.metrics();
+        let y = (metrics.height / 2.0) as u32;
+        let (left, right) = ((metrics.width * 0.25) as u32, (metrics.width * 0.75) as u32);
+
+        // Reverse video: white text background, black glyph
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes("\x1b[7m\u{258c}\x1b[0m\r".as_bytes());
+        canvas.draw(terminal.state(), true, true);
+        assert_eq!(pixel(&canvas, left, y), 0xffff_ffff);
+        assert_eq!(pixel(&canvas, right, y), 0xff00_0000);
+
+        // Colored text: a block in the text color
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes("\x1b[32m\u{258c}\x1b[0m\r".as_bytes());
+        canvas.draw(terminal.state(), true, true);
+        let Color { r, g, b } = Color::from_ansi_index(2);
+        let green = 0xff00_0000 | (r as u32) << 16 | (g as u32) << 8 | b as u32;
+        assert_eq!(pixel(&canvas, left, y), 0xff00_0000);
+        assert_eq!(pixel(&canvas, right, y), green);
+    }
+
+    #[test]
+    fn test_unfocused_block_cursor_is_hollow() {
+        let Some(mut canvas) = canvas(200, 100) else {
+            return;
+        };
+        let mut terminal = Terminal::new(10, 4);
+        canvas.set_focused(false);
+        canvas.draw(terminal.state(), true, true);
+
+        let metrics = *canvas.metrics();
+        let (middle_x, middle_y) = ((metrics.width / 2.0) as u32, (metrics.height / 2.0) as u32);
+        assert_eq!(pixel(&canvas, middle_x, middle_y), 0xff00_0000);
+        assert_eq!(pixel(&canvas, 0, middle_y), 0xffff_ffff);
+        assert_eq!(pixel(&canvas, middle_x, 0), 0xffff_ffff);
+
+        canvas.set_focused(true);
+        terminal.state_mut().grid.clear_damage();
+        canvas.draw(terminal.state(), true, true);
+        assert_eq!(pixel(&canvas, middle_x, middle_y), 0xffff_ffff);
+    }
+}
@@ -1,128 +1,313 @@
 //! Drawing primitives for CPU rendering
 //!
 //! Helper functions for drawing backgrounds, cursor shapes, and text decorations.
+//! All positions are the top-left corner of a cell; vertical placement inside
+//! the cell comes from the font's `CellMetrics`.
 
 use raqote::{DrawOptions, DrawTarget, Path, PathOp, Point, SolidSource, Source, StrokeStyle};
 
-/// Draw a solid background rectangle
+use crate::renderer::color_glyphs::ColorBitmap;
+use crate::renderer::font::CellMetrics;
+use crate::renderer::raster::GlyphMask;
+
+fn solid(r: u8, g: u8, b: u8) -> Source<'static> {
+    Source::Solid(SolidSource::from_unpremultiplied_argb(0xff, r, g, b))
+}
+
+/// Fill an axis-aligned rectangle
+fn fill_rect(dt: &mut DrawTarget, x: f32, y: f32, width: f32, height: f32, source: &Source) {
+    let rect = Path {
+        ops: vec![
+            PathOp::MoveTo(Point::new(x, y)),
+            PathOp::LineTo(Point::new(x + width, y)),
+            PathOp::LineTo(Point::new(x + width, y + height)),
+            PathOp::LineTo(Point::new(x, y + height)),
+            PathOp::Close,
+        ],
+        winding: raqote::Winding::NonZero,
+    };
+    dt.fill(&rect, source, &DrawOptions::new());
+}
+
+/// Draw a solid background rectangle `cells` cells wide
+#[allow(clippy::too_many_arguments)]
 pub(super) fn draw_background(
     dt: &mut DrawTarget,
     x: f32,
     y: f32,
-    width: f32,
+    metrics: &CellMetrics,
+    cells: usize,
     r: u8,
     g: u8,
     b: u8,
 ) {
-    let bg_rect = Path {
-        ops: vec![
-            PathOp::MoveTo(Point::new(x, y - 15.0)),
-            PathOp::LineTo(Point::new(x + width, y - 15.0)),
-            PathOp::LineTo(Point::new(x + width, y + 5.0)),
-            PathOp::LineTo(Point::new(x, y + 5.0)),
-            PathOp::Close,
-        ],
-        winding: raqote::Winding::NonZero,
-    };
-    dt.fill(
-        &bg_rect,
-        &Source::Solid(SolidSource::from_unpremultiplied_argb(0xff, r, g, b)),
-        &DrawOptions::new(),
-    );
+    let width = metrics.width * cells as f32;
+    fill_rect(dt, x, y, width, metrics.height, &solid(r, g, b));
 }
 
-/// Draw underline beneath text
-pub(super) fn draw_underline(dt: &mut DrawTarget, x: f32, y: f32, width: f32, r: u8, g: u8, b: u8) {
-    let underline_y = y + 2.0;
-    let underline_path = Path {
-        ops: vec![
-            PathOp::MoveTo(Point::new(x, underline_y)),
-            PathOp::LineTo(Point::new(x + width, underline_y)),
-        ],
-        winding: raqote::Winding::NonZero,
-    };
-    dt.stroke(
-        &underline_path,
-        &Source::Solid(SolidSource::from_unpremultiplied_argb(0xff, r, g, b)),
-        &StrokeStyle {
-            width: 1.0,
-            ..Default::default()
-        },
-        &DrawOptions::new(),
-    );
+/// Blend a coverage mask into the target in a solid color
+///
+/// The mask's top-left corner lands on the whole pixel (x, y). Only pixel rows
+/// `clip_top..clip_bottom` are touched, so glyphs overhanging the row being
+/// redrawn don't paint over rows that are not.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn draw_mask(
+    dt: &mut DrawTarget,
+    mask: &GlyphMask,
+    x: i32,
+    y: i32,
+    (clip_top, clip_bottom): (i32, i32),
+    r: u8,
+    g: u8,
+    b: u8,
+) {
+    let (width, height) = (dt.width(), dt.height());
+    let rows = (y.max(clip_top).max(0))..((y + mask.height as i32).min(clip_bottom).min(height));
+    let cols = x.max(0)..(x + mask.width as i32).min(width);
+    if rows.is_empty() || cols.is_empty() {
+        return;
+    }
+
+    let color = [b as u32, g as u32, r as u32, 0xff];
+    let data = dt.get_data_mut();
+    for row in rows {
+        let mask_row = &mask.pixels[(row - y) as usize * mask.width..];
+        let line = &mut data[(row * width) as usize..][..width as usize];
+        for col in cols.clone() {
+            let coverage = mask_row[(col - x) as usize] as u32;
+            if coverage == 0 {
+                continue;
+            }
+
+            // Source over with premultiplied ARGB, channel by channel
+            let pixel = &mut line[col as usize];
+            let mut blended = 0;
+            for (channel, source) in color.iter().enumerate() {
+                let shift = channel * 8;
+                let dest = (*pixel >> shift) & 0xff;
+                let value = (source * coverage + dest * (255 - coverage) + 127) / 255;
+                blended |= value << shift;
+            }
+            *pixel = blended;
+        }
+    }
 }
 
-/// Draw block cursor
-pub(super) fn draw_block_cursor(dt: &mut DrawTarget, x: f32, y: f32, width: f32) {
-    let cursor_rect = Path {
-        ops: vec![
-            PathOp::MoveTo(Point::new(x, y - 15.0)),
-            PathOp::LineTo(Point::new(x + width, y - 15.0)),
-            PathOp::LineTo(Point::new(x + width, y + 5.0)),
-            PathOp::LineTo(Point::new(x, y + 5.0)),
-            PathOp::Close,
-        ],
-        winding: raqote::Winding::NonZero,
+/// Draw a color glyph (emoji) in its own colors
+pub(super) fn draw_color_bitmap(dt: &mut DrawTarget, bitmap: &ColorBitmap, x: f32, y: f32) {
+    // Premultiplied RGBA to premultiplied ARGB
+    let data: Vec<u32> = bitmap
+        .pixels
+        .chunks_exact(4)
+        .map(|pixel| {
+            let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]].map(u32::from);
+            (a << 24) | (r << 16) | (g << 8) | b
+        })
+        .collect();
+    let image = raqote::Image {
+        width: bitmap.width as i32,
+        height: bitmap.height as i32,
+        data: &data,
     };
-    dt.fill(
-        &cursor_rect,
-        &Source::Solid(SolidSource::from_unpremultiplied_argb(0xff, 255, 255, 255)),
-        &DrawOptions::new(),
-    );
+    dt.draw_image_at(x.round(), y.round(), &image, &DrawOptions::new());
 }
 
-/// Draw underline cursor
-pub(super) fn draw_underline_cursor(dt: &mut DrawTarget, x: f32, y: f32, width: f32) {
-    let underline_y = y + 3.0;
-    let underline_path = Path {
+/// Stroke a horizontal decoration line across a cell
+///
+/// `top` is the top edge of the line. A non-empty `dash_array` is phased by `x`
+/// so dashes line up across adjacent cells.
+fn draw_horizontal_line(
+    dt: &mut DrawTarget,
+    x: f32,
+    top: f32,
+    metrics: &CellMetrics,
+    dash_array: Vec<f32>,
+    source: &Source,
+) {
+    let thickness = metrics.underline_thickness;
+    let period: f32 = dash_array.iter().sum();
+    let dash_offset = if period > 0.0 { x % period } else { 0.0 };
+    let center_y = top + thickness / 2.0;
+    let line_path = Path {
         ops: vec![
-            PathOp::MoveTo(Point::new(x, underline_y)),
-            PathOp::LineTo(Point::new(x + width, underline_y)),
+            PathOp::MoveTo(Point::new(x, center_y)),
+            PathOp::LineTo(Point::new(x + metrics.width, center_y)),
         ],
         winding: raqote::Winding::NonZero,
     };
     dt.stroke(
-        &underline_path,
-        &Source::Solid(SolidSource::from_unpremultiplied_argb(0xff, 255, 255, 255)),
+        &line_path,
+        source,
         &StrokeStyle {
-            width: 2.0,
+            width: thickness,
+            dash_array,
+            dash_offset,
             ..Default::default()
         },
         &DrawOptions::new(),
     );
 }
 
-/// Draw bar cursor
-pub(super) fn draw_bar_cursor(dt: &mut DrawTarget, x: f32, y: f32) {
-    let bar_path = Path {
+/// Draw underline beneath text
+pub(super) fn draw_underline(
+    dt: &mut DrawTarget,
+    x: f32,
+    y: f32,
+    metrics: &CellMetrics,
+    r: u8,
+    g: u8,
+    b: u8,
+) {
+    let top = y + metrics.underline_position;
+    draw_horizontal_line(dt, x, top, metrics, Vec::new(), &solid(r, g, b));
+}
+
+/// Draw double underline beneath text
+///
+/// The second line sits one line thickness below the first, clamped to the cell.
+pub(super) fn draw_double_underline(
+    dt: &mut DrawTarget,
+    x: f32,
+    y: f32,
+    metrics: &CellMetrics,
+    r: u8,
+    g: u8,
+    b: u8,
+) {
+    let source = solid(r, g, b);
+    let thickness = metrics.underline_thickness;
+    let second = (metrics.underline_position + thickness * 2.0).min(metrics.height - thickness);
+    let first = (second - thickness * 2.0).max(metrics.baseline);
+    draw_horizontal_line(dt, x, y + first, metrics, Vec::new(), &source);
+    draw_horizontal_line(dt, x, y + second, metrics, Vec::new(), &source);
+}
+
+/// Draw wavy underline (undercurl) beneath text
+///
+/// Each cell holds one full wave period, so waves join up across cells.
+pub(super) fn draw_curly_underline(
+    dt: &mut DrawTarget,
+    x: f32,
+    y: f32,
+    metrics: &CellMetrics,
+    r: u8,
+    g: u8,
+    b: u8,
+) {
+    let thickness = metrics.underline_thickness;
+    let amplitude = (thickness * 1.5).max(1.5);
+    // Keep the whole wave inside the cell
+    let wave_y = y
+        + (metrics.underline_position + thickness / 2.0)
+            .min(metrics.height - amplitude - thickness / 2.0);
+    let width = metrics.width;
+    let curl_path = Path {
         ops: vec![
-            PathOp::MoveTo(Point::new(x, y - 15.0)),
-            PathOp::LineTo(Point::new(x, y + 5.0)),
+            PathOp::MoveTo(Point::new(x, wave_y)),
+            PathOp::QuadTo(
+                Point::new(x + width * 0.25, wave_y - amplitude * 2.0),
+                Point::new(x + width * 0.5, wave_y),
+            ),
+            PathOp::QuadTo(
+                Point::new(x + width * 0.75, wave_y + amplitude * 2.0),
+                Point::new(x + width, wave_y),
+            ),
         ],
         winding: raqote::Winding::NonZero,
     };
     dt.stroke(
-        &bar_path,
-        &Source::Solid(SolidSource::from_unpremultiplied_argb(0xff, 255, 255, 255)),
+        &curl_path,
+        &solid(r, g, b),
         &StrokeStyle {
-            width: 2.0,
+            width: thickness,
             ..Default::default()
         },
         &DrawOptions::new(),
     );
 }
 
-/// Apply bold effect by brightening colors
-pub(super) fn apply_bold(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
-    let brighten = |c: u8| -> u8 { ((c as u16 * 3 / 2).min(255)) as u8 };
-    (brighten(r), brighten(g), brighten(b))
+/// Draw dotted underline beneath text
+pub(super) fn draw_dotted_underline(
+    dt: &mut DrawTarget,
+    x: f32,
+    y: f32,
+    metrics: &CellMetrics,
+    r: u8,
+    g: u8,
+    b: u8,
+) {
+    let dot = metrics.underline_thickness;
+    let top = y + metrics.underline_position;
+    draw_horizontal_line(dt, x, top, metrics, vec![dot, dot], &solid(r, g, b));
+}
+
+/// Draw dashed underline beneath text
+pub(super) fn draw_dashed_underline(
+    dt: &mut DrawTarget,
+    x: f32,
+    y: f32,
+    metrics: &CellMetrics,
+    r: u8,
+    g: u8,
+    b: u8,
+) {
+    let dash = (metrics.width / 3.0).max(2.0);
+    let top = y + metrics.underline_position;
+    draw_horizontal_line(dt, x, top, metrics, vec![dash, dash / 2.0], &solid(r, g, b));
+}
+
+/// Draw strikethrough line through the middle of the text
+pub(super) fn draw_strikethrough(
+    dt: &mut DrawTarget,
+    x: f32,
+    y: f32,
+    metrics: &CellMetrics,
+    r: u8,
+    g: u8,
+    b: u8,
+) {
+    let top = y + metrics.strikeout_position;
+    draw_horizontal_line(dt, x, top, metrics, Vec::new(), &solid(r, g, b));
+}
+
+/// Draw overline along the top of the cell
+pub(super) fn draw_overline(
+    dt: &mut DrawTarget,
+    x: f32,
+    y: f32,
+    metrics: &CellMetrics,
+    r: u8,
+    g: u8,
+    b: u8,
+) {
+    draw_horizontal_line(dt, x, y, metrics, Vec::new(), &solid(r, g, b));
+}
+
+/// Draw the outline of a block cursor, shown while the window is unfocused
+pub(super) fn draw_hollow_cursor(dt: &mut DrawTarget, x: f32, y: f32, metrics: &CellMetrics) {
+    let thickness = metrics.underline_thickness.max(1.0);
+    let (width, height) = (metrics.width, metrics.height);
+    let white = solid(255, 255, 255);
+    fill_rect(dt, x, y, width, thickness, &white);
+    fill_rect(dt, x, y + height - thickness, width, thickness, &white);
+    fill_rect(dt, x, y, thickness, height, &white);
+    fill_rect(dt, x + width - thickness, y, thickness, height, &white);
+}
+
+/// Draw underline cursor
+pub(super) fn draw_underline_cursor(dt: &mut DrawTarget, x: f32, y: f32, metrics: &CellMetrics) {
+    let thickness = (metrics.underline_thickness * 2.0).max(2.0);
+    let top = y + metrics.height - thickness;
+    fill_rect(dt, x, top, metrics.width, thickness, &solid(255, 255, 255));
+}
+
+/// Draw bar cursor
+pub(super) fn draw_bar_cursor(dt: &mut DrawTarget, x: f32, y: f32, metrics: &CellMetrics) {
+    let thickness = (metrics.underline_thickness * 2.0).max(2.0);
+    fill_rect(dt, x, y, thickness, metrics.height, &solid(255, 255, 255));
 }
 
-/// Apply italic effect by adding cyan tint
-pub(super) fn apply_italic(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
-    (
-        r,
-        ((g as u16 + 30).min(255)) as u8,
-        ((b as u16 + 30).min(255)) as u8,
-    )
+/// Apply faint effect by halving intensity
+pub(super) fn apply_faint(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+    (r / 2, g / 2, b / 2)
 }
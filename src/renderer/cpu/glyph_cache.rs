@@ -0,0 +1,138 @@
+//! Rasterized glyph cache for the CPU renderer
+//!
+//! The CPU counterpart of the GPU `GlyphAtlas`: coverage masks are rasterized
+//! once per glyph, face, size and subpixel position, then blended into the
+//! frame in each cell's text color. Masks live in plain memory, so there is
+//! no packing; the cache is simply dropped when it grows too large.
+
+use std::collections::HashMap;
+
+use crate::renderer::atlas::GlyphKey;
+use crate::renderer::custom_glyphs;
+use crate::renderer::font::{CellMetrics, ResolvedGlyph};
+use crate::renderer::raster::{self, GlyphMask};
+
+/// The cache is dropped when it grows past this many masks
+const CACHE_LIMIT: usize = 8192;
+
+#[derive(Default)]
+pub(super) struct GlyphCache {
+    masks: HashMap<GlyphKey, GlyphMask>,
+    /// Generated glyphs, `None` for chars in the custom ranges left to the fonts
+    custom: HashMap<GlyphKey, Option<GlyphMask>>,
+}
+
+impl GlyphCache {
+    /// Number of cached masks
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.masks.len() + self.custom.len()
+    }
+
+    /// Mask of a font glyph drawn at horizontal subpixel step `subpixel`,
+    /// rasterizing it on first use
+    pub fn glyph(
+        &mut self,
+        glyph: &ResolvedGlyph,
+        metrics: &CellMetrics,
+        subpixel: u8,
+    ) -> &GlyphMask {
+        let key = GlyphKey {
+            face: Some(glyph.face),
+            glyph_id: glyph.glyph_id,
+            size: glyph.font_size(metrics).to_bits(),
+            subpixel,
+            synthetic_bold: glyph.synthetic_bold,
+            synthetic_italic: glyph.synthetic_italic,
+        };
+        make_room(&mut self.masks, &key);
+        self.masks.entry(key).or_insert_with(|| {
+            raster::rasterize(glyph, metrics, subpixel).unwrap_or_else(|e| {
+                eprintln!("[FONT] Failed to rasterize glyph {}: {}", glyph.glyph_id, e);
+                GlyphMask::default()
+            })
+        })
+    }
+
+    /// Mask of a box drawing, block, braille or Powerline glyph generated to
+    /// fit the cell, or `None` if `ch` isn't one
+    pub fn custom(&mut self, ch: char, metrics: &CellMetrics) -> Option<&GlyphMask> {
+        if !custom_glyphs::is_custom_glyph(ch) {
+            return None;
+        }
+
+        // Custom glyphs are keyed by character, cell-sized and pixel aligned
+        let key = GlyphKey {
+            face: None,
+            glyph_id: ch as u32,
+            size: metrics.font_size.to_bits(),
+            subpixel: 0,
+            synthetic_bold: false,
+            synthetic_italic: false,
+        };
+        make_room(&mut self.custom, &key);
+        self.custom
+            .entry(key)
+            .or_insert_with(|| {
+                custom_glyphs::rasterize(ch, metrics).map(|bitmap| GlyphMask {
+                    width: bitmap.width,
+                    height: bitmap.height,
+                    pixels: bitmap.pixels,
+                    left: 0,
+                    top: 0,
+                })
+            })
+            .as_ref()
+    }
+}
+
+/// Drop a cache before a miss would grow it past `CACHE_LIMIT`
+fn make_room<V>(cache: &mut HashMap<GlyphKey, V>, key: &GlyphKey) {
+    if cache.len() >= CACHE_LIMIT && !cache.contains_key(key) {
+        cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> CellMetrics {
+        CellMetrics {
+            font_size: 16.0,
+            width: 10.0,
+            height: 19.0,
+            baseline: 15.0,
+            underline_position: 16.0,
+            underline_thickness: 1.0,
+            strikeout_position: 10.0,
+        }
+    }
+
+    #[test]
+    fn test_custom_glyphs_are_cached_per_size() {
+        let mut cache = GlyphCache::default();
+        let full_block = cache.custom('█', &metrics()).cloned().unwrap();
+        assert_eq!((full_block.width, full_block.height), (10, 19));
+        assert!(full_block.pixels.iter().all(|&coverage| coverage == 255));
+
+        cache.custom('█', &metrics());
+        assert_eq!(cache.len(), 1);
+
+        let larger = CellMetrics {
+            font_size: 20.0,
+            width: 12.0,
+            height: 24.0,
+            ..metrics()
+        };
+        cache.custom('█', &larger);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_other_chars_are_not_custom() {
+        let mut cache = GlyphCache::default();
+        assert!(cache.custom('a', &metrics()).is_none());
+        assert_eq!(cache.len(), 0);
+    }
+}
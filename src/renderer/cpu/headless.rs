@@ -0,0 +1,396 @@
+//! Offscreen renderer drawing into an in-memory image
+//!
+//! `HeadlessRenderer` draws with the same canvas as the CPU renderer but needs
+//! no window or display, so frames can be saved as screenshots or compared
+//! against golden images in tests.
+
+use anyhow::{Context as _, Result, bail};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use super::Canvas;
+use crate::renderer::font::FontCollection;
+
+/// An image with 8-bit RGBA pixels (not premultiplied), row by row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    /// Four bytes per pixel
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Convert premultiplied ARGB pixels as drawn by raqote
+    fn from_argb(width: u32, height: u32, data: &[u32]) -> Self {
+        let mut pixels = Vec::with_capacity(data.len() * 4);
+        for &pixel in data {
+            let a = pixel >> 24;
+            // Rounded to the nearest value; fully transparent pixels are black
+            let unpremultiply =
+                |c: u32| ((c & 0xff) * 255 + a / 2).checked_div(a).unwrap_or(0) as u8;
+            pixels.extend_from_slice(&[
+                unpremultiply(pixel >> 16),
+                unpremultiply(pixel >> 8),
+                unpremultiply(pixel),
+                a as u8,
+            ]);
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// RGBA of the pixel at `(x, y)`
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y * self.width + x) as usize * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    /// Encode as an 8-bit RGBA PNG
+    pub fn write_png(&self, writer: impl Write) -> Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        self.write_png(BufWriter::new(file))
+    }
+
+    /// Decode an 8-bit RGBA PNG, as written by `write_png`
+    pub fn read_png(reader: impl Read) -> Result<Self> {
+        let mut reader = png::Decoder::new(reader).read_info()?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut pixels)?;
+        if frame.color_type != png::ColorType::Rgba || frame.bit_depth != png::BitDepth::Eight {
+            bail!(
+                "Expected an 8-bit RGBA PNG, got {:?} at {:?}",
+                frame.color_type,
+                frame.bit_depth
+            );
+        }
+        pixels.truncate(frame.buffer_size());
+        Ok(Self {
+            width: frame.width,
+            height: frame.height,
+            pixels,
+        })
+    }
+
+    pub fn load_png(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Self::read_png(BufReader::new(file))
+    }
+}
+
+/// Renderer drawing into an in-memory image instead of a window
+///
+/// ```no_run
+/// use rustty::Terminal;
+/// use rustty::renderer::{DEFAULT_FONT_SIZE, FontCollection, HeadlessRenderer, Renderer};
+///
+/// let mut terminal = Terminal::new(80, 24);
+/// terminal.process_bytes(b"\x1b[1;32mhello\x1b[0m");
+///
+/// let fonts = FontCollection::load(&FontCollection::default_families())?;
+/// let mut renderer = HeadlessRenderer::with_grid_size(fonts, DEFAULT_FONT_SIZE, 80, 24);
+/// renderer.render(terminal.state())?;
+/// renderer.image().save_png("hello.png")?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct HeadlessRenderer {
+    canvas: Canvas,
+}
+
+impl HeadlessRenderer {
+    /// Create a `width` x `height` renderer with cells sized for `font_size`
+    pub fn new(fonts: FontCollection, font_size: f32, width: u32, height: u32) -> Self {
+        Self {
+            canvas: Canvas::new(fonts, font_size, width, height),
+        }
+    }
+
+    /// Create a renderer just large enough for a `cols` x `rows` grid and the default padding
+    pub fn with_grid_size(fonts: FontCollection, font_size: f32, cols: usize, rows: usize) -> Self {
+        let mut renderer = Self::new(fonts, font_size, 1, 1);
+        renderer.fit_grid(cols, rows);
+        renderer
+    }
+
+    /// Resize the image to fit a `cols` x `rows` grid with the padding on all sides
+    pub fn fit_grid(&mut self, cols: usize, rows: usize) {
+        let metrics = *self.canvas.metrics();
+        let (padding_x, padding_y) = self.canvas.padding();
+        self.canvas.resize(
+            (cols as f32 * metrics.width + padding_x * 2.0).ceil() as u32,
+            (rows as f32 * metrics.height + padding_y * 2.0).ceil() as u32,
+        );
+    }
+
+    /// The last rendered frame
+    pub fn image(&self) -> RgbaImage {
+        let (width, height) = self.canvas.size();
+        RgbaImage::from_argb(width, height, self.canvas.data())
+    }
+}
+
+impl crate::renderer::Renderer for HeadlessRenderer {
+    fn char_dimensions(&self) -> (f32, f32) {
+        let metrics = self.canvas.metrics();
+        (metrics.width, metrics.height)
+    }
+
+    fn font_size(&self) -> f32 {
+        self.canvas.metrics().font_size
+    }
+
+    fn set_font_size(&mut self, font_size: f32) -> Result<()> {
+        self.canvas.set_font_size(font_size);
+        Ok(())
+    }
+
+    fn set_padding(&mut self, x: f32, y: f32) {
+        self.canvas.set_padding(x, y);
+    }
+
+    fn set_ligatures(&mut self, enabled: bool) {
+        self.canvas.set_ligatures(enabled);
+    }
+
+    fn set_background_opacity(&mut self, opacity: f32) {
+        self.canvas.set_background_opacity(opacity);
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.canvas.set_focused(focused);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        self.canvas.resize(width, height);
+        Ok(())
+    }
+
+    fn render(&mut self, state: &crate::TerminalState) -> Result<()> {
+        self.render_with_blink(state, state.show_cursor, true)
+    }
+
+    fn render_with_blink(
+        &mut self,
+        state: &crate::TerminalState,
+        cursor_visible: bool,
+        text_blink_visible: bool,
+    ) -> Result<()> {
+        self.canvas.draw(state, cursor_visible, text_blink_visible);
+        Ok(())
+    }
+
+    fn is_animating(&self) -> bool {
+        false
+    }
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Terminal;
+    use crate::renderer::Renderer;
+
+    /// Reference images, regenerated with `RUSTTY_UPDATE_GOLDEN=1 cargo test`
+    const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/golden");
+
+    /// Font the golden images were drawn with; other fonts give other pixels
+    const GOLDEN_FONT: &str = "DejaVu Sans Mono";
+
+    /// Largest per-channel difference still counted as a match
+    const TOLERANCE: u8 = 8;
+
+    /// A renderer with little padding, or `None` when the golden font isn't installed
+    fn renderer(cols: usize, rows: usize) -> Option<HeadlessRenderer> {
+        let fonts = match FontCollection::load(&FontCollection::default_families()) {
+            Ok(fonts) => fonts,
+            Err(e) => {
+                eprintln!("Skipping golden test, no font available: {}", e);
+                return None;
+            }
+        };
+        let family = fonts.regular().family_name();
+        if family != GOLDEN_FONT {
+            eprintln!(
+                "Skipping golden test, drawn with {} rather than {}",
+                family, GOLDEN_FONT
+            );
+            return None;
+        }
+        let mut renderer = HeadlessRenderer::new(fonts, 16.0, 1, 1);
+        renderer.set_padding(4.0, 4.0);
+        renderer.fit_grid(cols, rows);
+        Some(renderer)
+    }
+
+    /// Compare a frame with `testdata/golden/<name>.png`
+    ///
+    /// On a mismatch the frame is saved to the temp directory for inspection.
+    fn assert_golden(name: &str, image: &RgbaImage) {
+        let path = Path::new(GOLDEN_DIR).join(format!("{}.png", name));
+        if std::env::var_os("RUSTTY_UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(GOLDEN_DIR).unwrap();
+            image.save_png(&path).unwrap();
+            return;
+        }
+
+        let golden = RgbaImage::load_png(&path).unwrap_or_else(|e| {
+            panic!("{:#}; set RUSTTY_UPDATE_GOLDEN=1 to create it", e);
+        });
+        let size_matches = (golden.width, golden.height) == (image.width, image.height);
+        let differing = golden
+            .pixels
+            .chunks(4)
+            .zip(image.pixels.chunks(4))
+            .filter(|(a, b)| a.iter().zip(*b).any(|(a, b)| a.abs_diff(*b) > TOLERANCE))
+            .count();
+        if !size_matches || differing > 0 {
+            let actual = std::env::temp_dir().join(format!("rustty-golden-{}.png", name));
+            image.save_png(&actual).unwrap();
+            panic!(
+                "{} differs from {} ({}x{} vs {}x{}, {} pixels differ), frame saved to {}",
+                name,
+                path.display(),
+                image.width,
+                image.height,
+                golden.width,
+                golden.height,
+                differing,
+                actual.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let image = RgbaImage {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 0, 0, 255, 0, 128, 255, 255],
+        };
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+        assert_eq!(RgbaImage::read_png(png.as_slice()).unwrap(), image);
+    }
+
+    #[test]
+    fn test_frames_are_unpremultiplied() {
+        let image = RgbaImage::from_argb(2, 1, &[0xff20_4060, 0x8040_2000]);
+        assert_eq!(image.pixel(0, 0), [0x20, 0x40, 0x60, 0xff]);
+        assert_eq!(image.pixel(1, 0), [0x80, 0x40, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn test_translucent_default_background() {
+        let Ok(fonts) = FontCollection::load(&FontCollection::default_families()) else {
+            return;
+        };
+        let mut renderer = HeadlessRenderer::with_grid_size(fonts, 16.0, 4, 1);
+        renderer.set_padding(0.0, 0.0);
+        renderer.set_background_opacity(0.5);
+        let mut terminal = Terminal::new(4, 1);
+        terminal.process_bytes(b"\x1b[?25l\x1b[41m \x1b[49m \x1b[40m \x1b[48;2;0;0;0m ");
+        renderer.render(terminal.state()).unwrap();
+
+        // Only cells with the default background let the desktop through, not
+        // those set to black explicitly
+        let image = renderer.image();
+        let (width, height) = renderer.char_dimensions();
+        let (x, y) = ((width / 2.0) as u32, (height / 2.0) as u32);
+        let cell = |col: u32| image.pixel(x + col * width as u32, y);
+        assert_eq!(cell(0), [205, 49, 49, 255]);
+        assert_eq!(cell(1), [0, 0, 0, 128]);
+        assert_eq!(cell(2), [0, 0, 0, 255]);
+        assert_eq!(cell(3), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_golden_sgr() {
+        let Some(mut renderer) = renderer(40, 6) else {
+            return;
+        };
+        let mut terminal = Terminal::new(40, 6);
+        terminal.process_bytes(
+            b"\x1b[?25l\
+              \x1b[31mred \x1b[32mgreen \x1b[33myellow \x1b[34mblue \x1b[35mmagenta\x1b[0m\r\n\
+              \x1b[41m  \x1b[42m  \x1b[43m  \x1b[44m  \x1b[45m  \x1b[46m  \x1b[47m  \x1b[0m \
+              \x1b[38;5;208m256\x1b[0m \x1b[38;2;10;200;120mtruecolor\x1b[0m\r\n\
+              \x1b[1mbold\x1b[0m \x1b[3mitalic\x1b[0m \x1b[1;3mboth\x1b[0m \
+              \x1b[2mfaint\x1b[0m \x1b[7mreverse\x1b[0m\r\n\
+              \x1b[4msingle\x1b[0m \x1b[4:2mdouble\x1b[0m \x1b[4:3mcurly\x1b[0m \
+              \x1b[4:4mdotted\x1b[0m \x1b[4:5mdashed\x1b[0m\r\n\
+              \x1b[9mstrike\x1b[0m \x1b[53moverline\x1b[0m \
+              \x1b[4;58;2;255;0;0mred underline\x1b[0m",
+        );
+        renderer.render(terminal.state()).unwrap();
+        assert_golden("sgr", &renderer.image());
+    }
+
+    #[test]
+    fn test_golden_cursor_styles() {
+        // DECSCUSR selects the style; unfocused, the block cursor is hollow
+        let styles: [(&str, &[u8], bool); 4] = [
+            ("cursor_block", b"\x1b[2 q", true),
+            ("cursor_underline", b"\x1b[4 q", true),
+            ("cursor_bar", b"\x1b[6 q", true),
+            ("cursor_hollow", b"\x1b[2 q", false),
+        ];
+        for (name, style, focused) in styles {
+            let Some(mut renderer) = renderer(6, 1) else {
+                return;
+            };
+            let mut terminal = Terminal::new(6, 1);
+            terminal.process_bytes(style);
+            terminal.process_bytes(b"abc\x1b[2G");
+            renderer.set_focused(focused);
+            renderer.render(terminal.state()).unwrap();
+            assert_golden(name, &renderer.image());
+        }
+    }
+
+    #[test]
+    fn test_golden_box_drawing() {
+        let Some(mut renderer) = renderer(16, 6) else {
+            return;
+        };
+        let mut terminal = Terminal::new(16, 6);
+        terminal.process_bytes(
+            "\x1b[?25l\
+             ┌──┬──┐ ╔══╦══╗\r\n\
+             │  │  │ ║  ║  ║\r\n\
+             ├──┼──┤ ╠══╬══╣\r\n\
+             └──┴──┘ ╚══╩══╝\r\n\
+             ╭─╮ ┏━┓ ╴╶╵╷\r\n\
+             █▓▒░▀▄▌▐ ⣿⡇\u{e0b0}\u{e0b2}"
+                .as_bytes(),
+        );
+        renderer.render(terminal.state()).unwrap();
+        assert_golden("box_drawing", &renderer.image());
+    }
+}
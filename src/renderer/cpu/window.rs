@@ -0,0 +1,161 @@
+//! CPU renderer presenting the canvas to a window through Softbuffer
+
+use anyhow::{Context as _, Result};
+use softbuffer::{Rect, Surface};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use winit::window::Window;
+
+use super::Canvas;
+use crate::renderer::font::FontCollection;
+
+/// CPU renderer using Raqote for 2D graphics and Softbuffer for display
+pub struct CpuRenderer {
+    surface: Surface<Arc<Window>, Arc<Window>>,
+    /// Frame drawn with raqote, copied into the surface's buffer to present
+    canvas: Canvas,
+}
+
+impl CpuRenderer {
+    /// Create a new CPU renderer
+    ///
+    /// Cell dimensions are derived from the regular face's metrics at `font_size`.
+    /// The surface starts at the window's current size.
+    pub fn new(
+        mut surface: Surface<Arc<Window>, Arc<Window>>,
+        fonts: FontCollection,
+        font_size: f32,
+    ) -> Self {
+        let size = surface.window().inner_size();
+        if let (Some(w), Some(h)) = (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+            && let Err(e) = surface.resize(w, h)
+        {
+            eprintln!("Failed to size surface: {:?}", e);
+        }
+        Self {
+            surface,
+            canvas: Canvas::new(fonts, font_size, size.width, size.height),
+        }
+    }
+
+    /// Render with custom cursor visibility
+    ///
+    /// This method allows the caller to control cursor visibility (e.g., for blinking).
+    /// Only the rows the canvas redrew are copied into the surface and presented.
+    pub fn render_with_blink(
+        &mut self,
+        state: &crate::TerminalState,
+        cursor_visible: bool,
+        text_blink_visible: bool,
+    ) -> Result<()> {
+        let (width, height) = self.canvas.size();
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let mut damage = self.canvas.draw(state, cursor_visible, text_blink_visible);
+
+        let mut buffer = self
+            .surface
+            .buffer_mut()
+            .map_err(|e| anyhow::anyhow!("Failed to get buffer: {:?}", e))?;
+
+        // A buffer holding the previous frame only needs the damaged rows, any
+        // other buffer (new, or older in a swap chain) gets the whole frame
+        if buffer.age() != 1 {
+            damage = vec![(0, height)];
+        }
+        let frame_data = self.canvas.data();
+        for &(top, bottom) in &damage {
+            let start = top as usize * width as usize;
+            let end = (bottom as usize * width as usize)
+                .min(frame_data.len())
+                .min(buffer.len());
+            if start < end {
+                buffer[start..end].copy_from_slice(&frame_data[start..end]);
+            }
+        }
+
+        let rects: Vec<Rect> = damage
+            .iter()
+            .filter_map(|&(top, bottom)| {
+                Some(Rect {
+                    x: 0,
+                    y: top,
+                    width: NonZeroU32::new(width)?,
+                    height: NonZeroU32::new(bottom - top)?,
+                })
+            })
+            .collect();
+        buffer
+            .present_with_damage(&rects)
+            .map_err(|e| anyhow::anyhow!("Failed to present buffer: {:?}", e))?;
+        Ok(())
+    }
+}
+
+impl crate::renderer::Renderer for CpuRenderer {
+    fn char_dimensions(&self) -> (f32, f32) {
+        let metrics = self.canvas.metrics();
+        (metrics.width, metrics.height)
+    }
+
+    fn font_size(&self) -> f32 {
+        self.canvas.metrics().font_size
+    }
+
+    fn set_font_size(&mut self, font_size: f32) -> Result<()> {
+        self.canvas.set_font_size(font_size);
+        Ok(())
+    }
+
+    fn set_padding(&mut self, x: f32, y: f32) {
+        self.canvas.set_padding(x, y);
+    }
+
+    fn set_ligatures(&mut self, enabled: bool) {
+        self.canvas.set_ligatures(enabled);
+    }
+
+    fn set_background_opacity(&mut self, _opacity: f32) {
+        // Softbuffer presents opaque pixels, so the window stays opaque
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.canvas.set_focused(focused);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        let w = NonZeroU32::new(width).context("Window width is zero")?;
+        let h = NonZeroU32::new(height).context("Window height is zero")?;
+        self.surface
+            .resize(w, h)
+            .map_err(|e| anyhow::anyhow!("Failed to resize surface: {:?}", e))?;
+        self.canvas.resize(width, height);
+        Ok(())
+    }
+
+    fn render(&mut self, state: &crate::TerminalState) -> Result<()> {
+        // Default to visible cursor for trait method
+        self.render_with_blink(state, true, true)
+    }
+
+    fn render_with_blink(
+        &mut self,
+        state: &crate::TerminalState,
+        cursor_visible: bool,
+        text_blink_visible: bool,
+    ) -> Result<()> {
+        // Delegate to the public method
+        CpuRenderer::render_with_blink(self, state, cursor_visible, text_blink_visible)
+    }
+
+    fn is_animating(&self) -> bool {
+        // Scrolling jumps a row at a time
+        false
+    }
+
+    fn is_initialized(&self) -> bool {
+        // CPU renderer is always initialized once created
+        true
+    }
+}
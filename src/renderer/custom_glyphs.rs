@@ -0,0 +1,841 @@
+//! Procedurally drawn glyphs shared by the CPU and GPU renderers
+//!
+//! Box drawing (U+2500–U+257F), block elements (U+2580–U+259F), Braille
+//! (U+2800–U+28FF), Powerline separators (U+E0B0–U+E0BF) and legacy computing
+//! sextants (U+1FB00–U+1FB3B) are drawn from the cell size instead of taken
+//! from a font, so lines and blocks meet exactly across neighbouring cells.
+//!
+//! Glyphs are produced as coverage bitmaps the size of one cell, which the
+//! renderers tint with the cell's foreground color.
+
+use crate::renderer::font::CellMetrics;
+
+/// Samples per pixel along each axis when anti-aliasing curves and diagonals
+const SUPERSAMPLE: usize = 4;
+
+/// An 8-bit coverage bitmap covering exactly one cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphBitmap {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major coverage, 0 (transparent) to 255 (foreground)
+    pub pixels: Vec<u8>,
+}
+
+impl GlyphBitmap {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    /// Coverage at (x, y)
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Fill the pixels inside a rectangle, edges rounded to the pixel grid
+    ///
+    /// A rectangle whose far edge rounds to before its near edge, as tiny
+    /// segments of dashed lines in very small cells do, fills nothing.
+    fn fill_rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, alpha: u8) {
+        let x0 = (x0.round().max(0.0) as usize).min(self.width);
+        let x1 = (x1.round().max(0.0) as usize).min(self.width).max(x0);
+        let y0 = (y0.round().max(0.0) as usize).min(self.height);
+        let y1 = (y1.round().max(0.0) as usize).min(self.height).max(y0);
+        for y in y0..y1 {
+            for pixel in &mut self.pixels[y * self.width + x0..y * self.width + x1] {
+                *pixel = (*pixel).max(alpha);
+            }
+        }
+    }
+
+    /// Fill every pixel by the fraction of its sub-samples that are inside a shape
+    fn fill_shape(&mut self, inside: impl Fn(f32, f32) -> bool) {
+        let step = 1.0 / SUPERSAMPLE as f32;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut hits = 0;
+                for sy in 0..SUPERSAMPLE {
+                    for sx in 0..SUPERSAMPLE {
+                        let px = x as f32 + (sx as f32 + 0.5) * step;
+                        let py = y as f32 + (sy as f32 + 0.5) * step;
+                        if inside(px, py) {
+                            hits += 1;
+                        }
+                    }
+                }
+                let alpha = (hits * 255 / (SUPERSAMPLE * SUPERSAMPLE)) as u8;
+                let pixel = &mut self.pixels[y * self.width + x];
+                *pixel = (*pixel).max(alpha);
+            }
+        }
+    }
+
+    /// Stroke a straight line segment with anti-aliased edges
+    fn stroke_segment(&mut self, from: (f32, f32), to: (f32, f32), thickness: f32) {
+        let half = thickness / 2.0;
+        self.fill_shape(|x, y| distance_to_segment((x, y), from, to) <= half);
+    }
+}
+
+/// Distance from a point to a line segment
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (cx, cy) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt()
+}
+
+/// Whether `ch` is drawn procedurally instead of from a font
+pub fn is_custom_glyph(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x2500..=0x259F | 0x2800..=0x28FF | 0xE0B0..=0xE0BF | 0x1FB00..=0x1FB3B
+    )
+}
+
+/// Draw `ch` at the cell size given by `metrics`
+///
+/// Returns `None` for characters that should come from a font.
+pub fn rasterize(ch: char, metrics: &CellMetrics) -> Option<GlyphBitmap> {
+    if !is_custom_glyph(ch) {
+        return None;
+    }
+
+    let width = metrics.width.max(1.0) as usize;
+    let height = metrics.height.max(1.0) as usize;
+    let mut bitmap = GlyphBitmap::new(width, height);
+    let light = metrics.underline_thickness.max(1.0);
+
+    let drawn = match ch as u32 {
+        0x2500..=0x257F => draw_box(&mut bitmap, ch, light),
+        0x2580..=0x259F => draw_block(&mut bitmap, ch),
+        0x2800..=0x28FF => {
+            draw_braille(&mut bitmap, ch as u32 - 0x2800);
+            true
+        }
+        0xE0B0..=0xE0BF => draw_powerline(&mut bitmap, ch, light),
+        0x1FB00..=0x1FB3B => {
+            draw_sextant(&mut bitmap, ch as u32 - 0x1FB00);
+            true
+        }
+        _ => false,
+    };
+    drawn.then_some(bitmap)
+}
+
+/// Line weight of one arm of a box drawing character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Weight {
+    None,
+    Light,
+    Heavy,
+    Double,
+}
+
+use Weight::{Double as D, Heavy as H, Light as L, None as N};
+
+/// Arms of the line box drawing characters as (up, right, down, left)
+fn box_arms(ch: char) -> Option<[Weight; 4]> {
+    let arms = match ch {
+        '─' => [N, L, N, L],
+        '━' => [N, H, N, H],
+        '│' => [L, N, L, N],
+        '┃' => [H, N, H, N],
+        '┌' => [N, L, L, N],
+        '┍' => [N, H, L, N],
+        '┎' => [N, L, H, N],
+        '┏' => [N, H, H, N],
+        '┐' => [N, N, L, L],
+        '┑' => [N, N, L, H],
+        '┒' => [N, N, H, L],
+        '┓' => [N, N, H, H],
+        '└' => [L, L, N, N],
+        '┕' => [L, H, N, N],
+        '┖' => [H, L, N, N],
+        '┗' => [H, H, N, N],
+        '┘' => [L, N, N, L],
+        '┙' => [L, N, N, H],
+        '┚' => [H, N, N, L],
+        '┛' => [H, N, N, H],
+        '├' => [L, L, L, N],
+        '┝' => [L, H, L, N],
+        '┞' => [H, L, L, N],
+        '┟' => [L, L, H, N],
+        '┠' => [H, L, H, N],
+        '┡' => [H, H, L, N],
+        '┢' => [L, H, H, N],
+        '┣' => [H, H, H, N],
+        '┤' => [L, N, L, L],
+        '┥' => [L, N, L, H],
+        '┦' => [H, N, L, L],
+        '┧' => [L, N, H, L],
+        '┨' => [H, N, H, L],
+        '┩' => [H, N, L, H],
+        '┪' => [L, N, H, H],
+        '┫' => [H, N, H, H],
+        '┬' => [N, L, L, L],
+        '┭' => [N, L, L, H],
+        '┮' => [N, H, L, L],
+        '┯' => [N, H, L, H],
+        '┰' => [N, L, H, L],
+        '┱' => [N, L, H, H],
+        '┲' => [N, H, H, L],
+        '┳' => [N, H, H, H],
+        '┴' => [L, L, N, L],
+        '┵' => [L, L, N, H],
+        '┶' => [L, H, N, L],
+        '┷' => [L, H, N, H],
+        '┸' => [H, L, N, L],
+        '┹' => [H, L, N, H],
+        '┺' => [H, H, N, L],
+        '┻' => [H, H, N, H],
+        '┼' => [L, L, L, L],
+        '┽' => [L, L, L, H],
+        '┾' => [L, H, L, L],
+        '┿' => [L, H, L, H],
+        '╀' => [H, L, L, L],
+        '╁' => [L, L, H, L],
+        '╂' => [H, L, H, L],
+        '╃' => [H, L, L, H],
+        '╄' => [H, H, L, L],
+        '╅' => [L, L, H, H],
+        '╆' => [L, H, H, L],
+        '╇' => [H, H, L, H],
+        '╈' => [L, H, H, H],
+        '╉' => [H, L, H, H],
+        '╊' => [H, H, H, L],
+        '╋' => [H, H, H, H],
+        '═' => [N, D, N, D],
+        '║' => [D, N, D, N],
+        '╒' => [N, D, L, N],
+        '╓' => [N, L, D, N],
+        '╔' => [N, D, D, N],
+        '╕' => [N, N, L, D],
+        '╖' => [N, N, D, L],
+        '╗' => [N, N, D, D],
+        '╘' => [L, D, N, N],
+        '╙' => [D, L, N, N],
+        '╚' => [D, D, N, N],
+        '╛' => [L, N, N, D],
+        '╜' => [D, N, N, L],
+        '╝' => [D, N, N, D],
+        '╞' => [L, D, L, N],
+        '╟' => [D, L, D, N],
+        '╠' => [D, D, D, N],
+        '╡' => [L, N, L, D],
+        '╢' => [D, N, D, L],
+        '╣' => [D, N, D, D],
+        '╤' => [N, D, L, D],
+        '╥' => [N, L, D, L],
+        '╦' => [N, D, D, D],
+        '╧' => [L, D, N, D],
+        '╨' => [D, L, N, L],
+        '╩' => [D, D, N, D],
+        '╪' => [L, D, L, D],
+        '╫' => [D, L, D, L],
+        '╬' => [D, D, D, D],
+        '╴' => [N, N, N, L],
+        '╵' => [L, N, N, N],
+        '╶' => [N, L, N, N],
+        '╷' => [N, N, L, N],
+        '╸' => [N, N, N, H],
+        '╹' => [H, N, N, N],
+        '╺' => [N, H, N, N],
+        '╻' => [N, N, H, N],
+        '╼' => [N, H, N, L],
+        '╽' => [L, N, H, N],
+        '╾' => [N, L, N, H],
+        '╿' => [H, N, L, N],
+        _ => return None,
+    };
+    Some(arms)
+}
+
+/// Draw a box drawing character; `light` is the light line thickness
+fn draw_box(bitmap: &mut GlyphBitmap, ch: char, light: f32) -> bool {
+    let width = bitmap.width as f32;
+    let height = bitmap.height as f32;
+    // Snap the center so a line has the same pixel position in every cell
+    let cx = (width / 2.0).floor();
+    let cy = (height / 2.0).floor();
+
+    match ch {
+        // Dashed lines: (dashes per cell, heavy, vertical)
+        '┄' | '┅' | '┆' | '┇' | '┈' | '┉' | '┊' | '┋' | '╌' | '╍' | '╎' | '╏' =>
+        {
+            let (dashes, heavy, vertical) = match ch {
+                '┄' => (3, false, false),
+                '┅' => (3, true, false),
+                '┆' => (3, false, true),
+                '┇' => (3, true, true),
+                '┈' => (4, false, false),
+                '┉' => (4, true, false),
+                '┊' => (4, false, true),
+                '┋' => (4, true, true),
+                '╌' => (2, false, false),
+                '╍' => (2, true, false),
+                '╎' => (2, false, true),
+                _ => (2, true, true),
+            };
+            let thickness = if heavy { light * 2.0 } else { light };
+            let length = if vertical { height } else { width };
+            let segment = length / dashes as f32;
+            let gap = (segment / 4.0).max(1.0);
+            for i in 0..dashes {
+                let start = i as f32 * segment + gap / 2.0;
+                let end = (i + 1) as f32 * segment - gap / 2.0;
+                if vertical {
+                    let x = cx - (thickness / 2.0).floor();
+                    bitmap.fill_rect(x, start, x + thickness, end, 255);
+                } else {
+                    let y = cy - (thickness / 2.0).floor();
+                    bitmap.fill_rect(start, y, end, y + thickness, 255);
+                }
+            }
+            true
+        }
+        // Rounded corners: a quarter circle joined to the two straight arms
+        '╭' | '╮' | '╯' | '╰' => {
+            let line_x = cx - (light / 2.0).floor() + light / 2.0;
+            let line_y = cy - (light / 2.0).floor() + light / 2.0;
+            let radius = (width - line_x).min(line_x).min(height / 2.0);
+            let (right, down) = match ch {
+                '╭' => (true, true),
+                '╮' => (false, true),
+                '╯' => (false, false),
+                _ => (true, false),
+            };
+            let arc_cx = if right {
+                line_x + radius
+            } else {
+                line_x - radius
+            };
+            let arc_cy = if down {
+                line_y + radius
+            } else {
+                line_y - radius
+            };
+            let half = light / 2.0;
+            bitmap.fill_shape(|x, y| {
+                let in_quadrant = (if right { x <= arc_cx } else { x >= arc_cx })
+                    && (if down { y <= arc_cy } else { y >= arc_cy });
+                if !in_quadrant {
+                    return false;
+                }
+                let distance = ((x - arc_cx).powi(2) + (y - arc_cy).powi(2)).sqrt();
+                (distance - radius).abs() <= half
+            });
+
+            // Straight runs from the arc to the cell edges
+            let x0 = cx - (light / 2.0).floor();
+            let y0 = cy - (light / 2.0).floor();
+            if down {
+                bitmap.fill_rect(x0, arc_cy, x0 + light, height, 255);
+            } else {
+                bitmap.fill_rect(x0, 0.0, x0 + light, arc_cy, 255);
+            }
+            if right {
+                bitmap.fill_rect(arc_cx, y0, width, y0 + light, 255);
+            } else {
+                bitmap.fill_rect(0.0, y0, arc_cx, y0 + light, 255);
+            }
+            true
+        }
+        // Diagonals run corner to corner so they continue into the next cell
+        '╱' | '╲' | '╳' => {
+            if ch != '╲' {
+                bitmap.stroke_segment((width, 0.0), (0.0, height), light);
+            }
+            if ch != '╱' {
+                bitmap.stroke_segment((0.0, 0.0), (width, height), light);
+            }
+            true
+        }
+        _ => match box_arms(ch) {
+            Some(arms) => {
+                draw_arms(bitmap, arms, light, cx, cy);
+                true
+            }
+            None => false,
+        },
+    }
+}
+
+/// Half the span across a line of the given weight, from the center line
+fn half_span(weight: Weight, light: f32) -> f32 {
+    match weight {
+        Weight::None => 0.0,
+        Weight::Light => light / 2.0,
+        Weight::Heavy => light,
+        Weight::Double => light * 1.5,
+    }
+}
+
+/// Draw the arms of a line box drawing character
+///
+/// Single lines run through the junction far enough to cover the widest
+/// crossing line. Double lines split into an inner and outer stroke that stop
+/// at, or wrap around, the crossing arms to form proper corners and tees.
+fn draw_arms(bitmap: &mut GlyphBitmap, arms: [Weight; 4], light: f32, cx: f32, cy: f32) {
+    let width = bitmap.width as f32;
+    let height = bitmap.height as f32;
+    let [up, right, down, left] = arms;
+
+    // Stroke offsets from the center line: one stroke, or two for double lines
+    let strokes = |weight: Weight| -> Vec<(f32, f32)> {
+        match weight {
+            Weight::None => Vec::new(),
+            Weight::Light => vec![(-(light / 2.0).floor(), light)],
+            Weight::Heavy => vec![(-light, light * 2.0)],
+            Weight::Double => vec![(-(light * 1.5).floor(), light), (light / 2.0, light)],
+        }
+    };
+
+    // Horizontal arms
+    for (weight, is_left) in [(left, true), (right, false)] {
+        if weight == Weight::None {
+            continue;
+        }
+        let cross = half_span(up, light).max(half_span(down, light));
+        for (index, (offset, thickness)) in strokes(weight).into_iter().enumerate() {
+            // Extent past the center towards the far side of the junction
+            let reach = if weight == Weight::Double {
+                // Upper stroke faces the up arm, lower stroke the down arm
+                let (near, far) = if index == 0 { (up, down) } else { (down, up) };
+                if near != Weight::None {
+                    -half_span(near, light)
+                } else if far != Weight::None {
+                    half_span(far, light)
+                } else {
+                    0.0
+                }
+            } else if cross > 0.0 {
+                cross
+            } else {
+                half_span(weight, light)
+            };
+            let y = cy + offset;
+            if is_left {
+                bitmap.fill_rect(0.0, y, cx + reach, y + thickness, 255);
+            } else {
+                bitmap.fill_rect(cx - reach, y, width, y + thickness, 255);
+            }
+        }
+    }
+
+    // Vertical arms
+    for (weight, is_up) in [(up, true), (down, false)] {
+        if weight == Weight::None {
+            continue;
+        }
+        let cross = half_span(left, light).max(half_span(right, light));
+        for (index, (offset, thickness)) in strokes(weight).into_iter().enumerate() {
+            let reach = if weight == Weight::Double {
+                // Left stroke faces the left arm, right stroke the right arm
+                let (near, far) = if index == 0 {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                if near != Weight::None {
+                    -half_span(near, light)
+                } else if far != Weight::None {
+                    half_span(far, light)
+                } else {
+                    0.0
+                }
+            } else if cross > 0.0 {
+                cross
+            } else {
+                half_span(weight, light)
+            };
+            let x = cx + offset;
+            if is_up {
+                bitmap.fill_rect(x, 0.0, x + thickness, cy + reach, 255);
+            } else {
+                bitmap.fill_rect(x, cy - reach, x + thickness, height, 255);
+            }
+        }
+    }
+}
+
+/// Draw a block element (U+2580–U+259F)
+fn draw_block(bitmap: &mut GlyphBitmap, ch: char) -> bool {
+    let width = bitmap.width as f32;
+    let height = bitmap.height as f32;
+    let eighth_x = |n: u32| (width * n as f32 / 8.0).round();
+    let eighth_y = |n: u32| (height * n as f32 / 8.0).round();
+
+    match ch as u32 {
+        // Upper half
+        0x2580 => bitmap.fill_rect(0.0, 0.0, width, eighth_y(4), 255),
+        // Lower one eighth to full block
+        code @ 0x2581..=0x2588 => {
+            let eighths = code - 0x2580;
+            bitmap.fill_rect(0.0, height - eighth_y(eighths), width, height, 255);
+        }
+        // Left seven eighths to left one eighth
+        code @ 0x2589..=0x258F => {
+            let eighths = 8 - (code - 0x2588);
+            bitmap.fill_rect(0.0, 0.0, eighth_x(eighths), height, 255);
+        }
+        // Right half
+        0x2590 => bitmap.fill_rect(eighth_x(4), 0.0, width, height, 255),
+        // Light, medium and dark shade
+        0x2591 => bitmap.fill_rect(0.0, 0.0, width, height, 64),
+        0x2592 => bitmap.fill_rect(0.0, 0.0, width, height, 128),
+        0x2593 => bitmap.fill_rect(0.0, 0.0, width, height, 192),
+        // Upper one eighth
+        0x2594 => bitmap.fill_rect(0.0, 0.0, width, eighth_y(1), 255),
+        // Right one eighth
+        0x2595 => bitmap.fill_rect(width - eighth_x(1), 0.0, width, height, 255),
+        // Quadrants
+        code @ 0x2596..=0x259F => {
+            const UPPER_LEFT: u8 = 1;
+            const UPPER_RIGHT: u8 = 2;
+            const LOWER_LEFT: u8 = 4;
+            const LOWER_RIGHT: u8 = 8;
+            let quadrants = match code {
+                0x2596 => LOWER_LEFT,
+                0x2597 => LOWER_RIGHT,
+                0x2598 => UPPER_LEFT,
+                0x2599 => UPPER_LEFT | LOWER_LEFT | LOWER_RIGHT,
+                0x259A => UPPER_LEFT | LOWER_RIGHT,
+                0x259B => UPPER_LEFT | UPPER_RIGHT | LOWER_LEFT,
+                0x259C => UPPER_LEFT | UPPER_RIGHT | LOWER_RIGHT,
+                0x259D => UPPER_RIGHT,
+                0x259E => UPPER_RIGHT | LOWER_LEFT,
+                _ => UPPER_RIGHT | LOWER_LEFT | LOWER_RIGHT,
+            };
+            let (mid_x, mid_y) = (eighth_x(4), eighth_y(4));
+            if quadrants & UPPER_LEFT != 0 {
+                bitmap.fill_rect(0.0, 0.0, mid_x, mid_y, 255);
+            }
+            if quadrants & UPPER_RIGHT != 0 {
+                bitmap.fill_rect(mid_x, 0.0, width, mid_y, 255);
+            }
+            if quadrants & LOWER_LEFT != 0 {
+                bitmap.fill_rect(0.0, mid_y, mid_x, height, 255);
+            }
+            if quadrants & LOWER_RIGHT != 0 {
+                bitmap.fill_rect(mid_x, mid_y, width, height, 255);
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Draw a Braille pattern from its dot bits (U+2800 + bits)
+fn draw_braille(bitmap: &mut GlyphBitmap, bits: u32) {
+    // Dot bit -> (column, row) in the 2x4 dot grid
+    const DOTS: [(u32, u32); 8] = [
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (1, 0),
+        (1, 1),
+        (1, 2),
+        (0, 3),
+        (1, 3),
+    ];
+    let column_width = bitmap.width as f32 / 2.0;
+    let row_height = bitmap.height as f32 / 4.0;
+    let radius = (column_width.min(row_height) * 0.35).max(0.75);
+
+    let centers: Vec<(f32, f32)> = DOTS
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| bits & (1 << bit) != 0)
+        .map(|(_, &(column, row))| {
+            (
+                (column as f32 + 0.5) * column_width,
+                (row as f32 + 0.5) * row_height,
+            )
+        })
+        .collect();
+    if centers.is_empty() {
+        return;
+    }
+    bitmap.fill_shape(|x, y| {
+        centers
+            .iter()
+            .any(|&(dx, dy)| (x - dx).powi(2) + (y - dy).powi(2) <= radius * radius)
+    });
+}
+
+/// Draw a Powerline separator (U+E0B0–U+E0BF)
+fn draw_powerline(bitmap: &mut GlyphBitmap, ch: char, light: f32) -> bool {
+    let width = bitmap.width as f32;
+    let height = bitmap.height as f32;
+    let mid = height / 2.0;
+
+    match ch as u32 {
+        // Solid right- and left-pointing triangles
+        0xE0B0 => bitmap.fill_shape(|x, y| x <= width * (1.0 - (y - mid).abs() / mid)),
+        0xE0B2 => bitmap.fill_shape(|x, y| x >= width * (y - mid).abs() / mid),
+        // Thin right- and left-pointing chevrons
+        0xE0B1 => {
+            bitmap.stroke_segment((0.0, 0.0), (width, mid), light);
+            bitmap.stroke_segment((width, mid), (0.0, height), light);
+        }
+        0xE0B3 => {
+            bitmap.stroke_segment((width, 0.0), (0.0, mid), light);
+            bitmap.stroke_segment((0.0, mid), (width, height), light);
+        }
+        // Solid and outlined half circles bulging right and left
+        0xE0B4 => bitmap.fill_shape(|x, y| (x / width).powi(2) + ((y - mid) / mid).powi(2) <= 1.0),
+        0xE0B6 => bitmap
+            .fill_shape(|x, y| ((width - x) / width).powi(2) + ((y - mid) / mid).powi(2) <= 1.0),
+        0xE0B5 | 0xE0B7 => {
+            let right = ch as u32 == 0xE0B5;
+            let inner_width = width - light;
+            let inner_mid = mid - light;
+            bitmap.fill_shape(|x, y| {
+                let dx = if right { x } else { width - x };
+                let outer = (dx / width).powi(2) + ((y - mid) / mid).powi(2) <= 1.0;
+                let inner = (dx / inner_width).powi(2) + ((y - mid) / inner_mid).powi(2) <= 1.0;
+                outer && !inner
+            });
+        }
+        // Solid corner triangles: lower left, lower right, upper left, upper right
+        0xE0B8 => bitmap.fill_shape(|x, y| x / width <= y / height),
+        0xE0BA => bitmap.fill_shape(|x, y| (width - x) / width <= y / height),
+        0xE0BC => bitmap.fill_shape(|x, y| x / width + y / height <= 1.0),
+        0xE0BE => bitmap.fill_shape(|x, y| x / width >= y / height),
+        // Thin diagonals matching the corner triangles
+        0xE0B9 | 0xE0BF => bitmap.stroke_segment((0.0, 0.0), (width, height), light),
+        0xE0BB | 0xE0BD => bitmap.stroke_segment((0.0, height), (width, 0.0), light),
+        _ => return false,
+    }
+    true
+}
+
+/// Draw a block sextant (U+1FB00 + index)
+///
+/// Sextants number the 2x3 sub-blocks 1–6 top to bottom, left to right. The
+/// patterns for the empty cell, full block and left and right halves exist in
+/// the block elements range and are skipped in this one.
+fn draw_sextant(bitmap: &mut GlyphBitmap, index: u32) {
+    let mut pattern = index + 1;
+    if pattern >= 0b010101 {
+        pattern += 1;
+    }
+    if pattern >= 0b101010 {
+        pattern += 1;
+    }
+
+    let width = bitmap.width as f32;
+    let height = bitmap.height as f32;
+    let mid_x = (width / 2.0).round();
+    let rows = [
+        0.0,
+        (height / 3.0).round(),
+        (height * 2.0 / 3.0).round(),
+        height,
+    ];
+    for row in 0..3 {
+        if pattern & (1 << (row * 2)) != 0 {
+            bitmap.fill_rect(0.0, rows[row], mid_x, rows[row + 1], 255);
+        }
+        if pattern & (1 << (row * 2 + 1)) != 0 {
+            bitmap.fill_rect(mid_x, rows[row], width, rows[row + 1], 255);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> CellMetrics {
+        CellMetrics {
+            font_size: 16.0,
+            width: 10.0,
+            height: 19.0,
+            baseline: 15.0,
+            underline_position: 16.0,
+            underline_thickness: 1.0,
+            strikeout_position: 10.0,
+        }
+    }
+
+    /// Rows of the left and right edge columns that are lit
+    fn edge_rows(bitmap: &GlyphBitmap) -> (Vec<usize>, Vec<usize>) {
+        let lit = |x| {
+            (0..bitmap.height)
+                .filter(|&y| bitmap.get(x, y) > 0)
+                .collect()
+        };
+        (lit(0), lit(bitmap.width - 1))
+    }
+
+    /// Columns of the top and bottom edge rows that are lit
+    fn edge_columns(bitmap: &GlyphBitmap) -> (Vec<usize>, Vec<usize>) {
+        let lit = |y| {
+            (0..bitmap.width)
+                .filter(|&x| bitmap.get(x, y) > 0)
+                .collect()
+        };
+        (lit(0), lit(bitmap.height - 1))
+    }
+
+    #[test]
+    fn test_only_custom_ranges_are_synthesized() {
+        assert!(rasterize('A', &metrics()).is_none());
+        assert!(rasterize('─', &metrics()).is_some());
+        assert!(rasterize('█', &metrics()).is_some());
+        assert!(rasterize('⣿', &metrics()).is_some());
+        assert!(rasterize('\u{E0B0}', &metrics()).is_some());
+        assert!(rasterize('\u{1FB00}', &metrics()).is_some());
+    }
+
+    #[test]
+    fn test_every_glyph_fits_tiny_cells() {
+        let ranges = [
+            0x2500..=0x259F,
+            0x2800..=0x28FF,
+            0xE0B0..=0xE0BF,
+            0x1FB00..=0x1FB3B,
+        ];
+        for (width, height) in [(1.0, 1.0), (2.0, 3.0), (3.0, 5.0)] {
+            let metrics = CellMetrics {
+                width,
+                height,
+                ..metrics()
+            };
+            for ch in ranges.iter().cloned().flatten().filter_map(char::from_u32) {
+                let bitmap = rasterize(ch, &metrics).unwrap();
+                assert_eq!(bitmap.pixels.len(), bitmap.width * bitmap.height);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bitmap_is_cell_sized() {
+        let bitmap = rasterize('┼', &metrics()).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (10, 19));
+        assert_eq!(bitmap.pixels.len(), 190);
+    }
+
+    #[test]
+    fn test_horizontal_lines_join_across_cells() {
+        // Every glyph with a left/right arm must hit the edges on the same rows
+        let (left, right) = edge_rows(&rasterize('─', &metrics()).unwrap());
+        assert_eq!(left, right);
+        assert!(!left.is_empty());
+        for ch in ['┼', '├', '┤', '┬', '┴', '└', '┘', '┌', '┐', '╭', '╰'] {
+            let (l, r) = edge_rows(&rasterize(ch, &metrics()).unwrap());
+            assert!(l.is_empty() || l == left, "{} left edge", ch);
+            assert!(r.is_empty() || r == left, "{} right edge", ch);
+        }
+    }
+
+    #[test]
+    fn test_vertical_lines_join_across_cells() {
+        let (top, bottom) = edge_columns(&rasterize('│', &metrics()).unwrap());
+        assert_eq!(top, bottom);
+        for ch in ['┼', '├', '┤', '┬', '┴', '└', '┘', '┌', '┐', '╮', '╯'] {
+            let (t, b) = edge_columns(&rasterize(ch, &metrics()).unwrap());
+            assert!(t.is_empty() || t == top, "{} top edge", ch);
+            assert!(b.is_empty() || b == top, "{} bottom edge", ch);
+        }
+    }
+
+    #[test]
+    fn test_double_lines_have_two_strokes() {
+        let (left, right) = edge_rows(&rasterize('═', &metrics()).unwrap());
+        assert_eq!(left, right);
+        assert_eq!(left.len(), 2);
+        assert!(left[1] - left[0] > 1, "strokes must be separated by a gap");
+
+        let (corner_left, _) = edge_rows(&rasterize('╗', &metrics()).unwrap());
+        assert_eq!(corner_left, left);
+    }
+
+    #[test]
+    fn test_corner_connects_arms() {
+        // ┌ is lit from its center down and to the right, nothing up or left
+        let bitmap = rasterize('┌', &metrics()).unwrap();
+        let (left, right) = edge_rows(&bitmap);
+        let (top, bottom) = edge_columns(&bitmap);
+        assert!(left.is_empty() && top.is_empty());
+        assert!(!right.is_empty() && !bottom.is_empty());
+        assert_eq!(bitmap.get(bottom[0], right[0]), 255);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let full = rasterize('█', &metrics()).unwrap();
+        assert!(full.pixels.iter().all(|&p| p == 255));
+
+        let upper = rasterize('▀', &metrics()).unwrap();
+        assert_eq!(upper.get(0, 0), 255);
+        assert_eq!(upper.get(0, 18), 0);
+
+        // Left and right halves split the cell without overlap
+        let left = rasterize('▌', &metrics()).unwrap();
+        let right = rasterize('▐', &metrics()).unwrap();
+        for (l, r) in left.pixels.iter().zip(&right.pixels) {
+            assert_eq!(l.max(r), &255);
+            assert_eq!(l.min(r), &0);
+        }
+
+        // Shades are partial coverage of the whole cell
+        let medium = rasterize('▒', &metrics()).unwrap();
+        assert!(medium.pixels.iter().all(|&p| p == 128));
+    }
+
+    #[test]
+    fn test_braille_dots() {
+        assert!(
+            rasterize('⠀', &metrics())
+                .unwrap()
+                .pixels
+                .iter()
+                .all(|&p| p == 0)
+        );
+
+        // Dot 1 is top left, dot 8 bottom right
+        let dot1 = rasterize('⠁', &metrics()).unwrap();
+        assert!(dot1.get(2, 2) > 0);
+        assert_eq!(dot1.get(7, 16), 0);
+        let dot8 = rasterize('⢀', &metrics()).unwrap();
+        assert!(dot8.get(7, 16) > 0);
+        assert_eq!(dot8.get(2, 2), 0);
+    }
+
+    #[test]
+    fn test_sextants_skip_half_blocks() {
+        // U+1FB00 is sextant 1 (top left only)
+        let first = rasterize('\u{1FB00}', &metrics()).unwrap();
+        assert_eq!(first.get(0, 0), 255);
+        assert_eq!(first.get(9, 0), 0);
+
+        // U+1FB3B is sextant 2-3-4-5-6 (all but top left)
+        let last = rasterize('\u{1FB3B}', &metrics()).unwrap();
+        assert_eq!(last.get(0, 0), 0);
+        assert_eq!(last.get(9, 0), 255);
+        assert_eq!(last.get(0, 18), 255);
+    }
+
+    #[test]
+    fn test_powerline_triangle() {
+        let bitmap = rasterize('\u{E0B0}', &metrics()).unwrap();
+        // Spans the left edge, point at the middle of the right edge
+        assert!(bitmap.get(0, 0) > 0);
+        assert!(bitmap.get(0, 18) > 0);
+        assert_eq!(bitmap.get(0, 9), 255);
+        assert_eq!(bitmap.get(9, 0), 0);
+        assert!(bitmap.get(9, 9) > 0);
+    }
+}
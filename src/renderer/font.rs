@@ -0,0 +1,713 @@
+//! Font loading shared by the CPU and GPU renderers
+//!
+//! A `FontCollection` holds the regular, bold, italic and bold-italic faces of
+//! the terminal font. Faces missing from the system fall back to the regular
+//! face and are marked for synthetic emboldening and/or slanting at draw time.
+//!
+//! Characters the terminal font lacks are looked up in an ordered list of
+//! fallback fonts, then in every other font installed on the system. The font
+//! found for each character is cached.
+
+use anyhow::{Context as _, Result};
+use font_kit::family_name::FamilyName;
+use font_kit::font::Font;
+use font_kit::handle::Handle;
+use font_kit::metrics::Metrics;
+use font_kit::properties::{Properties, Style, Weight};
+use font_kit::source::SystemSource;
+use std::collections::HashMap;
+use std::sync::Arc;
+use unicode_width::UnicodeWidthChar;
+
+use crate::renderer::color_glyphs;
+
+/// Font size in points used at startup and restored by Ctrl+0
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+/// Smallest font size reachable by zooming out
+pub const MIN_FONT_SIZE: f32 = 6.0;
+
+/// Largest font size reachable by zooming in
+pub const MAX_FONT_SIZE: f32 = 72.0;
+
+/// Horizontal shear applied to synthetic italics (x offset per unit of height)
+pub const SYNTHETIC_ITALIC_SKEW: f32 = 0.2;
+
+/// Horizontal offset in pixels of the second stroke used for synthetic bold
+pub const SYNTHETIC_BOLD_OFFSET: f32 = 1.0;
+
+/// Font style selected by a cell's bold and italic attributes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl FontStyle {
+    /// Select the style for a cell's bold/italic attributes
+    pub fn from_attributes(bold: bool, italic: bool) -> Self {
+        match (bold, italic) {
+            (false, false) => Self::Regular,
+            (true, false) => Self::Bold,
+            (false, true) => Self::Italic,
+            (true, true) => Self::BoldItalic,
+        }
+    }
+
+    pub fn is_bold(self) -> bool {
+        matches!(self, Self::Bold | Self::BoldItalic)
+    }
+
+    pub fn is_italic(self) -> bool {
+        matches!(self, Self::Italic | Self::BoldItalic)
+    }
+}
+
+/// A loaded font face and the synthetic effects needed to match its style
+pub struct FontFace {
+    pub font: Font,
+    /// The face is not bold itself; draw it twice with a small offset
+    pub synthetic_bold: bool,
+    /// The face is upright; shear it to fake an italic
+    pub synthetic_italic: bool,
+}
+
+/// Cell geometry derived from font metrics at a given size
+///
+/// All positions are in pixels, measured down from the top of the cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellMetrics {
+    /// Font size in points (one point is one pixel)
+    pub font_size: f32,
+    /// Cell width, the advance of a monospace glyph
+    pub width: f32,
+    /// Cell height, ascent plus descent plus line gap
+    pub height: f32,
+    /// Baseline position
+    pub baseline: f32,
+    /// Top edge of the underline
+    pub underline_position: f32,
+    /// Underline (and other decoration line) thickness
+    pub underline_thickness: f32,
+    /// Top edge of the strikethrough line, half the x-height above the baseline
+    pub strikeout_position: f32,
+}
+
+impl CellMetrics {
+    /// Compute cell metrics for a font at a given size
+    pub fn from_font(font: &Font, font_size: f32) -> Self {
+        let metrics = font.metrics();
+        let advance = font
+            .glyph_for_char('M')
+            .and_then(|glyph_id| font.advance(glyph_id).ok())
+            .map(|advance| advance.x())
+            .unwrap_or(metrics.units_per_em as f32 * 0.6);
+        Self::from_metrics(&metrics, advance, font_size)
+    }
+
+    /// Compute cell metrics from raw font metrics and a glyph advance in font units
+    pub fn from_metrics(metrics: &Metrics, advance: f32, font_size: f32) -> Self {
+        let scale = font_size / metrics.units_per_em as f32;
+        let ascent = metrics.ascent * scale;
+        // Descent is negative (below the baseline) in font units
+        let descent = -metrics.descent * scale;
+        let line_gap = metrics.line_gap.max(0.0) * scale;
+
+        let width = (advance * scale).ceil().max(1.0);
+        let height = (ascent + descent + line_gap).ceil().max(1.0);
+        let baseline = (line_gap / 2.0 + ascent).round();
+
+        let underline_thickness = (metrics.underline_thickness * scale).round().max(1.0);
+        // Underline position is the offset of the line's center below the baseline
+        let underline_position =
+            (baseline - metrics.underline_position * scale - underline_thickness / 2.0)
+                .round()
+                .clamp(0.0, height - underline_thickness);
+
+        let x_height = if metrics.x_height > 0.0 {
+            metrics.x_height * scale
+        } else {
+            ascent / 2.0
+        };
+        let strikeout_position = (baseline - x_height / 2.0 - underline_thickness / 2.0).round();
+
+        Self {
+            font_size,
+            width,
+            height,
+            baseline,
+            underline_position,
+            underline_thickness,
+            strikeout_position,
+        }
+    }
+}
+
+/// Identifies the face a glyph was resolved from within a `FontCollection`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FaceId {
+    /// One of the terminal font's four faces
+    Styled(FontStyle),
+    /// An entry of the fallback chain, by index
+    Fallback(usize),
+}
+
+/// A glyph resolved through the font fallback chain, ready to draw
+pub struct ResolvedGlyph<'a> {
+    pub font: &'a Font,
+    /// The face `font` belongs to, stable while the collection lives
+    pub face: FaceId,
+    pub glyph_id: u32,
+    /// Draw a second, offset stroke to fake bold
+    pub synthetic_bold: bool,
+    /// Shear the glyph to fake italic
+    pub synthetic_italic: bool,
+    /// Factor applied to the font size so the glyph fits the cell (at most 1.0)
+    pub scale: f32,
+    /// The glyph comes from a color font (emoji) and is drawn in its own colors
+    pub color: bool,
+}
+
+impl ResolvedGlyph<'_> {
+    /// Font size to draw this glyph at for the given cell metrics
+    pub fn font_size(&self, metrics: &CellMetrics) -> f32 {
+        metrics.font_size * self.scale
+    }
+}
+
+/// Raw font file data and the index of the face within it (for collections)
+pub fn font_data(font: &Font) -> Option<(Arc<Vec<u8>>, u32)> {
+    let index = match font.handle() {
+        Some(Handle::Path { font_index, .. }) | Some(Handle::Memory { font_index, .. }) => {
+            font_index
+        }
+        None => 0,
+    };
+    Some((font.copy_font_data()?, index))
+}
+
+/// Scale factor that shrinks a fallback glyph to fit its cells
+///
+/// Fallback fonts are often wider (CJK, symbols) or taller than the terminal
+/// font; their glyphs are scaled down uniformly so they stay inside the
+/// `cells` cells the character occupies.
+///
+/// `advance` is the glyph's advance width in font units.
+fn fit_scale(advance: f32, font_metrics: &Metrics, metrics: &CellMetrics, cells: usize) -> f32 {
+    let scale = metrics.font_size / font_metrics.units_per_em as f32;
+    let width = advance * scale;
+    let height = (font_metrics.ascent - font_metrics.descent) * scale;
+    let cells_width = metrics.width * cells as f32;
+
+    let mut fit: f32 = 1.0;
+    if width > cells_width {
+        fit = fit.min(cells_width / width);
+    }
+    if height > metrics.height {
+        fit = fit.min(metrics.height / height);
+    }
+    fit
+}
+
+/// A fallback font and whether it has color glyphs
+struct FallbackFont {
+    font: Font,
+    color: bool,
+}
+
+impl FallbackFont {
+    fn new(font: Font) -> Self {
+        Self {
+            color: color_glyphs::is_color_font(&font),
+            font,
+        }
+    }
+}
+
+/// Characters a font has glyphs for, as sorted, disjoint ranges
+struct Charset(Vec<(u32, u32)>);
+
+impl Charset {
+    /// Read the Unicode cmap of a font; empty if the font can't be read or parsed
+    fn of(handle: &Handle) -> Self {
+        let (data, index) = match handle {
+            Handle::Path { path, font_index } => match std::fs::read(path) {
+                Ok(data) => (Arc::new(data), *font_index),
+                Err(_) => return Self(Vec::new()),
+            },
+            Handle::Memory { bytes, font_index } => (bytes.clone(), *font_index),
+        };
+        let mut codepoints = Vec::new();
+        if let Ok(face) = rustybuzz::ttf_parser::Face::parse(&data, index)
+            && let Some(cmap) = face.tables().cmap
+        {
+            for subtable in cmap.subtables {
+                if subtable.is_unicode() {
+                    subtable.codepoints(|codepoint| codepoints.push(codepoint));
+                }
+            }
+        }
+        Self::from_codepoints(codepoints)
+    }
+
+    fn from_codepoints(mut codepoints: Vec<u32>) -> Self {
+        codepoints.sort_unstable();
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for codepoint in codepoints {
+            match ranges.last_mut() {
+                Some((_, end)) if codepoint <= *end + 1 => *end = (*end).max(codepoint),
+                _ => ranges.push((codepoint, codepoint)),
+            }
+        }
+        Self(ranges)
+    }
+
+    fn contains(&self, ch: char) -> bool {
+        let ch = ch as u32;
+        let index = self.0.partition_point(|&(_, end)| end < ch);
+        self.0.get(index).is_some_and(|&(start, _)| start <= ch)
+    }
+}
+
+/// Installed fonts searched for characters the fallback list lacks
+///
+/// Each font is read once to learn its characters and then dropped; only the
+/// font picked as a fallback is loaded again and kept.
+struct SystemFonts {
+    /// Fonts not read yet, in reverse enumeration order
+    unscanned: Vec<Handle>,
+    /// Fonts read already, none of which had the characters looked up so far
+    scanned: Vec<(Handle, Charset)>,
+}
+
+impl SystemFonts {
+    fn new(mut handles: Vec<Handle>) -> Self {
+        handles.reverse();
+        Self {
+            unscanned: handles,
+            scanned: Vec::new(),
+        }
+    }
+
+    /// Load the first installed font with a glyph for `ch`
+    fn take_covering(&mut self, ch: char) -> Option<Font> {
+        while let Some(position) = self
+            .scanned
+            .iter()
+            .position(|(_, charset)| charset.contains(ch))
+        {
+            let (handle, _) = self.scanned.remove(position);
+            if let Some(font) = Self::load_covering(&handle, ch) {
+                return Some(font);
+            }
+        }
+        while let Some(handle) = self.unscanned.pop() {
+            let charset = Charset::of(&handle);
+            if charset.contains(ch) {
+                if let Some(font) = Self::load_covering(&handle, ch) {
+                    return Some(font);
+                }
+            } else {
+                self.scanned.push((handle, charset));
+            }
+        }
+        None
+    }
+
+    fn load_covering(handle: &Handle, ch: char) -> Option<Font> {
+        handle
+            .load()
+            .ok()
+            .filter(|font| font.glyph_for_char(ch).is_some())
+    }
+}
+
+/// Regular, bold, italic and bold-italic faces of one font family, plus the
+/// fallback fonts used for characters that family lacks
+pub struct FontCollection {
+    regular: FontFace,
+    bold: FontFace,
+    italic: FontFace,
+    bold_italic: FontFace,
+    /// Fallback fonts in lookup order; system fonts found on demand are appended
+    fallbacks: Vec<FallbackFont>,
+    /// Index into `fallbacks` per character, `None` if no installed font has it
+    fallback_cache: HashMap<char, Option<usize>>,
+    /// Installed fonts not yet in `fallbacks`, enumerated on the first system lookup
+    system_fonts: Option<SystemFonts>,
+}
+
+impl FontCollection {
+    /// Font families tried in order when loading the terminal font
+    pub fn default_families() -> Vec<FamilyName> {
+        vec![
+            FamilyName::Title("CaskaydiaCove Nerd Font Mono".to_string()),
+            FamilyName::Title("CaskaydiaCove NF Mono".to_string()),
+            FamilyName::Monospace,
+        ]
+    }
+
+    /// Font families tried in order when the terminal font lacks a glyph
+    pub fn default_fallback_families() -> Vec<String> {
+        [
+            "Symbols Nerd Font Mono",
+            "Noto Color Emoji",
+            "Noto Sans Mono CJK SC",
+            "Noto Sans CJK SC",
+            "Noto Sans Symbols",
+            "Noto Sans Symbols 2",
+            "DejaVu Sans Mono",
+            "DejaVu Sans",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect()
+    }
+
+    /// Load all four faces of the best matching family, with the default fallbacks
+    ///
+    /// Fails only if no regular face can be loaded.
+    pub fn load(families: &[FamilyName]) -> Result<Self> {
+        Self::load_with_fallbacks(families, &Self::default_fallback_families())
+    }
+
+    /// Load all four faces of the best matching family and the named fallback
+    /// families that are installed
+    ///
+    /// Fails only if no regular face can be loaded.
+    pub fn load_with_fallbacks(
+        families: &[FamilyName],
+        fallback_families: &[String],
+    ) -> Result<Self> {
+        let source = SystemSource::new();
+        let regular = source
+            .select_best_match(families, &Properties::new())
+            .context("Failed to find suitable font")?
+            .load()
+            .context("Failed to load font")?;
+
+        // Search the regular face's family first so styled faces match it
+        let mut styled_families = vec![FamilyName::Title(regular.family_name())];
+        styled_families.extend_from_slice(families);
+
+        let load_face = |style: FontStyle| -> FontFace {
+            let mut properties = Properties::new();
+            if style.is_bold() {
+                properties.weight(Weight::BOLD);
+            }
+            if style.is_italic() {
+                properties.style(Style::Italic);
+            }
+
+            let font = source
+                .select_best_match(&styled_families, &properties)
+                .ok()
+                .and_then(|handle| handle.load().ok())
+                .unwrap_or_else(|| regular.clone());
+
+            let loaded = font.properties();
+            FontFace {
+                synthetic_bold: style.is_bold() && loaded.weight < Weight::SEMIBOLD,
+                synthetic_italic: style.is_italic() && loaded.style == Style::Normal,
+                font,
+            }
+        };
+
+        let bold = load_face(FontStyle::Bold);
+        let italic = load_face(FontStyle::Italic);
+        let bold_italic = load_face(FontStyle::BoldItalic);
+
+        // Missing fallback families are skipped, as is the primary family itself
+        let mut fallbacks: Vec<FallbackFont> = Vec::new();
+        for name in fallback_families {
+            let Some(font) = source
+                .select_best_match(&[FamilyName::Title(name.clone())], &Properties::new())
+                .ok()
+                .and_then(|handle| handle.load().ok())
+            else {
+                continue;
+            };
+            let postscript_name = font.postscript_name();
+            let duplicate = regular.postscript_name() == postscript_name
+                || fallbacks
+                    .iter()
+                    .any(|fallback| fallback.font.postscript_name() == postscript_name);
+            if !duplicate {
+                fallbacks.push(FallbackFont::new(font));
+            }
+        }
+
+        Ok(Self {
+            regular: FontFace {
+                font: regular,
+                synthetic_bold: false,
+                synthetic_italic: false,
+            },
+            bold,
+            italic,
+            bold_italic,
+            fallbacks,
+            fallback_cache: HashMap::new(),
+            system_fonts: None,
+        })
+    }
+
+    /// Get the face for a style
+    pub fn face(&self, style: FontStyle) -> &FontFace {
+        match style {
+            FontStyle::Regular => &self.regular,
+            FontStyle::Bold => &self.bold,
+            FontStyle::Italic => &self.italic,
+            FontStyle::BoldItalic => &self.bold_italic,
+        }
+    }
+
+    /// The regular face, used for metrics
+    pub fn regular(&self) -> &Font {
+        &self.regular.font
+    }
+
+    /// Cell metrics of the regular face at a given size
+    pub fn cell_metrics(&self, font_size: f32) -> CellMetrics {
+        CellMetrics::from_font(&self.regular.font, font_size)
+    }
+
+    /// A glyph of the styled face by id, as produced by shaping
+    pub fn styled_glyph(&self, style: FontStyle, glyph_id: u32) -> ResolvedGlyph<'_> {
+        let styled = self.face(style);
+        ResolvedGlyph {
+            font: &styled.font,
+            face: FaceId::Styled(style),
+            glyph_id,
+            synthetic_bold: styled.synthetic_bold,
+            synthetic_italic: styled.synthetic_italic,
+            scale: 1.0,
+            color: false,
+        }
+    }
+
+    /// Find the font to draw `ch` with in the given style
+    ///
+    /// Tries the styled face, the regular face, then the fallback chain.
+    /// Fallback glyphs get synthetic bold/italic for styled text and are
+    /// scaled to fit the cell. Returns `None` if no installed font has `ch`.
+    pub fn resolve(
+        &mut self,
+        ch: char,
+        style: FontStyle,
+        metrics: &CellMetrics,
+    ) -> Option<ResolvedGlyph<'_>> {
+        if let Some(glyph_id) = self.face(style).font.glyph_for_char(ch) {
+            let styled = self.face(style);
+            return Some(ResolvedGlyph {
+                font: &styled.font,
+                face: FaceId::Styled(style),
+                glyph_id,
+                synthetic_bold: styled.synthetic_bold,
+                synthetic_italic: styled.synthetic_italic,
+                scale: 1.0,
+                color: false,
+            });
+        }
+
+        let (font, face, glyph_id, scale, color) = match self.regular.font.glyph_for_char(ch) {
+            Some(glyph_id) => (
+                &self.regular.font,
+                FaceId::Styled(FontStyle::Regular),
+                glyph_id,
+                1.0,
+                false,
+            ),
+            None => {
+                let index = self.fallback_index(ch)?;
+                let fallback = &self.fallbacks[index];
+                let font = &fallback.font;
+                let glyph_id = font.glyph_for_char(ch)?;
+                let advance = font.advance(glyph_id).map_or(0.0, |advance| advance.x());
+                let cells = ch.width().unwrap_or(1).max(1);
+                let scale = fit_scale(advance, &font.metrics(), metrics, cells);
+                (
+                    font,
+                    FaceId::Fallback(index),
+                    glyph_id,
+                    scale,
+                    fallback.color,
+                )
+            }
+        };
+        Some(ResolvedGlyph {
+            font,
+            face,
+            glyph_id,
+            // Color glyphs keep their own look for any style
+            synthetic_bold: style.is_bold() && !color,
+            synthetic_italic: style.is_italic() && !color,
+            scale,
+            color,
+        })
+    }
+
+    /// Index of the first fallback font with a glyph for `ch`, cached per character
+    fn fallback_index(&mut self, ch: char) -> Option<usize> {
+        if let Some(&index) = self.fallback_cache.get(&ch) {
+            return index;
+        }
+
+        let index = self
+            .fallbacks
+            .iter()
+            .position(|fallback| fallback.font.glyph_for_char(ch).is_some())
+            .or_else(|| self.load_system_fallback(ch));
+        self.fallback_cache.insert(ch, index);
+        index
+    }
+
+    /// Search the installed fonts for one with a glyph for `ch`
+    ///
+    /// The first match is appended to the fallback list so later lookups find
+    /// it without searching the system fonts again.
+    fn load_system_fallback(&mut self, ch: char) -> Option<usize> {
+        let system_fonts = self.system_fonts.get_or_insert_with(|| {
+            SystemFonts::new(SystemSource::new().all_fonts().unwrap_or_else(|e| {
+                eprintln!("[FONT] Failed to enumerate system fonts: {}", e);
+                Vec::new()
+            }))
+        });
+
+        let font = system_fonts.take_covering(ch)?;
+        eprintln!(
+            "[FONT] Using {} as fallback for U+{:04X}",
+            font.full_name(),
+            ch as u32
+        );
+        self.fallbacks.push(FallbackFont::new(font));
+        Some(self.fallbacks.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_style_from_attributes() {
+        assert_eq!(FontStyle::from_attributes(false, false), FontStyle::Regular);
+        assert_eq!(FontStyle::from_attributes(true, false), FontStyle::Bold);
+        assert_eq!(FontStyle::from_attributes(false, true), FontStyle::Italic);
+        assert_eq!(
+            FontStyle::from_attributes(true, true),
+            FontStyle::BoldItalic
+        );
+    }
+
+    #[test]
+    fn test_font_style_flags() {
+        assert!(FontStyle::BoldItalic.is_bold());
+        assert!(FontStyle::BoldItalic.is_italic());
+        assert!(!FontStyle::Italic.is_bold());
+        assert!(!FontStyle::Bold.is_italic());
+        assert!(!FontStyle::Regular.is_bold());
+    }
+
+    fn test_metrics() -> Metrics {
+        Metrics {
+            units_per_em: 2048,
+            ascent: 1901.0,
+            descent: -483.0,
+            line_gap: 0.0,
+            underline_position: -130.0,
+            underline_thickness: 90.0,
+            cap_height: 1493.0,
+            x_height: 1120.0,
+            bounding_box: pathfinder_geometry::rect::RectF::default(),
+        }
+    }
+
+    #[test]
+    fn test_cell_metrics_from_font_metrics() {
+        // DejaVu Sans Mono at 16px: advance 1233 units
+        let metrics = CellMetrics::from_metrics(&test_metrics(), 1233.0, 16.0);
+
+        assert_eq!(metrics.width, 10.0);
+        assert_eq!(metrics.height, 19.0);
+        assert_eq!(metrics.baseline, 15.0);
+        assert_eq!(metrics.underline_thickness, 1.0);
+        assert_eq!(metrics.underline_position, 16.0);
+        assert!(metrics.strikeout_position < metrics.baseline);
+        assert!(metrics.strikeout_position > 0.0);
+    }
+
+    #[test]
+    fn test_cell_metrics_scale_with_font_size() {
+        let small = CellMetrics::from_metrics(&test_metrics(), 1233.0, 12.0);
+        let large = CellMetrics::from_metrics(&test_metrics(), 1233.0, 32.0);
+
+        assert!(large.width > small.width);
+        assert!(large.height > small.height);
+        assert!(large.baseline > small.baseline);
+        assert!(large.underline_position < large.height);
+    }
+
+    #[test]
+    fn test_fallback_glyph_fits_cell() {
+        let cell = CellMetrics::from_metrics(&test_metrics(), 1233.0, 16.0);
+
+        // A glyph no wider than the cell is drawn at full size
+        assert_eq!(fit_scale(1233.0, &test_metrics(), &cell, 1), 1.0);
+
+        // A full-width glyph (CJK) is shrunk to the cell width
+        let scale = fit_scale(2048.0, &test_metrics(), &cell, 1);
+        assert!(scale < 1.0);
+        assert!(2048.0 * 16.0 / 2048.0 * scale <= cell.width);
+
+        // ...but fits a double-width cell pair at full size
+        assert_eq!(fit_scale(2048.0, &test_metrics(), &cell, 2), 1.0);
+    }
+
+    #[test]
+    fn test_fallback_glyph_fits_cell_height() {
+        let cell = CellMetrics::from_metrics(&test_metrics(), 1233.0, 16.0);
+        let tall = Metrics {
+            ascent: 2400.0,
+            descent: -800.0,
+            ..test_metrics()
+        };
+
+        let scale = fit_scale(1000.0, &tall, &cell, 1);
+        assert!(scale < 1.0);
+        assert!((3200.0 * 16.0 / 2048.0 * scale) <= cell.height + 0.001);
+    }
+
+    #[test]
+    fn test_charset_ranges() {
+        let charset = Charset::from_codepoints(vec![0x62, 0x41, 0x61, 0x42, 0x63, 0x4E00]);
+        assert_eq!(
+            charset.0,
+            vec![(0x41, 0x42), (0x61, 0x63), (0x4E00, 0x4E00)]
+        );
+        assert!(charset.contains('A') && charset.contains('c') && charset.contains('\u{4E00}'));
+        assert!(!charset.contains('C') && !charset.contains('@') && !charset.contains('d'));
+    }
+
+    #[test]
+    fn test_system_fonts_keep_only_charsets() {
+        let Ok(handle) =
+            SystemSource::new().select_best_match(&[FamilyName::Monospace], &Properties::new())
+        else {
+            eprintln!("Skipping system font test, no monospace font installed");
+            return;
+        };
+        let mut fonts = SystemFonts::new(vec![handle]);
+
+        // A noncharacter no font covers leaves the font scanned but not loaded
+        assert!(fonts.take_covering('\u{10FFFF}').is_none());
+        assert!(fonts.unscanned.is_empty());
+        assert_eq!(fonts.scanned.len(), 1);
+
+        // A character it has loads it from the scanned handle
+        assert!(fonts.take_covering('x').is_some());
+        assert!(fonts.scanned.is_empty());
+    }
+}
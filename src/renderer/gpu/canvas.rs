@@ -0,0 +1,1067 @@
+//! Window-independent half of the GPU renderer
+//!
+//! `Canvas` keeps the cells of the viewport in a persistent instance buffer,
+//! rebuilding only the rows that changed, and draws them into any texture
+//! view: the window's surface, or an offscreen texture in tests and benchmarks.
+
+use anyhow::Result;
+use std::time::Instant;
+
+use super::cell_buffer::CellBuffer;
+use super::glyph_atlas::{AtlasEntry, GlyphAtlas, PageKind};
+use super::scroll::{ScrollAnimation, SmoothScroll};
+use super::vertex::{CellInstance, FLAG_COLOR_GLYPH, FLAG_PAGE_MASK, Globals, Vertex, push_rect};
+use crate::renderer::atlas::{AtlasStats, snap_subpixel};
+use crate::renderer::font::{CellMetrics, FontCollection, FontStyle};
+use crate::renderer::shaping::Shaper;
+use crate::terminal::{Cell, Color, UnderlineStyle};
+
+/// Color of the cursor, and the background of the cell under a block cursor
+const CURSOR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Draws terminal state with wgpu into a texture view
+pub struct Canvas {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    background_pipeline: wgpu::RenderPipeline,
+    glyph_pipeline: wgpu::RenderPipeline,
+    overlay_pipeline: wgpu::RenderPipeline,
+    globals_buffer: wgpu::Buffer,
+    globals_bind_group: wgpu::BindGroup,
+    glyph_atlas: GlyphAtlas,
+    fonts: FontCollection,
+    shaper: Shaper,
+    metrics: CellMetrics,
+    /// Distance from the target's edge to the grid, in physical pixels
+    padding_x: f32,
+    padding_y: f32,
+    /// Target size in physical pixels
+    size: (u32, u32),
+    /// One instance per cell, plus extra glyphs of ligatures
+    cells: CellBuffer,
+    /// Decoration quads of each viewport row, drawn over the glyphs
+    decorations: Vec<Vec<Vertex>>,
+    /// Decorations and the cursor of the current frame
+    overlay_buffer: wgpu::Buffer,
+    overlays: Vec<Vertex>,
+    /// Premultiplied clear color behind cells with the default background
+    background: wgpu::Color,
+    /// Rebuild every row on the next frame, e.g. after font or padding changes
+    full_rebuild: bool,
+    /// Eases the drawn rows towards the viewport when it moves
+    scroll: ScrollAnimation,
+    /// Absolute grid row drawn as the first row in the last frame
+    last_first_row: usize,
+    /// Whether the last frame was part of a scroll animation
+    animating: bool,
+    /// Whether the window has keyboard focus; unfocused, the block cursor is hollow
+    focused: bool,
+    /// Viewport row the cursor was on in the last frame
+    last_cursor_row: Option<usize>,
+    /// Blink phase the blinking text was built in in the last frame
+    last_text_blink_visible: bool,
+    /// Atlas evictions seen so far; kept rows may refer to evicted glyphs' slots
+    last_evictions: u64,
+    /// Rows rebuilt in the last frame
+    #[cfg(test)]
+    rows_built: usize,
+}
+
+impl Canvas {
+    /// Create a `width` x `height` canvas drawing into targets of `format`,
+    /// with cells sized for `font_size`
+    pub fn new(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        format: wgpu::TextureFormat,
+        fonts: FontCollection,
+        font_size: f32,
+        width: u32,
+        height: u32,
+    ) -> Result<Self> {
+        let metrics = fonts.cell_metrics(font_size);
+        let glyph_atlas = GlyphAtlas::new(&device, &metrics)?;
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Terminal Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/terminal.wgsl").into()),
+        });
+
+        // Target size for converting pixels to clip space, shared by all passes
+        let globals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Globals Buffer"),
+            size: std::mem::size_of::<Globals>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let globals_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Globals Bind Group Layout"),
+        });
+        let globals_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &globals_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: globals_buffer.as_entire_binding(),
+            }],
+            label: Some("Globals Bind Group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Terminal Pipeline Layout"),
+            bind_group_layouts: &[&glyph_atlas.bind_group_layout, &globals_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, vs_entry, fs_entry, buffer| {
+            create_pipeline(
+                &device,
+                &pipeline_layout,
+                &shader,
+                label,
+                (vs_entry, fs_entry),
+                buffer,
+                format,
+            )
+        };
+        let background_pipeline = pipeline(
+            "Background Pipeline",
+            "vs_background",
+            "fs_solid",
+            CellInstance::desc(),
+        );
+        let glyph_pipeline = pipeline(
+            "Glyph Pipeline",
+            "vs_glyph",
+            "fs_glyph",
+            CellInstance::desc(),
+        );
+        let overlay_pipeline =
+            pipeline("Overlay Pipeline", "vs_overlay", "fs_solid", Vertex::desc());
+
+        let cells = CellBuffer::new(&device);
+        let overlay_buffer = create_overlay_buffer(&device, 1024);
+
+        let mut canvas = Self {
+            background_pipeline,
+            glyph_pipeline,
+            overlay_pipeline,
+            globals_buffer,
+            globals_bind_group,
+            glyph_atlas,
+            shaper: Shaper::new(&fonts),
+            fonts,
+            metrics,
+            padding_x: 10.0,
+            padding_y: 20.0,
+            size: (0, 0),
+            cells,
+            decorations: Vec::new(),
+            overlay_buffer,
+            overlays: Vec::new(),
+            background: background_color(1.0),
+            full_rebuild: true,
+            scroll: ScrollAnimation::new(),
+            last_first_row: 0,
+            animating: false,
+            focused: true,
+            last_cursor_row: None,
+            last_text_blink_visible: true,
+            last_evictions: 0,
+            #[cfg(test)]
+            rows_built: 0,
+            device,
+            queue,
+        };
+        canvas.resize(width, height);
+        Ok(canvas)
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn metrics(&self) -> &CellMetrics {
+        &self.metrics
+    }
+
+    /// Target size in pixels
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Match the target size
+    ///
+    /// Cells are placed in pixels and converted to clip space by the shader,
+    /// so no row needs rebuilding.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+    }
+
+    /// Change the font size, recomputing cell metrics
+    ///
+    /// Glyphs at the old size stay in the atlas until evicted, so zooming back is cheap.
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.metrics = self.fonts.cell_metrics(font_size);
+        self.glyph_atlas.set_metrics(&self.metrics);
+        self.full_rebuild = true;
+    }
+
+    pub fn set_padding(&mut self, x: f32, y: f32) {
+        self.padding_x = x;
+        self.padding_y = y;
+        self.full_rebuild = true;
+    }
+
+    pub fn set_ligatures(&mut self, enabled: bool) {
+        self.shaper.set_enabled(enabled);
+        self.full_rebuild = true;
+    }
+
+    /// Set the opacity of the default background, from 0.0 (transparent) to 1.0
+    ///
+    /// Only the clear color changes, cells with the default background draw
+    /// no background of their own.
+    pub fn set_background_opacity(&mut self, opacity: f32) {
+        self.background = background_color(opacity);
+    }
+
+    /// Animate viewport changes, or jump to the new position with `None`
+    pub fn set_smooth_scroll(&mut self, config: Option<SmoothScroll>) {
+        self.scroll.set_config(config);
+    }
+
+    /// Whether a scroll animation is running, so more frames are needed
+    pub fn is_animating(&self) -> bool {
+        self.animating
+    }
+
+    /// Follow the window's focus
+    ///
+    /// The cursor row is rebuilt every frame, so the change shows on the next one.
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Glyph atlas usage counters, for debugging
+    pub fn atlas_stats(&self) -> AtlasStats {
+        self.glyph_atlas.stats()
+    }
+
+    /// Draw `state` into `view`
+    ///
+    /// Only rows damaged in the grid, the rows the cursor left and entered,
+    /// and rows whose blinking text changed phase are rebuilt and uploaded;
+    /// the rest of the instance buffer is drawn as is. While a scroll
+    /// animation runs, every row is rebuilt.
+    pub fn draw(
+        &mut self,
+        view: &wgpu::TextureView,
+        state: &crate::TerminalState,
+        cursor_visible: bool,
+        text_blink_visible: bool,
+    ) -> Result<()> {
+        self.glyph_atlas.begin_frame();
+
+        // While scrolling smoothly, rows are drawn from a fractional position
+        // above or below the viewport, with one more row to fill the gap, and
+        // shifted by the fraction of a row in the shader
+        let grid = &state.grid;
+        let offset = self.scroll.offset(state, Instant::now());
+        let top = (grid.viewport_start as f32 - offset).clamp(0.0, grid.screen_start() as f32);
+        let first_row = top.floor() as usize;
+        let fraction = top - first_row as f32;
+        let row_count = grid.viewport_height + usize::from(fraction > 0.0);
+        let end = (first_row + row_count).min(grid.cells.len());
+        let viewport = &grid.cells[first_row.min(end)..end];
+        self.animating = offset != 0.0;
+
+        let mut full_rebuild = std::mem::take(&mut self.full_rebuild)
+            || self.decorations.len() != viewport.len()
+            || first_row != self.last_first_row
+            || self.animating;
+        self.last_first_row = first_row;
+        self.cells.set_row_count(viewport.len());
+        self.decorations.resize_with(viewport.len(), Vec::new);
+
+        let (width, height) = self.size;
+        let globals = Globals {
+            screen_size: [width.max(1) as f32, height.max(1) as f32],
+            offset: [0.0, -fraction * self.metrics.height],
+        };
+        self.queue
+            .write_buffer(&self.globals_buffer, 0, bytemuck::bytes_of(&globals));
+
+        // Cursor rows are screen-relative; hide the cursor when scrolled out of view
+        let cursor_viewport_row = (grid.screen_start() + state.cursor.row)
+            .checked_sub(first_row)
+            .unwrap_or(usize::MAX);
+        let block_cursor =
+            cursor_visible && self.focused && state.cursor.style == crate::CursorStyle::Block;
+        let blink_changed = text_blink_visible != self.last_text_blink_visible;
+
+        #[cfg(test)]
+        {
+            self.rows_built = 0;
+        }
+        loop {
+            for (row, line) in viewport.iter().enumerate() {
+                let rebuild = full_rebuild
+                    || state.grid.is_row_damaged(row)
+                    || row == cursor_viewport_row
+                    || Some(row) == self.last_cursor_row
+                    || (blink_changed && line.iter().any(|cell| cell.blink));
+                if rebuild {
+                    // Ligatures never form across the cursor, even while it blinks
+                    // off. A focused block cursor is drawn as its cell in inverted colors.
+                    let on_cursor_row = row == cursor_viewport_row;
+                    let cursor_col =
+                        (state.show_cursor && on_cursor_row).then_some(state.cursor.col);
+                    let inverted_col = (block_cursor && on_cursor_row).then_some(state.cursor.col);
+                    self.build_row(row, line, cursor_col, inverted_col, text_blink_visible)?;
+                }
+            }
+
+            // Glyphs of rows kept from earlier frames aren't marked as used, so
+            // they may have been evicted and their slots reused. Rebuilding every
+            // row only evicts glyphs unused in this frame.
+            let evictions = self.glyph_atlas.stats().evictions;
+            let evicted = evictions != self.last_evictions;
+            self.last_evictions = evictions;
+            if full_rebuild || !evicted {
+                break;
+            }
+            full_rebuild = true;
+        }
+
+        self.last_cursor_row =
+            (cursor_viewport_row < viewport.len()).then_some(cursor_viewport_row);
+        self.last_text_blink_visible = text_blink_visible;
+
+        self.cells.upload(&self.device, &self.queue);
+
+        // Decorations and the cursor are few, so they are gathered every frame
+        self.overlays.clear();
+        for decorations in &self.decorations {
+            self.overlays.extend_from_slice(decorations);
+        }
+        if cursor_visible && cursor_viewport_row < viewport.len() {
+            self.push_cursor(state, cursor_viewport_row);
+        }
+        let overlay_data: &[u8] = bytemuck::cast_slice(&self.overlays);
+        if overlay_data.len() > self.overlay_buffer.size() as usize {
+            self.overlay_buffer = create_overlay_buffer(&self.device, self.overlays.len());
+        }
+        if !overlay_data.is_empty() {
+            self.queue
+                .write_buffer(&self.overlay_buffer, 0, overlay_data);
+        }
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.background),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_bind_group(1, &self.globals_bind_group, &[]);
+
+            // Rows scrolled partly out of the grid area don't reach into the padding
+            let grid_top = (self.padding_y.max(0.0) as u32).min(height);
+            let grid_bottom = ((self.padding_y + grid.viewport_height as f32 * self.metrics.height)
+                .ceil() as u32)
+                .clamp(grid_top, height);
+            let visible = grid_bottom > grid_top;
+            if visible {
+                render_pass.set_scissor_rect(0, grid_top, width, grid_bottom - grid_top);
+            }
+
+            // Backgrounds of every cell first, then glyphs, which may overhang
+            // into neighbouring cells, once per atlas page
+            let instances = self.cells.instance_count();
+            if visible && instances > 0 {
+                render_pass.set_vertex_buffer(0, self.cells.buffer().slice(..));
+                render_pass.set_pipeline(&self.background_pipeline);
+                render_pass.set_bind_group(0, &self.glyph_atlas.solid_bind_group, &[]);
+                render_pass.draw(0..6, 0..instances);
+
+                render_pass.set_pipeline(&self.glyph_pipeline);
+                for page in self.glyph_atlas.pages() {
+                    let first = page.slot() * 6;
+                    render_pass.set_bind_group(0, self.glyph_atlas.bind_group(page), &[]);
+                    render_pass.draw(first..first + 6, 0..instances);
+                }
+            }
+
+            if visible && !self.overlays.is_empty() {
+                render_pass.set_pipeline(&self.overlay_pipeline);
+                render_pass.set_bind_group(0, &self.glyph_atlas.solid_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.overlay_buffer.slice(..));
+                render_pass.draw(0..self.overlays.len() as u32, 0..1);
+            }
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(())
+    }
+
+    /// Rebuild the instances and decorations of one viewport row
+    ///
+    /// The cell in `inverted_col` is drawn with its colors swapped: its text in
+    /// the cell's background color on a block of its foreground color.
+    fn build_row(
+        &mut self,
+        row: usize,
+        line: &[Cell],
+        cursor_col: Option<usize>,
+        inverted_col: Option<usize>,
+        text_blink_visible: bool,
+    ) -> Result<()> {
+        #[cfg(test)]
+        {
+            self.rows_built += 1;
+        }
+        let metrics = self.metrics;
+        let shaped = self
+            .shaper
+            .shape_line(line, cursor_col, &self.fonts, metrics.font_size);
+        let instances = self.cells.row_mut(row);
+        let decorations = &mut self.decorations[row];
+        decorations.clear();
+
+        let y = self.padding_y + row as f32 * metrics.height;
+        for (col, cell) in line.iter().enumerate() {
+            let x = self.padding_x + col as f32 * metrics.width;
+
+            // The second half of a double-width character is drawn with its first half
+            let covered = cell.wide_spacer && col > 0 && line[col - 1].wide;
+            let cells = if cell.wide && col + 1 < line.len() {
+                2.0
+            } else {
+                1.0
+            };
+
+            let mut fg = [
+                cell.fg.r as f32 / 255.0,
+                cell.fg.g as f32 / 255.0,
+                cell.fg.b as f32 / 255.0,
+                1.0,
+            ];
+            if cell.faint {
+                // Halve intensity for faint
+                fg[0] *= 0.5;
+                fg[1] *= 0.5;
+                fg[2] *= 0.5;
+            }
+            let mut bg = [
+                cell.bg.r as f32 / 255.0,
+                cell.bg.g as f32 / 255.0,
+                cell.bg.b as f32 / 255.0,
+                1.0,
+            ];
+            let mut size = [metrics.width * cells, metrics.height];
+            if inverted_col == Some(col) {
+                std::mem::swap(&mut fg, &mut bg);
+                if covered {
+                    // On the second half of a wide character
+                    size = [metrics.width, metrics.height];
+                }
+            } else if covered || cell.default_bg {
+                // The clear color shows through, translucent if so configured
+                size = [0.0; 2];
+            }
+
+            let mut instance = CellInstance {
+                position: [x, y],
+                size,
+                fg,
+                bg,
+                ..Default::default()
+            };
+
+            // Concealed cells and blinking cells in their off phase show no text
+            let text_hidden = cell.conceal || (cell.blink && !text_blink_visible);
+            let mut extra_glyphs = Vec::new();
+            if !covered && !text_hidden && cell.ch != ' ' && !cell.ch.is_control() {
+                // Glyph images are placed on whole pixels, rasterized at the
+                // pen's subpixel offset. Ligature runs place their shaped glyphs
+                // relative to the cell, others get one glyph in the face
+                // selected by bold/italic
+                let style = FontStyle::from_attributes(cell.bold, cell.italic);
+                let mut entries = Vec::new();
+                if let Some(shaped_glyphs) = shaped.glyphs_at(col) {
+                    for shaped_glyph in shaped_glyphs {
+                        let glyph = self.fonts.styled_glyph(style, shaped_glyph.glyph_id);
+                        let (pen_x, subpixel) = snap_subpixel(x + shaped_glyph.x);
+                        let pen_y = y.round() + shaped_glyph.y;
+                        let entry = self.glyph_atlas.get_or_rasterize_glyph(
+                            &glyph,
+                            subpixel,
+                            &self.device,
+                            &self.queue,
+                        )?;
+                        entries.extend(entry.map(|entry| (entry, pen_x, pen_y)));
+                    }
+                } else {
+                    let (pen_x, subpixel) = snap_subpixel(x);
+                    let entry = self.glyph_atlas.get_or_rasterize(
+                        cell.ch,
+                        style,
+                        subpixel,
+                        &mut self.fonts,
+                        &self.device,
+                        &self.queue,
+                    )?;
+                    entries.extend(entry.map(|entry| (entry, pen_x, y.round())));
+                }
+
+                // The cell's instance carries its first glyph; further glyphs
+                // of a ligature cluster get instances without a background
+                let mut entries = entries
+                    .into_iter()
+                    .filter(|(entry, _, _)| !entry.is_empty());
+                if let Some((entry, pen_x, pen_y)) = entries.next() {
+                    set_glyph(&mut instance, &entry, pen_x, pen_y);
+                }
+                for (entry, pen_x, pen_y) in entries {
+                    let mut extra = CellInstance {
+                        position: [x, y],
+                        fg,
+                        ..Default::default()
+                    };
+                    set_glyph(&mut extra, &entry, pen_x, pen_y);
+                    extra_glyphs.push(extra);
+                }
+            }
+            instances.push(instance);
+            instances.extend(extra_glyphs);
+
+            // Decorations are solid quads drawn over the cell
+            if !text_hidden {
+                let underline_color = cell.underline_color.map_or(fg, |c| {
+                    [
+                        c.r as f32 / 255.0,
+                        c.g as f32 / 255.0,
+                        c.b as f32 / 255.0,
+                        1.0,
+                    ]
+                });
+
+                let thickness = metrics.underline_thickness;
+                if cell.overline {
+                    push_rect(decorations, [x, y, metrics.width, thickness], fg);
+                }
+                if cell.strikethrough {
+                    let top = y + metrics.strikeout_position;
+                    push_rect(decorations, [x, top, metrics.width, thickness], fg);
+                }
+                for (rx, ry, rw, rh) in underline_rects(cell.underline, &metrics, x) {
+                    push_rect(decorations, [x + rx, y + ry, rw, rh], underline_color);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the cursor's quads to the overlays, except for a focused block
+    /// cursor, which is drawn with its row
+    fn push_cursor(&mut self, state: &crate::TerminalState, row: usize) {
+        use crate::CursorStyle;
+
+        let (width, height) = (self.metrics.width, self.metrics.height);
+        let x = self.padding_x + state.cursor.col as f32 * width;
+        let y = self.padding_y + row as f32 * height;
+        let thickness = (self.metrics.underline_thickness * 2.0).max(2.0);
+
+        let overlays = &mut self.overlays;
+        match state.cursor.style {
+            CursorStyle::Block if self.focused => {}
+            CursorStyle::Block => {
+                let line = self.metrics.underline_thickness.max(1.0);
+                push_rect(overlays, [x, y, width, line], CURSOR_COLOR);
+                push_rect(overlays, [x, y + height - line, width, line], CURSOR_COLOR);
+                push_rect(overlays, [x, y, line, height], CURSOR_COLOR);
+                push_rect(overlays, [x + width - line, y, line, height], CURSOR_COLOR);
+            }
+            CursorStyle::Underline => {
+                let top = y + height - thickness;
+                push_rect(overlays, [x, top, width, thickness], CURSOR_COLOR);
+            }
+            CursorStyle::Bar => push_rect(overlays, [x, y, thickness, height], CURSOR_COLOR),
+        }
+    }
+}
+
+/// The default background color at `opacity`, premultiplied
+fn background_color(opacity: f32) -> wgpu::Color {
+    let alpha = opacity.clamp(0.0, 1.0) as f64;
+    let Color { r, g, b } = Color::default_background();
+    let channel = |c: u8| c as f64 / 255.0 * alpha;
+    wgpu::Color {
+        r: channel(r),
+        g: channel(g),
+        b: channel(b),
+        a: alpha,
+    }
+}
+
+/// Point an instance at a glyph image whose pen position is `(pen_x, pen_y)`
+fn set_glyph(instance: &mut CellInstance, entry: &AtlasEntry, pen_x: f32, pen_y: f32) {
+    let [x, y] = instance.position;
+    instance.glyph_offset = [pen_x + entry.left as f32 - x, pen_y + entry.top as f32 - y];
+    instance.glyph_rect = [
+        entry.x as f32,
+        entry.y as f32,
+        entry.width as f32,
+        entry.height as f32,
+    ];
+    instance.flags = entry.page.slot() & FLAG_PAGE_MASK;
+    if entry.page.kind == PageKind::Color {
+        instance.flags |= FLAG_COLOR_GLYPH;
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    label: &str,
+    (vs_entry, fs_entry): (&str, &str),
+    buffer: wgpu::VertexBufferLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: vs_entry,
+            buffers: &[buffer],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: fs_entry,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+fn create_overlay_buffer(device: &wgpu::Device, vertices: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Overlay Vertex Buffer"),
+        size: (vertices.next_power_of_two() * std::mem::size_of::<Vertex>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Pixel rectangles `(x, y, width, height)` making up an underline, relative to the cell's top-left
+///
+/// Curly underlines are approximated by short steps along a sine wave, dotted and
+/// dashed underlines by evenly spaced segments phased on the cell's pixel `cell_x`
+/// so the pattern continues across adjacent cells.
+fn underline_rects(
+    style: UnderlineStyle,
+    metrics: &CellMetrics,
+    cell_x: f32,
+) -> Vec<(f32, f32, f32, f32)> {
+    let width = metrics.width;
+    let thickness = metrics.underline_thickness;
+    let base_y = metrics.underline_position;
+
+    // Split the cell into segments of `on` pixels followed by `off` pixels
+    let dashes = |on: f32, off: f32| {
+        let period = on + off;
+        let mut rects = Vec::new();
+        let mut start = -(cell_x % period);
+        while start < width {
+            let left = start.max(0.0);
+            let right = (start + on).min(width);
+            if right > left {
+                rects.push((left, base_y, right - left, thickness));
+            }
+            start += period;
+        }
+        rects
+    };
+
+    match style {
+        UnderlineStyle::None => Vec::new(),
+        UnderlineStyle::Single => vec![(0.0, base_y, width, thickness)],
+        UnderlineStyle::Double => {
+            // Second line one thickness below the first, kept inside the cell
+            let second = (base_y + thickness * 2.0).min(metrics.height - thickness);
+            let first = (second - thickness * 2.0).max(metrics.baseline);
+            vec![
+                (0.0, first, width, thickness),
+                (0.0, second, width, thickness),
+            ]
+        }
+        UnderlineStyle::Curly => {
+            const STEPS: usize = 8;
+            let step = width / STEPS as f32;
+            let amplitude = (thickness * 1.5).max(1.5);
+            let center = base_y.min(metrics.height - amplitude - thickness);
+            (0..STEPS)
+                .map(|i| {
+                    let phase = (i as f32 + 0.5) / STEPS as f32 * std::f32::consts::TAU;
+                    (
+                        i as f32 * step,
+                        center - phase.sin() * amplitude,
+                        step,
+                        thickness,
+                    )
+                })
+                .collect()
+        }
+        UnderlineStyle::Dotted => dashes(thickness, thickness),
+        UnderlineStyle::Dashed => {
+            let dash = (width / 3.0).max(2.0);
+            dashes(dash, dash / 2.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Terminal;
+
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    /// An offscreen canvas without padding and its target, or `None` when
+    /// there is no adapter or monospace font
+    fn canvas(width: u32, height: u32) -> Option<(Canvas, wgpu::Texture)> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let (device, queue) =
+            match pollster::block_on(super::super::request_device(&instance, None)) {
+                Ok(device) => device,
+                Err(e) => {
+                    eprintln!("Skipping GPU canvas test, no adapter available: {}", e);
+                    return None;
+                }
+            };
+        let fonts = match FontCollection::load(&FontCollection::default_families()) {
+            Ok(fonts) => fonts,
+            Err(e) => {
+                eprintln!("Skipping GPU canvas test, no font available: {}", e);
+                return None;
+            }
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let mut canvas = Canvas::new(device, queue, FORMAT, fonts, 16.0, width, height).ok()?;
+        canvas.set_padding(0.0, 0.0);
+        Some((canvas, texture))
+    }
+
+    fn draw(
+        canvas: &mut Canvas,
+        texture: &wgpu::Texture,
+        terminal: &mut Terminal,
+        cursor_visible: bool,
+    ) {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        canvas
+            .draw(&view, terminal.state(), cursor_visible, true)
+            .unwrap();
+        terminal.state_mut().grid.clear_damage();
+    }
+
+    /// RGBA pixels of the target, row by row
+    fn read_pixels(canvas: &Canvas, texture: &wgpu::Texture) -> Vec<[u8; 4]> {
+        let (width, height) = canvas.size();
+        let padded_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = canvas.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (padded_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = canvas
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        canvas.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        canvas.device.poll(wgpu::Maintain::Wait);
+        let data = slice.get_mapped_range();
+        data.chunks(padded_row as usize)
+            .flat_map(|row| row[..(width * 4) as usize].chunks(4))
+            .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+            .collect()
+    }
+
+    /// Pixel at `(col, row)` measured in cells, e.g. `(0.5, 0.5)` for the middle of the first cell
+    fn cell_pixel(canvas: &Canvas, pixels: &[[u8; 4]], col: f32, row: f32) -> [u8; 4] {
+        let metrics = canvas.metrics();
+        let x = (col * metrics.width) as u32;
+        let y = (row * metrics.height) as u32;
+        pixels[(y * canvas.size().0 + x) as usize]
+    }
+
+    #[test]
+    fn test_cells_draw_backgrounds_and_glyphs() {
+        let Some((mut canvas, texture)) = canvas(200, 100) else {
+            return;
+        };
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes(b"\x1b[41m \x1b[0m\x1b[32m\xe2\x96\x88");
+        draw(&mut canvas, &texture, &mut terminal, false);
+
+        let pixels = read_pixels(&canvas, &texture);
+        let pixel = |col, row| cell_pixel(&canvas, &pixels, col, row);
+
+        // Red background, a full block glyph in green, untouched cells black
+        assert_eq!(pixel(0.5, 0.5), [205, 49, 49, 255]);
+        let block = pixel(1.5, 0.5);
+        assert!(block[1] > 150 && block[0] < 50, "{:?}", block);
+        assert_eq!(pixel(0.5, 1.5), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_translucent_default_background() {
+        let Some((mut canvas, texture)) = canvas(200, 100) else {
+            return;
+        };
+        canvas.set_background_opacity(0.5);
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes(b"\x1b[41m \x1b[49m \x1b[40m \x1b[48;2;0;0;0m ");
+        draw(&mut canvas, &texture, &mut terminal, false);
+
+        let pixels = read_pixels(&canvas, &texture);
+        assert_eq!(cell_pixel(&canvas, &pixels, 0.5, 0.5), [205, 49, 49, 255]);
+        let [r, g, b, a] = cell_pixel(&canvas, &pixels, 1.5, 0.5);
+        assert_eq!([r, g, b], [0, 0, 0]);
+        assert!((127..=128).contains(&a), "{}", a);
+
+        // Black set explicitly stays opaque
+        assert_eq!(cell_pixel(&canvas, &pixels, 2.5, 0.5), [0, 0, 0, 255]);
+        assert_eq!(cell_pixel(&canvas, &pixels, 3.5, 0.5), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_decorations_and_reverse_video() {
+        let Some((mut canvas, texture)) = canvas(200, 100) else {
+            return;
+        };
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes(b"\x1b[4m \x1b[0m \x1b[9m \x1b[0m \x1b[7m \x1b[0m");
+        draw(&mut canvas, &texture, &mut terminal, false);
+
+        let metrics = *canvas.metrics();
+        let pixels = read_pixels(&canvas, &texture);
+        let pixel = |col, row| cell_pixel(&canvas, &pixels, col, row);
+        let black = [0, 0, 0, 255];
+
+        // Underlined and struck-through spaces
+        let underline = (metrics.underline_position + 0.5) / metrics.height;
+        assert_ne!(pixel(0.5, underline), black);
+        assert_eq!(pixel(0.5, 0.2), black);
+        let strikeout = (metrics.strikeout_position + 0.5) / metrics.height;
+        assert_ne!(pixel(2.5, strikeout), black);
+
+        // Reverse video shows the text color as the background
+        assert_ne!(pixel(4.5, 0.5), black);
+        assert_eq!(pixel(3.5, 0.5), black);
+    }
+
+    #[test]
+    fn test_block_cursor_inverts_its_cell() {
+        let Some((mut canvas, texture)) = canvas(200, 100) else {
+            return;
+        };
+        let mut terminal = Terminal::new(10, 4);
+        // Left half block under the cursor
+        terminal.process_bytes("\u{258c}\r".as_bytes());
+        draw(&mut canvas, &texture, &mut terminal, true);
+
+        let pixels = read_pixels(&canvas, &texture);
+        let pixel = |col, row| cell_pixel(&canvas, &pixels, col, row);
+        assert_eq!(pixel(0.25, 0.5), [0, 0, 0, 255]);
+        assert_eq!(pixel(0.75, 0.5), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_block_cursor_swaps_cell_colors() {
+        let Some((mut canvas, texture)) = canvas(200, 100) else {
+            return;
+        };
+
+        // Reverse video: white text background, black glyph
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes("\x1b[7m\u{258c}\x1b[0m\r".as_bytes());
+        draw(&mut canvas, &texture, &mut terminal, true);
+        let pixels = read_pixels(&canvas, &texture);
+        assert_eq!(
+            cell_pixel(&canvas, &pixels, 0.25, 0.5),
+            [255, 255, 255, 255]
+        );
+        assert_eq!(cell_pixel(&canvas, &pixels, 0.75, 0.5), [0, 0, 0, 255]);
+
+        // Colored text: a block in the text color
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes("\x1b[32m\u{258c}\x1b[0m\r".as_bytes());
+        draw(&mut canvas, &texture, &mut terminal, true);
+        let pixels = read_pixels(&canvas, &texture);
+        let Color { r, g, b } = Color::from_ansi_index(2);
+        assert_eq!(cell_pixel(&canvas, &pixels, 0.25, 0.5), [0, 0, 0, 255]);
+        assert_eq!(cell_pixel(&canvas, &pixels, 0.75, 0.5), [r, g, b, 255]);
+    }
+
+    #[test]
+    fn test_unfocused_block_cursor_is_hollow() {
+        let Some((mut canvas, texture)) = canvas(200, 100) else {
+            return;
+        };
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes(b"\x1b[2;3H");
+        canvas.set_focused(false);
+        draw(&mut canvas, &texture, &mut terminal, true);
+
+        let pixels = read_pixels(&canvas, &texture);
+        let pixel = |col, row| cell_pixel(&canvas, &pixels, col, row);
+        assert_eq!(pixel(2.5, 1.5), [0, 0, 0, 255]);
+        assert_eq!(pixel(2.0, 1.5), [255, 255, 255, 255]);
+        assert_eq!(pixel(2.5, 1.0), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_cursor_follows_scrolled_viewport() {
+        let Some((mut canvas, texture)) = canvas(200, 100) else {
+            return;
+        };
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes(b"x\r\nx\r\nx\r\nx\r\nx\r\nx\r\n\x1b[4;6H");
+        draw(&mut canvas, &texture, &mut terminal, true);
+        let pixels = read_pixels(&canvas, &texture);
+        assert_eq!(cell_pixel(&canvas, &pixels, 5.5, 3.5), [255, 255, 255, 255]);
+
+        // Scrolled back a line, the cursor's row is below the viewport
+        let grid = &mut terminal.state_mut().grid;
+        grid.viewport_start -= 1;
+        grid.damage_all();
+        draw(&mut canvas, &texture, &mut terminal, true);
+        let pixels = read_pixels(&canvas, &texture);
+        assert_eq!(cell_pixel(&canvas, &pixels, 5.5, 3.5), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_smooth_scroll_starts_from_the_old_position() {
+        let Some((mut canvas, texture)) = canvas(200, 100) else {
+            return;
+        };
+        canvas.set_smooth_scroll(Some(SmoothScroll {
+            duration: std::time::Duration::from_secs(3600),
+            easing: crate::renderer::gpu::Easing::Linear,
+        }));
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes(b"\x1b[41m \x1b[0m\r\n\n\n");
+        draw(&mut canvas, &texture, &mut terminal, false);
+        assert!(!canvas.is_animating());
+
+        // Scrolled a line, the red cell is still drawn on the first row
+        terminal.process_bytes(b"\n");
+        draw(&mut canvas, &texture, &mut terminal, false);
+        assert!(canvas.is_animating());
+        let pixels = read_pixels(&canvas, &texture);
+        assert_eq!(cell_pixel(&canvas, &pixels, 0.5, 0.5), [205, 49, 49, 255]);
+
+        // Without animation it jumps out of view
+        canvas.set_smooth_scroll(None);
+        draw(&mut canvas, &texture, &mut terminal, false);
+        assert!(!canvas.is_animating());
+        let pixels = read_pixels(&canvas, &texture);
+        assert_eq!(cell_pixel(&canvas, &pixels, 0.5, 0.5), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_only_changed_rows_are_rebuilt() {
+        let Some((mut canvas, texture)) = canvas(200, 100) else {
+            return;
+        };
+        let mut terminal = Terminal::new(10, 4);
+        draw(&mut canvas, &texture, &mut terminal, false);
+        assert_eq!(canvas.rows_built, 4);
+
+        // Nothing changed
+        draw(&mut canvas, &texture, &mut terminal, false);
+        assert_eq!(canvas.rows_built, 1);
+
+        // Writing on the cursor's row rebuilds it, and moving the cursor
+        // rebuilds the row it left as well
+        terminal.process_bytes(b"\x1b[3;1Hx");
+        draw(&mut canvas, &texture, &mut terminal, false);
+        assert_eq!(canvas.rows_built, 2);
+
+        canvas.set_padding(5.0, 5.0);
+        draw(&mut canvas, &texture, &mut terminal, false);
+        assert_eq!(canvas.rows_built, 4);
+    }
+}
@@ -0,0 +1,118 @@
+//! Persistent instance buffer holding the cells of the viewport
+//!
+//! Each viewport row owns a fixed region of `stride` instance slots, so a
+//! damaged row is rewritten in place without touching the others. Slots past
+//! the end of a row are zeroed and draw nothing.
+
+use super::vertex::CellInstance;
+
+/// Row regions are rounded up to a multiple of this many slots, leaving room
+/// for the extra glyphs of ligatures
+const STRIDE_ALIGN: usize = 16;
+
+pub(super) struct CellBuffer {
+    buffer: wgpu::Buffer,
+    /// Instances of each viewport row as last built
+    rows: Vec<Vec<CellInstance>>,
+    /// Rows changed since the last upload
+    dirty: Vec<bool>,
+    /// Instance slots per row region
+    stride: usize,
+    /// Number of rows the regions in the buffer are laid out for
+    laid_out_rows: usize,
+    /// Dirty rows padded to their regions, reused between uploads
+    scratch: Vec<CellInstance>,
+}
+
+impl CellBuffer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        // Room for an 80x24 grid to start with
+        let stride = 80usize.next_multiple_of(STRIDE_ALIGN);
+        Self {
+            buffer: create_buffer(device, stride * 24),
+            rows: Vec::new(),
+            dirty: Vec::new(),
+            stride,
+            laid_out_rows: 0,
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Number of instances to draw, including the empty slots of each region
+    pub fn instance_count(&self) -> u32 {
+        (self.laid_out_rows * self.stride) as u32
+    }
+
+    /// Match the number of viewport rows; new rows start out empty and dirty
+    pub fn set_row_count(&mut self, rows: usize) {
+        self.rows.resize_with(rows, Vec::new);
+        self.dirty.resize(rows, true);
+    }
+
+    /// Clear a row to rebuild its instances, marking it for upload
+    pub fn row_mut(&mut self, row: usize) -> &mut Vec<CellInstance> {
+        self.dirty[row] = true;
+        let instances = &mut self.rows[row];
+        instances.clear();
+        instances
+    }
+
+    /// Write the dirty rows into the buffer
+    ///
+    /// When the row count changed or a row outgrew its region, the regions
+    /// are laid out again, growing the buffer if needed, and every row is
+    /// written.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let longest = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        if longest > self.stride || self.rows.len() != self.laid_out_rows {
+            self.stride = self.stride.max(longest.next_multiple_of(STRIDE_ALIGN));
+            self.laid_out_rows = self.rows.len();
+            self.dirty.fill(true);
+
+            let needed = self.laid_out_rows * self.stride;
+            if needed * std::mem::size_of::<CellInstance>() > self.buffer.size() as usize {
+                self.buffer = create_buffer(device, needed);
+            }
+        }
+
+        // Adjacent dirty rows are written together
+        let mut row = 0;
+        while row < self.rows.len() {
+            if !self.dirty[row] {
+                row += 1;
+                continue;
+            }
+            let first = row;
+            self.scratch.clear();
+            while row < self.rows.len() && self.dirty[row] {
+                let instances = &self.rows[row];
+                self.scratch.extend_from_slice(instances);
+                self.scratch.resize(
+                    self.scratch.len() + self.stride - instances.len(),
+                    CellInstance::default(),
+                );
+                row += 1;
+            }
+            let offset = first * self.stride * std::mem::size_of::<CellInstance>();
+            queue.write_buffer(
+                &self.buffer,
+                offset as wgpu::BufferAddress,
+                bytemuck::cast_slice(&self.scratch),
+            );
+        }
+        self.dirty.fill(false);
+    }
+}
+
+fn create_buffer(device: &wgpu::Device, instances: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Cell Instance Buffer"),
+        size: (instances.max(1) * std::mem::size_of::<CellInstance>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
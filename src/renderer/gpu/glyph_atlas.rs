@@ -1,58 +1,160 @@
 use anyhow::Result;
+use std::collections::HashSet;
+use unicode_width::UnicodeWidthChar;
 
-#[derive(Clone, Copy)]
-pub(super) struct AtlasPosition {
+use crate::renderer::atlas::{AtlasAllocator, AtlasStats, GlyphKey, PageChange};
+use crate::renderer::color_glyphs;
+use crate::renderer::custom_glyphs;
+use crate::renderer::font::{CellMetrics, FontCollection, FontStyle, ResolvedGlyph};
+use crate::renderer::raster::{self, GlyphMask};
+
+/// Pages start at this size in texels and double as they fill
+const INITIAL_PAGE_SIZE: u32 = 512;
+
+/// Largest page size; once reached, further glyphs go to new pages
+const MAX_PAGE_SIZE: u32 = 2048;
+
+/// Pages per kind before least recently used glyphs are evicted
+const MAX_PAGES: usize = 4;
+
+/// Texture format of a page, chosen by the kind of glyphs it holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(super) enum PageKind {
+    /// Coverage masks, tinted with the cell's foreground color
+    Mono,
+    /// Premultiplied color glyphs (emoji), drawn as-is
+    Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(super) struct PageId {
+    pub kind: PageKind,
+    pub index: usize,
+}
+
+impl PageId {
+    /// Number identifying the page among pages of both kinds, stored in cell instances
+    pub fn slot(self) -> u32 {
+        let kind = match self.kind {
+            PageKind::Mono => 0,
+            PageKind::Color => 1,
+        };
+        (kind * MAX_PAGES + self.index) as u32
+    }
+}
+
+/// A glyph image in the atlas
+#[derive(Debug, Clone, Copy)]
+pub(super) struct AtlasEntry {
+    pub page: PageId,
+    /// Position and size of the image in its page, in texels
     pub x: u32,
     pub y: u32,
     pub width: u32,
     pub height: u32,
+    /// Offset of the image from the top-left corner of the cell, in pixels
+    pub left: i32,
+    pub top: i32,
+}
+
+impl AtlasEntry {
+    /// Whether the glyph has no visible pixels (and no quad to draw)
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+struct Page {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+/// The pages of one kind and the allocator managing their space
+struct PageSet {
+    kind: PageKind,
+    format: wgpu::TextureFormat,
+    allocator: AtlasAllocator<GlyphKey, (i32, i32)>,
+    pages: Vec<Page>,
+    /// Glyphs that didn't fit, so each is logged only once
+    unplaced: HashSet<GlyphKey>,
+}
+
+impl PageSet {
+    fn new(kind: PageKind, format: wgpu::TextureFormat) -> Self {
+        Self {
+            kind,
+            format,
+            allocator: AtlasAllocator::new(INITIAL_PAGE_SIZE, MAX_PAGE_SIZE, MAX_PAGES),
+            pages: Vec::new(),
+            unplaced: HashSet::new(),
+        }
+    }
+
+    fn bytes_per_texel(&self) -> u32 {
+        match self.kind {
+            PageKind::Mono => 1,
+            PageKind::Color => 4,
+        }
+    }
+
+    fn get(&mut self, key: &GlyphKey) -> Option<AtlasEntry> {
+        let (slot, (left, top)) = self.allocator.get(key)?;
+        Some(AtlasEntry {
+            page: PageId {
+                kind: self.kind,
+                index: slot.page,
+            },
+            x: slot.x,
+            y: slot.y,
+            width: slot.width,
+            height: slot.height,
+            left,
+            top,
+        })
+    }
+}
+
+/// A rasterized glyph waiting to be uploaded
+struct GlyphImage {
+    width: u32,
+    height: u32,
+    /// One byte per texel for mono pages, four for color pages
+    pixels: Vec<u8>,
+    left: i32,
+    top: i32,
+}
+
+impl From<GlyphMask> for GlyphImage {
+    fn from(mask: GlyphMask) -> Self {
+        Self {
+            width: mask.width as u32,
+            height: mask.height as u32,
+            pixels: mask.pixels,
+            left: mask.left,
+            top: mask.top,
+        }
+    }
 }
 
 /// Glyph Atlas for efficient text rendering
+///
+/// Glyph images are packed tightly into texture pages managed by an
+/// `AtlasAllocator`: coverage masks in `R8Unorm` pages and color glyphs in
+/// `Rgba8Unorm` pages. Pages grow, multiply and evict least recently used
+/// glyphs as needed, so texture coordinates are handed out in texels and
+/// normalized by the shader.
 pub(super) struct GlyphAtlas {
-    pub texture: wgpu::Texture,
     pub bind_group_layout: wgpu::BindGroupLayout,
-    pub bind_group: wgpu::BindGroup,
-    pub width: u32,
-    pub height: u32,
-    next_x: u32,
-    next_y: u32,
-    row_height: u32,
-    cache: std::collections::HashMap<char, AtlasPosition>,
-    cell_width: u32,
-    cell_height: u32,
-    baseline_y: f32,
+    /// Bind group for quads that don't sample a page (backgrounds, decorations)
+    pub solid_bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    mono: PageSet,
+    color: PageSet,
+    metrics: CellMetrics,
 }
 
 impl GlyphAtlas {
-    pub fn new(
-        device: &wgpu::Device,
-        _queue: &wgpu::Queue,
-        font: &font_kit::font::Font,
-        cell_width: u32,
-        cell_height: u32,
-    ) -> Result<Self> {
-        let width = 2048;
-        let height = 2048;
-
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Glyph Atlas"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::R8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
-
-        // Create texture view
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-
+    pub fn new(device: &wgpu::Device, metrics: &CellMetrics) -> Result<Self> {
         // Create sampler
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -87,129 +189,340 @@ impl GlyphAtlas {
             label: Some("Glyph Atlas Bind Group Layout"),
         });
 
-        // Create bind group
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-            ],
-            label: Some("Glyph Atlas Bind Group"),
-        });
-
-        // Calculate baseline
-        let metrics = font.metrics();
-        let units_per_em = metrics.units_per_em as f32;
-        let ascent = metrics.ascent / units_per_em;
-        let font_size = 16.0;
-        let baseline_y = (ascent * font_size).ceil();
+        // Solid quads still need a texture bound; a single texel will do
+        let solid_texture = create_page_texture(device, wgpu::TextureFormat::R8Unorm, 1);
+        let solid_bind_group =
+            create_page_bind_group(device, &bind_group_layout, &sampler, &solid_texture);
 
         Ok(Self {
-            texture,
             bind_group_layout,
-            bind_group,
-            width,
-            height,
-            next_x: 0,
-            next_y: 0,
-            row_height: 0,
-            cache: std::collections::HashMap::new(),
-            cell_width,
-            cell_height,
-            baseline_y,
+            solid_bind_group,
+            sampler,
+            mono: PageSet::new(PageKind::Mono, wgpu::TextureFormat::R8Unorm),
+            color: PageSet::new(PageKind::Color, wgpu::TextureFormat::Rgba8Unorm),
+            metrics: *metrics,
+        })
+    }
+
+    /// Switch to new cell metrics
+    ///
+    /// Glyphs are keyed by size, so those rasterized at the old size stay
+    /// cached until they are evicted.
+    pub fn set_metrics(&mut self, metrics: &CellMetrics) {
+        self.metrics = *metrics;
+    }
+
+    /// Start a new frame; glyphs used from now on won't be evicted until the next one
+    pub fn begin_frame(&mut self) {
+        self.mono.allocator.begin_frame();
+        self.color.allocator.begin_frame();
+    }
+
+    /// Bind group for sampling a page
+    pub fn bind_group(&self, page: PageId) -> &wgpu::BindGroup {
+        let set = match page.kind {
+            PageKind::Mono => &self.mono,
+            PageKind::Color => &self.color,
+        };
+        &set.pages[page.index].bind_group
+    }
+
+    /// Every page allocated so far, of both kinds
+    pub fn pages(&self) -> impl Iterator<Item = PageId> + '_ {
+        [&self.mono, &self.color].into_iter().flat_map(|set| {
+            (0..set.pages.len()).map(|index| PageId {
+                kind: set.kind,
+                index,
+            })
         })
     }
 
+    /// Usage counters of both page kinds, for debugging
+    pub fn stats(&self) -> AtlasStats {
+        self.mono
+            .allocator
+            .stats()
+            .merge(self.color.allocator.stats())
+    }
+
+    /// Look up the image of `ch` drawn at horizontal subpixel step `subpixel`,
+    /// rasterizing and uploading it on first use
+    ///
+    /// Returns `None` for characters no font has and for glyphs that don't
+    /// fit in the atlas even after eviction.
     pub fn get_or_rasterize(
         &mut self,
         ch: char,
-        font: &font_kit::font::Font,
+        style: FontStyle,
+        subpixel: u8,
+        fonts: &mut FontCollection,
+        device: &wgpu::Device,
         queue: &wgpu::Queue,
-    ) -> Result<AtlasPosition> {
-        if let Some(pos) = self.cache.get(&ch) {
-            return Ok(*pos);
+    ) -> Result<Option<AtlasEntry>> {
+        let metrics = self.metrics;
+
+        // Box drawing and block glyphs are generated to fit the cell exactly
+        if custom_glyphs::is_custom_glyph(ch) {
+            let key = GlyphKey {
+                face: None,
+                glyph_id: ch as u32,
+                size: metrics.font_size.to_bits(),
+                subpixel: 0,
+                synthetic_bold: false,
+                synthetic_italic: false,
+            };
+            if let Some(entry) = self.mono.get(&key) {
+                return Ok(Some(entry));
+            }
+            if let Some(bitmap) = custom_glyphs::rasterize(ch, &metrics) {
+                let image = GlyphImage {
+                    width: bitmap.width as u32,
+                    height: bitmap.height as u32,
+                    pixels: bitmap.pixels,
+                    left: 0,
+                    top: 0,
+                };
+                return self.insert(PageKind::Mono, key, image, device, queue);
+            }
         }
 
-        // Rasterize glyph
-        let glyph_id = font
-            .glyph_for_char(ch)
-            .unwrap_or(font.glyph_for_char(' ').unwrap());
-
-        use font_kit::canvas::{Canvas, Format, RasterizationOptions};
-        use font_kit::hinting::HintingOptions;
-        use pathfinder_geometry::transform2d::Transform2F;
-        use pathfinder_geometry::vector::{Vector2F, Vector2I};
-
-        // Use fixed cell size for all glyphs - ensures consistent UV mapping
-        let canvas_size = Vector2I::new(self.cell_width as i32, self.cell_height as i32);
-        let mut canvas = Canvas::new(canvas_size, Format::A8);
-
-        // Position all glyphs at baseline
-        let transform = Transform2F::from_translation(Vector2F::new(0.0, self.baseline_y));
-
-        let font_size = 16.0;
-        font.rasterize_glyph(
-            &mut canvas,
-            glyph_id,
-            font_size,
-            transform,
-            HintingOptions::None,
-            RasterizationOptions::GrayscaleAa,
-        )?;
-
-        // Find position in atlas
-        if self.next_x + self.cell_width > self.width {
-            self.next_x = 0;
-            self.next_y += self.row_height;
-            self.row_height = 0;
+        // Others are rasterized through the fallback chain
+        let Some(glyph) = fonts.resolve(ch, style, &metrics) else {
+            return Ok(None);
+        };
+        let mut key = GlyphKey {
+            face: Some(glyph.face),
+            glyph_id: glyph.glyph_id,
+            size: glyph.font_size(&metrics).to_bits(),
+            subpixel,
+            synthetic_bold: glyph.synthetic_bold,
+            synthetic_italic: glyph.synthetic_italic,
+        };
+
+        // Emoji keep their own colors in the color pages, falling back to a
+        // coverage mask when the font has no color data for the glyph
+        if glyph.color {
+            key.subpixel = 0;
+            if let Some(entry) = self.color.get(&key) {
+                return Ok(Some(entry));
+            }
+            if let Some(entry) = self.mono.get(&key) {
+                return Ok(Some(entry));
+            }
+            let cells = ch.width().unwrap_or(1).max(1);
+            if let Some(bitmap) =
+                color_glyphs::rasterize(glyph.font, glyph.glyph_id, &metrics, cells)
+            {
+                let image = GlyphImage {
+                    width: bitmap.width as u32,
+                    height: bitmap.height as u32,
+                    pixels: bitmap.pixels,
+                    left: 0,
+                    top: 0,
+                };
+                return self.insert(PageKind::Color, key, image, device, queue);
+            }
         }
 
-        if self.next_y + self.cell_height > self.height {
-            anyhow::bail!("Glyph atlas full");
+        self.get_or_rasterize_glyph(&glyph, key.subpixel, device, queue)
+    }
+
+    /// Get an already resolved glyph (e.g. from shaping) from the mono pages,
+    /// rasterizing it on a miss
+    pub fn get_or_rasterize_glyph(
+        &mut self,
+        glyph: &ResolvedGlyph,
+        subpixel: u8,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Option<AtlasEntry>> {
+        let key = GlyphKey {
+            face: Some(glyph.face),
+            glyph_id: glyph.glyph_id,
+            size: glyph.font_size(&self.metrics).to_bits(),
+            subpixel,
+            synthetic_bold: glyph.synthetic_bold,
+            synthetic_italic: glyph.synthetic_italic,
+        };
+        if let Some(entry) = self.mono.get(&key) {
+            return Ok(Some(entry));
         }
+        let mask = raster::rasterize(glyph, &self.metrics, subpixel)?;
+        self.insert(PageKind::Mono, key, mask.into(), device, queue)
+    }
 
-        let pos = AtlasPosition {
-            x: self.next_x,
-            y: self.next_y,
-            width: self.cell_width,
-            height: self.cell_height,
+    /// Reserve space for an image, apply any page change and upload it
+    fn insert(
+        &mut self,
+        kind: PageKind,
+        key: GlyphKey,
+        image: GlyphImage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Option<AtlasEntry>> {
+        let set = match kind {
+            PageKind::Mono => &mut self.mono,
+            PageKind::Color => &mut self.color,
         };
+        let Some((slot, change)) =
+            set.allocator
+                .insert(key, image.width, image.height, (image.left, image.top))
+        else {
+            // Rows with the glyph are rebuilt every frame; log it once
+            if set.unplaced.insert(key) {
+                eprintln!(
+                    "[ATLAS] No room for a {}x{} glyph: {}",
+                    image.width,
+                    image.height,
+                    set.allocator.stats()
+                );
+            }
+            return Ok(None);
+        };
+
+        match change {
+            Some(PageChange::Added { page, size }) => {
+                let texture = create_page_texture(device, set.format, size);
+                let bind_group = create_page_bind_group(
+                    device,
+                    &self.bind_group_layout,
+                    &self.sampler,
+                    &texture,
+                );
+                debug_assert_eq!(page, set.pages.len());
+                set.pages.push(Page {
+                    texture,
+                    bind_group,
+                });
+                eprintln!(
+                    "[ATLAS] Added {:?} page {} ({}x{}): {}",
+                    kind,
+                    page,
+                    size,
+                    size,
+                    set.allocator.stats()
+                );
+            }
+            Some(PageChange::Grown { page, size }) => {
+                // Copy the old contents into a larger texture; pending uploads
+                // to the old texture are applied before this submission
+                let old = &set.pages[page].texture;
+                let texture = create_page_texture(device, set.format, size);
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Glyph Atlas Grow Encoder"),
+                });
+                encoder.copy_texture_to_texture(
+                    old.as_image_copy(),
+                    texture.as_image_copy(),
+                    old.size(),
+                );
+                queue.submit(std::iter::once(encoder.finish()));
+
+                let bind_group = create_page_bind_group(
+                    device,
+                    &self.bind_group_layout,
+                    &self.sampler,
+                    &texture,
+                );
+                set.pages[page] = Page {
+                    texture,
+                    bind_group,
+                };
+                eprintln!(
+                    "[ATLAS] Grew {:?} page {} to {}x{}: {}",
+                    kind,
+                    page,
+                    size,
+                    size,
+                    set.allocator.stats()
+                );
+            }
+            None => {}
+        }
 
-        // Upload to GPU
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &self.texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d {
-                    x: pos.x,
-                    y: pos.y,
-                    z: 0,
+        if slot.width > 0 && slot.height > 0 {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &set.pages[slot.page].texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: slot.x,
+                        y: slot.y,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
                 },
-                aspect: wgpu::TextureAspect::All,
-            },
-            &canvas.pixels,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(self.cell_width),
-                rows_per_image: Some(self.cell_height),
-            },
-            wgpu::Extent3d {
-                width: self.cell_width,
-                height: self.cell_height,
-                depth_or_array_layers: 1,
+                &image.pixels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(image.width * set.bytes_per_texel()),
+                    rows_per_image: Some(image.height),
+                },
+                wgpu::Extent3d {
+                    width: slot.width,
+                    height: slot.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        Ok(Some(AtlasEntry {
+            page: PageId {
+                kind,
+                index: slot.page,
             },
-        );
+            x: slot.x,
+            y: slot.y,
+            width: slot.width,
+            height: slot.height,
+            left: image.left,
+            top: image.top,
+        }))
+    }
+}
 
-        self.next_x += self.cell_width;
-        self.row_height = self.row_height.max(self.cell_height);
+fn create_page_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    size: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Glyph Atlas Page"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
 
-        self.cache.insert(ch, pos);
-        Ok(pos)
-    }
+fn create_page_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    texture: &wgpu::Texture,
+) -> wgpu::BindGroup {
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+        label: Some("Glyph Atlas Bind Group"),
+    })
 }
@@ -0,0 +1,252 @@
+//! Pixel-smooth scrolling
+//!
+//! When the viewport moves, whether the user scrolls through the scrollback or
+//! output scrolls the screen, the drawn viewport trails the grid's by an
+//! offset in rows that eases to zero, so text glides into place instead of
+//! jumping a whole row at a time.
+
+use std::time::{Duration, Instant};
+
+use crate::TerminalState;
+
+/// Progress curve of a scroll animation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    Linear,
+    EaseOutQuad,
+    /// Starts fast and settles gently
+    #[default]
+    EaseOutCubic,
+    EaseOutExpo,
+}
+
+impl Easing {
+    /// Map the elapsed fraction of an animation `t` in `0..=1` to its progress
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseOutQuad => 1.0 - (1.0 - t).powi(2),
+            Easing::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseOutExpo if t >= 1.0 => 1.0,
+            Easing::EaseOutExpo => 1.0 - 2f32.powf(-10.0 * t),
+        }
+    }
+}
+
+/// How viewport changes are animated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmoothScroll {
+    /// Time to reach the new position
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+impl Default for SmoothScroll {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_millis(120),
+            easing: Easing::default(),
+        }
+    }
+}
+
+/// What the viewport showed in a frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frame {
+    position: usize,
+    alternate_screen: bool,
+    size: (usize, usize),
+    synchronized_updates: u64,
+}
+
+impl Frame {
+    fn of(state: &TerminalState) -> Self {
+        let grid = &state.grid;
+        Self {
+            position: grid.scroll_position(),
+            alternate_screen: grid.use_alternate_screen,
+            size: (grid.width, grid.viewport_height),
+            synchronized_updates: state.synchronized_updates,
+        }
+    }
+}
+
+/// Follows the viewport between frames and eases the drawn position towards it
+pub(super) struct ScrollAnimation {
+    /// `None` to jump straight to the new position
+    config: Option<SmoothScroll>,
+    last_frame: Option<Frame>,
+    /// Offset in rows when the running animation started
+    from: f32,
+    start: Instant,
+}
+
+impl ScrollAnimation {
+    pub fn new() -> Self {
+        Self {
+            config: None,
+            last_frame: None,
+            from: 0.0,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: Option<SmoothScroll>) {
+        self.config = config;
+        self.from = 0.0;
+    }
+
+    /// Rows the drawn viewport trails the grid's at `now`: positive while
+    /// content scrolls up into place, negative while it scrolls down
+    ///
+    /// Only scrolling of the main screen is animated. Alternate screen apps,
+    /// resizes and synchronized updates, where an app wants its frame shown
+    /// as a whole, jump straight to the new position. The offset never
+    /// exceeds one screen.
+    pub fn offset(&mut self, state: &TerminalState, now: Instant) -> f32 {
+        let frame = Frame::of(state);
+        let last_frame = self.last_frame.replace(frame);
+        let Some(config) = self.config else {
+            return 0.0;
+        };
+
+        let animate = !state.synchronized_output
+            && !frame.alternate_screen
+            && last_frame.is_some_and(|last| {
+                !last.alternate_screen
+                    && last.size == frame.size
+                    && last.synchronized_updates == frame.synchronized_updates
+            });
+        match last_frame {
+            Some(last) if animate => {
+                if last.position != frame.position {
+                    let moved = frame.position as f32 - last.position as f32;
+                    let limit = frame.size.1 as f32;
+                    self.from = (self.current(&config, now) + moved).clamp(-limit, limit);
+                    self.start = now;
+                }
+            }
+            _ => self.from = 0.0,
+        }
+        self.current(&config, now)
+    }
+
+    fn current(&self, config: &SmoothScroll, now: Instant) -> f32 {
+        let elapsed = now.saturating_duration_since(self.start);
+        if self.from == 0.0 || elapsed >= config.duration {
+            return 0.0;
+        }
+        let t = elapsed.as_secs_f32() / config.duration.as_secs_f32();
+        self.from * (1.0 - config.easing.apply(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Terminal;
+
+    fn animation(easing: Easing) -> ScrollAnimation {
+        let mut animation = ScrollAnimation::new();
+        animation.set_config(Some(SmoothScroll {
+            duration: Duration::from_millis(100),
+            easing,
+        }));
+        animation
+    }
+
+    #[test]
+    fn test_easings_start_at_zero_and_end_at_one() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseOutQuad,
+            Easing::EaseOutCubic,
+            Easing::EaseOutExpo,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert!(easing.apply(0.5) >= 0.5, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn test_scrolling_output_eases_into_place() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut animation = animation(Easing::Linear);
+        let start = Instant::now();
+        assert_eq!(animation.offset(terminal.state(), start), 0.0);
+
+        terminal.process_bytes(b"\n\n\n\n");
+        assert_eq!(animation.offset(terminal.state(), start), 2.0);
+
+        let halfway = start + Duration::from_millis(50);
+        assert_eq!(animation.offset(terminal.state(), halfway), 1.0);
+        let done = start + Duration::from_millis(100);
+        assert_eq!(animation.offset(terminal.state(), done), 0.0);
+    }
+
+    #[test]
+    fn test_scrolling_back_animates_downwards() {
+        let mut terminal = Terminal::new(10, 3);
+        terminal.process_bytes(b"\n\n\n\n\n");
+        let mut animation = animation(Easing::Linear);
+        let now = Instant::now();
+        animation.offset(terminal.state(), now);
+
+        terminal.state_mut().grid.scroll_viewport(-2);
+        assert_eq!(animation.offset(terminal.state(), now), -2.0);
+
+        // Scrolling again mid-way continues from where the animation is
+        terminal.state_mut().grid.scroll_viewport(1);
+        assert_eq!(animation.offset(terminal.state(), now), -1.0);
+    }
+
+    #[test]
+    fn test_offset_is_limited_to_one_screen() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut animation = animation(Easing::Linear);
+        let now = Instant::now();
+        animation.offset(terminal.state(), now);
+
+        terminal.process_bytes(&[b'\n'; 20]);
+        assert_eq!(animation.offset(terminal.state(), now), 3.0);
+    }
+
+    #[test]
+    fn test_full_screen_apps_are_not_animated() {
+        let mut terminal = Terminal::new(10, 3);
+        terminal.process_bytes(b"\n\n\n\n\n");
+        let mut animation = animation(Easing::Linear);
+        let now = Instant::now();
+        animation.offset(terminal.state(), now);
+
+        // Switching to the alternate screen, scrolling inside it and switching back
+        terminal.process_bytes(b"\x1b[?1049h");
+        assert_eq!(animation.offset(terminal.state(), now), 0.0);
+        terminal.process_bytes(b"\x1b[3;1H\n\n\n\n");
+        assert_eq!(animation.offset(terminal.state(), now), 0.0);
+        terminal.process_bytes(b"\x1b[?1049l");
+        assert_eq!(animation.offset(terminal.state(), now), 0.0);
+
+        // Scrolling inside a synchronized update
+        terminal.process_bytes(b"\x1b[?2026h\n\n\n\n\x1b[?2026l");
+        assert_eq!(animation.offset(terminal.state(), now), 0.0);
+
+        // Resizing
+        terminal.process_bytes(b"\n\n\n\n");
+        terminal.resize(10, 2);
+        assert_eq!(animation.offset(terminal.state(), now), 0.0);
+    }
+
+    #[test]
+    fn test_disabled_animation_jumps() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut animation = ScrollAnimation::new();
+        let now = Instant::now();
+        animation.offset(terminal.state(), now);
+
+        terminal.process_bytes(b"\n\n\n\n");
+        assert_eq!(animation.offset(terminal.state(), now), 0.0);
+    }
+}
@@ -1,47 +1,116 @@
-// Vertex shader
+// Cells are instanced quads in pixel coordinates, drawn in two passes over the
+// same instance buffer: backgrounds first, then glyphs, so glyphs reaching
+// into neighbouring cells aren't covered by their backgrounds. Decorations
+// and the cursor are plain solid triangles drawn last.
 
-struct VertexInput {
+struct Globals {
+    screen_size: vec2<f32>,
+    offset: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> globals: Globals;
+
+struct CellInstance {
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) glyph_offset: vec2<f32>,
+    @location(3) glyph_rect: vec4<f32>,
+    @location(4) fg: vec4<f32>,
+    @location(5) bg: vec4<f32>,
+    @location(6) flags: u32,
+}
+
+struct OverlayVertex {
     @location(0) position: vec2<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) fg_color: vec4<f32>,
-    @location(3) bg_color: vec4<f32>,
+    @location(1) color: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
-    @location(1) fg_color: vec4<f32>,
-    @location(2) bg_color: vec4<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) color_glyph: u32,
+}
+
+// Must match FLAG_PAGE_MASK and FLAG_COLOR_GLYPH in vertex.rs
+const FLAG_PAGE_MASK: u32 = 0xffu;
+const FLAG_COLOR_GLYPH: u32 = 0x100u;
+
+// Corner of a quad made of the triangles (0,0) (1,0) (0,1) and (1,0) (1,1) (0,1)
+fn corner(vertex_index: u32) -> vec2<f32> {
+    let i = vertex_index % 6u;
+    let x = select(0.0, 1.0, i == 1u || i == 3u || i == 4u);
+    let y = select(0.0, 1.0, i == 2u || i == 4u || i == 5u);
+    return vec2<f32>(x, y);
+}
+
+// Pixels have their origin at the top-left, clip space at the center, y up.
+// Everything is shifted by the smooth scrolling offset.
+fn to_clip(pixel: vec2<f32>) -> vec4<f32> {
+    let ndc = (pixel + globals.offset) / globals.screen_size * 2.0 - 1.0;
+    return vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+}
+
+@vertex
+fn vs_background(@builtin(vertex_index) vertex_index: u32, cell: CellInstance) -> VertexOutput {
+    // Cells without a background have a zero size, collapsing the quad
+    var out: VertexOutput;
+    out.clip_position = to_clip(cell.position + corner(vertex_index) * cell.size);
+    out.tex_coords = vec2<f32>(0.0, 0.0);
+    out.color = cell.bg;
+    out.color_glyph = 0u;
+    return out;
 }
 
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_glyph(@builtin(vertex_index) vertex_index: u32, cell: CellInstance) -> VertexOutput {
+    // The glyph pass is drawn once per atlas page with vertices
+    // 6 * slot .. 6 * slot + 6, collapsing glyphs that are in other pages
+    let on_page = vertex_index / 6u == (cell.flags & FLAG_PAGE_MASK);
+    let size = select(vec2<f32>(0.0, 0.0), cell.glyph_rect.zw, on_page);
+    let c = corner(vertex_index);
+
     var out: VertexOutput;
-    out.tex_coords = model.tex_coords;
-    out.fg_color = model.fg_color;
-    out.bg_color = model.bg_color;
-    out.clip_position = vec4<f32>(model.position, 0.0, 1.0);
+    out.clip_position = to_clip(cell.position + cell.glyph_offset + c * size);
+    out.tex_coords = cell.glyph_rect.xy + c * cell.glyph_rect.zw;
+    out.color = cell.fg;
+    out.color_glyph = cell.flags & FLAG_COLOR_GLYPH;
     return out;
 }
 
-// Fragment shader
+@vertex
+fn vs_overlay(vertex: OverlayVertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = to_clip(vertex.position);
+    out.tex_coords = vec2<f32>(0.0, 0.0);
+    out.color = vertex.color;
+    out.color_glyph = 0u;
+    return out;
+}
 
 @group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
+var t_atlas: texture_2d<f32>;
 @group(0) @binding(1)
-var s_diffuse: sampler;
+var s_atlas: sampler;
+
+// Backgrounds, decorations and the cursor
+@fragment
+fn fs_solid(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color.rgb, 1.0);
+}
 
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let alpha = textureSample(t_diffuse, s_diffuse, in.tex_coords).r;
+fn fs_glyph(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Texture coordinates are in texels since atlas pages can grow
+    let size = vec2<f32>(textureDimensions(t_atlas));
+    let texel = textureSample(t_atlas, s_atlas, in.tex_coords / size);
 
-    // If bg_color has alpha=0, this is a background-only quad (solid color blocks)
-    if (in.bg_color.a < 0.5) {
-        return vec4<f32>(in.bg_color.rgb, 1.0);
+    // Color glyphs are already premultiplied
+    if (in.color_glyph != 0u) {
+        return texel;
     }
 
-    // Composite foreground glyph over background color
-    // mix(a, b, t) = a * (1-t) + b * t
-    let color = mix(in.bg_color.rgb, in.fg_color.rgb, alpha);
-    return vec4<f32>(color, 1.0);
+    // Coverage mask tinted with the text color, premultiplied for blending
+    return vec4<f32>(in.color.rgb * texel.r, texel.r);
 }
@@ -1,47 +1,99 @@
+/// Low bits of `CellInstance::flags`: slot of the atlas page the glyph is in
+pub(super) const FLAG_PAGE_MASK: u32 = 0xff;
+
+/// The glyph is a premultiplied image from a color page, drawn as-is rather
+/// than tinted with the text color
+pub(super) const FLAG_COLOR_GLYPH: u32 = 0x100;
+
+/// One cell of the grid, drawn as an instanced quad by the background pass and
+/// again by the glyph pass
+///
+/// All positions are in physical pixels. Unused slots of the instance buffer
+/// are zeroed, which gives them an empty background and no glyph.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct CellInstance {
+    /// Top-left corner of the cell
+    pub position: [f32; 2],
+    /// Size of the background quad; zero for no background, e.g. for the
+    /// second half of a wide character or extra glyphs of a ligature
+    pub size: [f32; 2],
+    /// Top-left corner of the glyph image relative to `position`
+    pub glyph_offset: [f32; 2],
+    /// Atlas page rectangle `(x, y, width, height)` in texels; zero width for no glyph
+    pub glyph_rect: [f32; 4],
+    pub fg: [f32; 4],
+    pub bg: [f32; 4],
+    /// Atlas page slot in `FLAG_PAGE_MASK`, plus `FLAG_COLOR_GLYPH`
+    pub flags: u32,
+}
+
+impl CellInstance {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+            0 => Float32x2, // position
+            1 => Float32x2, // size
+            2 => Float32x2, // glyph_offset
+            3 => Float32x4, // glyph_rect
+            4 => Float32x4, // fg
+            5 => Float32x4, // bg
+            6 => Uint32,    // flags
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CellInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Corner of a solid quad drawn over the cells: decorations and the cursor
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub(super) struct Vertex {
+    /// Position in physical pixels
     pub position: [f32; 2],
-    pub tex_coords: [f32; 2],
-    pub fg_color: [f32; 4],
-    pub bg_color: [f32; 4],
+    pub color: [f32; 4],
 }
 
 impl Vertex {
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+            0 => Float32x2, // position
+            1 => Float32x4, // color
+        ];
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                // position: [f32; 2] at offset 0
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                // tex_coords: [f32; 2] at offset 8
-                wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                // fg_color: [f32; 4] at offset 16
-                wgpu::VertexAttribute {
-                    offset: (std::mem::size_of::<[f32; 2]>() + std::mem::size_of::<[f32; 2]>())
-                        as wgpu::BufferAddress,
-                    shader_location: 2,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                // bg_color: [f32; 4] at offset 32
-                wgpu::VertexAttribute {
-                    offset: (std::mem::size_of::<[f32; 2]>()
-                        + std::mem::size_of::<[f32; 2]>()
-                        + std::mem::size_of::<[f32; 4]>())
-                        as wgpu::BufferAddress,
-                    shader_location: 3,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-            ],
+            attributes: &ATTRIBUTES,
         }
     }
 }
+
+/// Shader constants shared by every pass
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct Globals {
+    /// Size of the render target in physical pixels
+    pub screen_size: [f32; 2],
+    /// Translation of every cell and overlay in physical pixels, shifting
+    /// the rows by a fraction of a row while scrolling smoothly
+    pub offset: [f32; 2],
+}
+
+/// Append two triangles covering the pixel rectangle `(x, y, width, height)`
+pub(super) fn push_rect(vertices: &mut Vec<Vertex>, rect: [f32; 4], color: [f32; 4]) {
+    let [x, y, w, h] = rect;
+    let vertex = |x, y| Vertex {
+        position: [x, y],
+        color,
+    };
+    vertices.extend_from_slice(&[
+        vertex(x, y),
+        vertex(x + w, y),
+        vertex(x, y + h),
+        vertex(x + w, y),
+        vertex(x + w, y + h),
+        vertex(x, y + h),
+    ]);
+}
@@ -4,7 +4,14 @@
 //! used to display terminal content. All renderers implement the `Renderer` trait
 //! for uniform behavior.
 
-#[cfg(feature = "ui-cpu")]
+pub mod atlas;
+pub mod color_glyphs;
+pub mod custom_glyphs;
+pub mod font;
+pub mod raster;
+pub mod shaping;
+
+#[cfg(feature = "headless")]
 pub mod cpu;
 
 #[cfg(feature = "ui-gpu")]
@@ -14,9 +21,14 @@ pub mod gpu;
 #[cfg(feature = "ui-cpu")]
 pub use cpu::CpuRenderer;
 
+#[cfg(feature = "headless")]
+pub use cpu::{HeadlessRenderer, RgbaImage};
+
 #[cfg(feature = "ui-gpu")]
 pub use gpu::GpuRenderer;
 
+pub use font::{CellMetrics, DEFAULT_FONT_SIZE, FontCollection, FontStyle};
+
 /// Abstraction for different rendering backends (CPU, GPU)
 ///
 /// This trait allows code to work with both CPU and GPU renderers uniformly,
@@ -27,6 +39,31 @@ pub trait Renderer {
     /// Returns (width, height) tuple representing the size of each character cell.
     fn char_dimensions(&self) -> (f32, f32);
 
+    /// Get the current font size in physical pixels
+    fn font_size(&self) -> f32;
+
+    /// Change the font size in physical pixels
+    ///
+    /// Recomputes the cell metrics from the font and flushes any cached glyphs.
+    fn set_font_size(&mut self, font_size: f32) -> anyhow::Result<()>;
+
+    /// Set the padding between the window edge and the grid, in physical pixels
+    fn set_padding(&mut self, x: f32, y: f32);
+
+    /// Turn programming ligatures on or off
+    fn set_ligatures(&mut self, enabled: bool);
+
+    /// Set the opacity of the default background, from 0.0 (transparent) to 1.0
+    ///
+    /// Cells with another background color stay opaque. Whether the window
+    /// shows what is behind it depends on the backend and the compositor.
+    fn set_background_opacity(&mut self, opacity: f32);
+
+    /// Follow the window's keyboard focus
+    ///
+    /// While unfocused, a block cursor is drawn as a hollow box.
+    fn set_focused(&mut self, focused: bool);
+
     /// Resize the renderer surface
     ///
     /// Called when the window is resized to update the rendering surface dimensions.
@@ -40,12 +77,19 @@ pub trait Renderer {
     /// Render with custom cursor visibility (for blinking support)
     ///
     /// This method allows the caller to control cursor visibility independently.
+    /// `text_blink_visible` is the blink phase for cells with the blink attribute.
     fn render_with_blink(
         &mut self,
         state: &crate::TerminalState,
         cursor_visible: bool,
+        text_blink_visible: bool,
     ) -> anyhow::Result<()>;
 
+    /// Check whether an animation is running, e.g. smooth scrolling
+    ///
+    /// While it is, the caller keeps requesting frames.
+    fn is_animating(&self) -> bool;
+
     /// Check if renderer is initialized and ready to render
     ///
     /// Returns true if the renderer has been set up and can accept render calls.
@@ -0,0 +1,147 @@
+//! Glyph coverage rasterization shared by the CPU and GPU glyph caches
+//!
+//! Glyphs are rasterized once per subpixel position into tight coverage masks,
+//! with synthetic bold and italic applied, so the renderers only have to blend
+//! cached masks in the text color.
+
+use anyhow::Result;
+use font_kit::canvas::{Canvas, Format, RasterizationOptions};
+use font_kit::hinting::HintingOptions;
+use pathfinder_geometry::rect::RectI;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::Vector2I;
+
+use crate::renderer::atlas::SUBPIXEL_STEPS;
+use crate::renderer::font::{
+    CellMetrics, ResolvedGlyph, SYNTHETIC_BOLD_OFFSET, SYNTHETIC_ITALIC_SKEW,
+};
+
+/// A glyph's coverage, one byte per pixel, positioned relative to its cell
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlyphMask {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major coverage, 0 (transparent) to 255 (opaque)
+    pub pixels: Vec<u8>,
+    /// Offset of the mask's top-left corner from the cell's top-left corner
+    pub left: i32,
+    pub top: i32,
+}
+
+impl GlyphMask {
+    /// Whether the glyph has no visible pixels (e.g. a space)
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+/// Rasterize a glyph's coverage into an image just large enough to hold it
+///
+/// The pen sits at the cell's left edge plus the subpixel offset, on the
+/// baseline; the image offset is relative to the cell's top-left corner.
+pub fn rasterize(glyph: &ResolvedGlyph, metrics: &CellMetrics, subpixel: u8) -> Result<GlyphMask> {
+    // Position all glyphs at baseline, sheared around it for synthetic italics
+    let skew = if glyph.synthetic_italic {
+        SYNTHETIC_ITALIC_SKEW
+    } else {
+        0.0
+    };
+    let pen_x = subpixel as f32 / SUBPIXEL_STEPS as f32;
+    let transform = |dx: f32, origin: Vector2I| {
+        Transform2F::row_major(
+            1.0,
+            -skew,
+            0.0,
+            1.0,
+            pen_x + dx - origin.x() as f32,
+            metrics.baseline - origin.y() as f32,
+        )
+    };
+
+    // Fallback glyphs are scaled down to fit the cell
+    let font_size = glyph.font_size(metrics);
+    let raster_bounds = |dx: f32| -> Result<RectI> {
+        Ok(glyph.font.raster_bounds(
+            glyph.glyph_id,
+            font_size,
+            transform(dx, Vector2I::zero()),
+            HintingOptions::None,
+            RasterizationOptions::GrayscaleAa,
+        )?)
+    };
+
+    // Synthetic bold: merge in a second, offset rasterization
+    let mut bounds = raster_bounds(0.0)?;
+    if glyph.synthetic_bold {
+        let offset = raster_bounds(SYNTHETIC_BOLD_OFFSET)?;
+        bounds = RectI::from_points(
+            bounds.origin().min(offset.origin()),
+            bounds.lower_right().max(offset.lower_right()),
+        );
+    }
+    if bounds.width() <= 0 || bounds.height() <= 0 {
+        return Ok(GlyphMask::default());
+    }
+
+    let mut canvas = Canvas::new(bounds.size(), Format::A8);
+    glyph.font.rasterize_glyph(
+        &mut canvas,
+        glyph.glyph_id,
+        font_size,
+        transform(0.0, bounds.origin()),
+        HintingOptions::None,
+        RasterizationOptions::GrayscaleAa,
+    )?;
+    if glyph.synthetic_bold {
+        let mut offset_canvas = Canvas::new(bounds.size(), Format::A8);
+        glyph.font.rasterize_glyph(
+            &mut offset_canvas,
+            glyph.glyph_id,
+            font_size,
+            transform(SYNTHETIC_BOLD_OFFSET, bounds.origin()),
+            HintingOptions::None,
+            RasterizationOptions::GrayscaleAa,
+        )?;
+        for (pixel, offset_pixel) in canvas.pixels.iter_mut().zip(&offset_canvas.pixels) {
+            *pixel = (*pixel).max(*offset_pixel);
+        }
+    }
+
+    Ok(GlyphMask {
+        width: bounds.width() as usize,
+        height: bounds.height() as usize,
+        pixels: canvas.pixels,
+        left: bounds.origin_x(),
+        top: bounds.origin_y(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::font::{FontCollection, FontStyle};
+
+    #[test]
+    fn test_glyph_sits_on_baseline() {
+        let mut fonts = match FontCollection::load(&FontCollection::default_families()) {
+            Ok(fonts) => fonts,
+            Err(e) => {
+                eprintln!("Skipping raster test, no font available: {}", e);
+                return;
+            }
+        };
+        let metrics = fonts.cell_metrics(16.0);
+        let glyph = fonts
+            .resolve('x', FontStyle::Regular, &metrics)
+            .expect("font has no 'x'");
+        let mask = rasterize(&glyph, &metrics, 0).unwrap();
+
+        assert!(!mask.is_empty());
+        assert!(mask.pixels.contains(&255));
+        // An 'x' stands on the baseline and stays within the cell
+        let bottom = mask.top + mask.height as i32;
+        assert!((bottom - metrics.baseline.round() as i32).abs() <= 1);
+        assert!(mask.top > 0);
+        assert!(mask.left >= -1 && mask.left + (mask.width as i32) <= metrics.width as i32 + 1);
+    }
+}
@@ -0,0 +1,426 @@
+//! Text shaping for programming ligatures, shared by the CPU and GPU renderers
+//!
+//! Each line is split into runs of cells with identical style, broken at
+//! spaces, at the cursor and at characters the terminal font doesn't cover.
+//! Runs are shaped with rustybuzz using the face for their style. Shaped
+//! glyphs stay attached to the cell of the character they came from (their
+//! cluster), so the grid, cursor and selection keep working per cell; only
+//! drawing changes. Runs that shape to one nominal glyph per character are
+//! left to the renderers' per-cell path.
+
+use rustybuzz::{Face, UnicodeBuffer};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::renderer::custom_glyphs;
+use crate::renderer::font::{FontCollection, FontStyle, font_data};
+use crate::terminal::Cell;
+
+/// Shaped runs are cached by style and text; the cache is dropped when it
+/// grows past this many runs
+const CACHE_LIMIT: usize = 4096;
+
+const STYLES: [FontStyle; 4] = [
+    FontStyle::Regular,
+    FontStyle::Bold,
+    FontStyle::Italic,
+    FontStyle::BoldItalic,
+];
+
+/// A glyph of a shaped run, positioned relative to the cell it belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph {
+    pub glyph_id: u32,
+    /// Offset from the cell's left edge, in pixels
+    pub x: f32,
+    /// Offset from the baseline, in pixels, down is positive
+    pub y: f32,
+}
+
+/// A glyph of a shaped run in font units, relative to the run
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RunGlyph {
+    /// Cell within the run
+    cell: usize,
+    glyph_id: u32,
+    /// Offset from the cell's left edge, up is positive for `y`
+    x: i32,
+    y: i32,
+}
+
+/// Shaper output for one glyph: id, cluster and position in font units
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ShaperGlyph {
+    glyph_id: u32,
+    cluster: u32,
+    x_advance: i32,
+    x_offset: i32,
+    y_offset: i32,
+}
+
+/// Shaped glyphs of one line, by cell
+#[derive(Debug, Default)]
+pub struct ShapedLine {
+    glyphs: Vec<ShapedGlyph>,
+    /// Per cell, the range of `glyphs` it draws if it is part of a shaped run
+    cells: Vec<Option<Range<usize>>>,
+}
+
+impl ShapedLine {
+    /// Glyphs to draw at `col`, or `None` if the cell is drawn character by
+    /// character
+    ///
+    /// An empty slice means the cell's character is part of a ligature drawn
+    /// from another cell.
+    pub fn glyphs_at(&self, col: usize) -> Option<&[ShapedGlyph]> {
+        let range = self.cells.get(col)?.clone()?;
+        Some(&self.glyphs[range])
+    }
+}
+
+/// Font data of one face, parsed into a rustybuzz face when a run needs shaping
+struct FaceData {
+    data: Arc<Vec<u8>>,
+    index: u32,
+    units_per_em: f32,
+}
+
+/// Shapes lines of cells into ligatures with the terminal font
+pub struct Shaper {
+    enabled: bool,
+    /// Face data per style, in `STYLES` order
+    faces: Vec<Option<FaceData>>,
+    /// Shaped runs by style and text, `None` for runs without ligatures
+    cache: HashMap<(FontStyle, String), Option<Vec<RunGlyph>>>,
+}
+
+impl Shaper {
+    /// Prepare shaping with the four faces of a font collection
+    pub fn new(fonts: &FontCollection) -> Self {
+        let faces = STYLES
+            .iter()
+            .map(|&style| {
+                let font = &fonts.face(style).font;
+                let (data, index) = font_data(font)?;
+                Some(FaceData {
+                    data,
+                    index,
+                    units_per_em: font.metrics().units_per_em as f32,
+                })
+            })
+            .collect();
+        Self {
+            enabled: true,
+            faces,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Turn ligatures on or off
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Shape the ligatures of one line
+    ///
+    /// `cursor_col` is the cursor's column if it is on this line; ligatures
+    /// never span it, so the character under the cursor is always drawn
+    /// on its own.
+    pub fn shape_line(
+        &mut self,
+        line: &[Cell],
+        cursor_col: Option<usize>,
+        fonts: &FontCollection,
+        font_size: f32,
+    ) -> ShapedLine {
+        let mut shaped = ShapedLine::default();
+        if !self.enabled {
+            return shaped;
+        }
+
+        let runs = split_runs(line, cursor_col, |cell| {
+            let style = FontStyle::from_attributes(cell.bold, cell.italic);
+            fonts.face(style).font.glyph_for_char(cell.ch).is_some()
+        });
+        if runs.is_empty() {
+            return shaped;
+        }
+        shaped.cells = vec![None; line.len()];
+
+        for run in runs {
+            let style = FontStyle::from_attributes(line[run.start].bold, line[run.start].italic);
+            let style_index = STYLES.iter().position(|&s| s == style).unwrap_or(0);
+            let Some(face) = &self.faces[style_index] else {
+                continue;
+            };
+            let text: String = line[run.clone()].iter().map(|cell| cell.ch).collect();
+
+            if self.cache.len() >= CACHE_LIMIT {
+                self.cache.clear();
+            }
+            let glyphs = self
+                .cache
+                .entry((style, text))
+                .or_insert_with_key(|(_, text)| shape_run(face, text));
+            let Some(glyphs) = glyphs else {
+                continue;
+            };
+
+            // Attach each glyph to its cell, converting font units to pixels
+            let scale = font_size / face.units_per_em;
+            let mut next = 0;
+            for cell in 0..run.len() {
+                let start = shaped.glyphs.len();
+                while next < glyphs.len() && glyphs[next].cell == cell {
+                    let glyph = glyphs[next];
+                    shaped.glyphs.push(ShapedGlyph {
+                        glyph_id: glyph.glyph_id,
+                        x: glyph.x as f32 * scale,
+                        y: -glyph.y as f32 * scale,
+                    });
+                    next += 1;
+                }
+                shaped.cells[run.start + cell] = Some(start..shaped.glyphs.len());
+            }
+        }
+        shaped
+    }
+}
+
+/// Shape a run's text, `None` if it needs no more than one nominal glyph per character
+fn shape_run(face: &FaceData, text: &str) -> Option<Vec<RunGlyph>> {
+    let face = Face::from_slice(&face.data, face.index)?;
+
+    // Clusters are character indices, which are also cell offsets in the run
+    let mut buffer = UnicodeBuffer::new();
+    for (i, ch) in text.chars().enumerate() {
+        buffer.add(ch, i as u32);
+    }
+    buffer.guess_segment_properties();
+    let output = rustybuzz::shape(&face, &[], buffer);
+
+    let shaper_glyphs: Vec<ShaperGlyph> = output
+        .glyph_infos()
+        .iter()
+        .zip(output.glyph_positions())
+        .map(|(info, position)| ShaperGlyph {
+            glyph_id: info.glyph_id,
+            cluster: info.cluster,
+            x_advance: position.x_advance,
+            x_offset: position.x_offset,
+            y_offset: position.y_offset,
+        })
+        .collect();
+    let nominal: Vec<u32> = text
+        .chars()
+        .map(|ch| face.glyph_index(ch).map_or(0, |id| id.0 as u32))
+        .collect();
+    place_glyphs(&shaper_glyphs, &nominal)
+}
+
+/// Position shaped glyphs relative to the cells of their clusters
+///
+/// Glyphs of one cluster are laid out with their advances from the cluster's
+/// cell, keeping every cluster on the grid regardless of the font's advances.
+/// Returns `None` when the output is exactly the `nominal` glyph of each
+/// character, unmoved.
+fn place_glyphs(glyphs: &[ShaperGlyph], nominal: &[u32]) -> Option<Vec<RunGlyph>> {
+    let unchanged = glyphs.len() == nominal.len()
+        && glyphs
+            .iter()
+            .zip(nominal)
+            .enumerate()
+            .all(|(i, (glyph, &id))| {
+                glyph.cluster == i as u32
+                    && glyph.glyph_id == id
+                    && glyph.x_offset == 0
+                    && glyph.y_offset == 0
+            });
+    if unchanged {
+        return None;
+    }
+
+    let mut placed = Vec::with_capacity(glyphs.len());
+    let mut cluster = None;
+    let mut pen = 0;
+    for glyph in glyphs {
+        if cluster != Some(glyph.cluster) {
+            cluster = Some(glyph.cluster);
+            pen = 0;
+        }
+        placed.push(RunGlyph {
+            cell: glyph.cluster as usize,
+            glyph_id: glyph.glyph_id,
+            x: pen + glyph.x_offset,
+            y: glyph.y_offset,
+        });
+        pen += glyph.x_advance;
+    }
+    Some(placed)
+}
+
+/// Whether two cells draw their text the same way
+fn same_style(a: &Cell, b: &Cell) -> bool {
+    a.bold == b.bold
+        && a.italic == b.italic
+        && a.fg == b.fg
+        && a.bg == b.bg
+        && a.reverse == b.reverse
+        && a.faint == b.faint
+        && a.blink == b.blink
+        && a.conceal == b.conceal
+}
+
+/// Split a line into runs worth shaping
+///
+/// Runs hold at least two cells with identical style whose characters pass
+/// `in_font`. They break at spaces, double-width characters, procedurally
+/// drawn glyphs and on both sides of the cursor.
+fn split_runs(
+    line: &[Cell],
+    cursor_col: Option<usize>,
+    in_font: impl Fn(&Cell) -> bool,
+) -> Vec<Range<usize>> {
+    let shapeable = |cell: &Cell| {
+        !cell.wide
+            && !cell.wide_spacer
+            && cell.ch != ' '
+            && !cell.ch.is_control()
+            && !custom_glyphs::is_custom_glyph(cell.ch)
+            && in_font(cell)
+    };
+
+    let mut runs = Vec::new();
+    let mut start: Option<usize> = None;
+    for (col, cell) in line.iter().enumerate() {
+        let continues = start.is_some_and(|start| {
+            same_style(&line[start], cell) && Some(col) != cursor_col && Some(col - 1) != cursor_col
+        });
+        if continues && shapeable(cell) {
+            continue;
+        }
+        if let Some(start) = start.take()
+            && col - start >= 2
+        {
+            runs.push(start..col);
+        }
+        if shapeable(cell) {
+            start = Some(col);
+        }
+    }
+    if let Some(start) = start
+        && line.len() - start >= 2
+    {
+        runs.push(start..line.len());
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::Color;
+
+    fn line(text: &str) -> Vec<Cell> {
+        text.chars()
+            .map(|ch| Cell::new(ch, Color::new(255, 255, 255), Color::new(0, 0, 0)))
+            .collect()
+    }
+
+    fn glyph(glyph_id: u32, cluster: u32, x_advance: i32) -> ShaperGlyph {
+        ShaperGlyph {
+            glyph_id,
+            cluster,
+            x_advance,
+            x_offset: 0,
+            y_offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_runs_break_at_spaces() {
+        let cells = line("a -> b => c");
+        assert_eq!(split_runs(&cells, None, |_| true), vec![2..4, 7..9]);
+    }
+
+    #[test]
+    fn test_runs_break_on_style_change() {
+        let mut cells = line("!==");
+        cells[2].bold = true;
+        assert_eq!(split_runs(&cells, None, |_| true), vec![0..2]);
+
+        let mut cells = line("!==");
+        cells[1].fg = Color::new(255, 0, 0);
+        assert!(split_runs(&cells, None, |_| true).is_empty());
+    }
+
+    #[test]
+    fn test_runs_never_span_the_cursor() {
+        let cells = line("a===b");
+        assert_eq!(split_runs(&cells, Some(2), |_| true), vec![0..2, 3..5]);
+        assert_eq!(split_runs(&cells, Some(0), |_| true), vec![1..5]);
+        assert_eq!(split_runs(&cells, Some(4), |_| true), vec![0..4]);
+    }
+
+    #[test]
+    fn test_runs_skip_characters_outside_the_font() {
+        let cells = line("->│<-");
+        assert_eq!(split_runs(&cells, None, |_| true), vec![0..2, 3..5]);
+        assert_eq!(split_runs(&cells, None, |cell| cell.ch != '>'), vec![3..5]);
+    }
+
+    #[test]
+    fn test_nominal_glyphs_are_not_shaped() {
+        let glyphs = [glyph(10, 0, 600), glyph(11, 1, 600)];
+        assert_eq!(place_glyphs(&glyphs, &[10, 11]), None);
+    }
+
+    #[test]
+    fn test_substituted_glyphs_stay_in_their_cells() {
+        // A ligature drawn from the last cell over a spacer glyph, as in Fira Code
+        let glyphs = [glyph(90, 0, 600), glyph(91, 1, 600)];
+        let placed = place_glyphs(&glyphs, &[10, 11]).unwrap();
+        assert_eq!(
+            placed,
+            vec![
+                RunGlyph {
+                    cell: 0,
+                    glyph_id: 90,
+                    x: 0,
+                    y: 0
+                },
+                RunGlyph {
+                    cell: 1,
+                    glyph_id: 91,
+                    x: 0,
+                    y: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merged_cluster_is_drawn_from_its_first_cell() {
+        // One glyph for three characters, then a character after it
+        let glyphs = [glyph(95, 0, 1800), glyph(12, 3, 600)];
+        let placed = place_glyphs(&glyphs, &[10, 10, 10, 12]).unwrap();
+        assert_eq!(placed.len(), 2);
+        assert_eq!((placed[0].cell, placed[0].x), (0, 0));
+        assert_eq!((placed[1].cell, placed[1].x), (3, 0));
+    }
+
+    #[test]
+    fn test_glyphs_within_a_cluster_follow_each_other() {
+        // A base glyph and a mark positioned back over it
+        let mut mark = glyph(50, 0, 0);
+        mark.x_offset = -300;
+        mark.y_offset = 100;
+        let placed = place_glyphs(&[glyph(20, 0, 600), mark], &[20]).unwrap();
+        assert_eq!((placed[1].cell, placed[1].x, placed[1].y), (0, 300, 100));
+    }
+}
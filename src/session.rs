@@ -4,8 +4,44 @@
 //! with shell process management for applications that need terminal functionality
 //! without a full UI.
 
+use crate::asciicast::{Playback, Recorder, Recording};
 use crate::{Shell, Terminal, TerminalState};
 use anyhow::Result;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Longest a synchronized update (mode 2026) may hold back frames before one
+/// is shown anyway, in case the app never ends it
+pub const SYNCHRONIZED_OUTPUT_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// Tracks the synchronized update in progress, if any
+#[derive(Debug, Default)]
+struct SynchronizedOutput {
+    /// When the update in progress was first seen, and its number in
+    /// `TerminalState::synchronized_updates`
+    started: Option<(Instant, u64)>,
+}
+
+impl SynchronizedOutput {
+    /// Follow the mode after output was processed at `now`
+    fn update(&mut self, state: &TerminalState, now: Instant) {
+        self.started = match self.started {
+            _ if !state.synchronized_output => None,
+            Some((since, update)) if update == state.synchronized_updates => Some((since, update)),
+            _ => Some((now, state.synchronized_updates)),
+        };
+    }
+
+    /// When the update in progress times out
+    fn deadline(&self) -> Option<Instant> {
+        self.started
+            .map(|(since, _)| since + SYNCHRONIZED_OUTPUT_TIMEOUT)
+    }
+
+    fn frame_ready(&self, now: Instant) -> bool {
+        self.deadline().is_none_or(|deadline| now >= deadline)
+    }
+}
 
 /// Terminal session that orchestrates Terminal and Shell
 ///
@@ -18,6 +54,11 @@ use anyhow::Result;
 pub struct TerminalSession {
     terminal: Terminal,
     shell: Option<Shell>,
+    synchronized_output: SynchronizedOutput,
+    /// Records output, input and resizes while set
+    recorder: Option<Recorder>,
+    /// Plays a recording into the terminal in place of a shell
+    playback: Option<Playback>,
 }
 
 impl TerminalSession {
@@ -34,7 +75,29 @@ impl TerminalSession {
             eprintln!("Failed to create shell");
         }
 
-        Ok(Self { terminal, shell })
+        Ok(Self {
+            terminal,
+            shell,
+            synchronized_output: SynchronizedOutput::default(),
+            recorder: None,
+            playback: None,
+        })
+    }
+
+    /// Create a session that plays a recording instead of running a shell
+    ///
+    /// `process_output` plays the events due at `speed` times the original
+    /// speed. The terminal keeps the recording's size, following its resizes
+    /// rather than `resize`. Fails unless `speed` is finite and positive.
+    pub fn playback(recording: Recording, speed: f64) -> Result<Self> {
+        let playback = Playback::new(recording, speed)?;
+        Ok(Self {
+            terminal: playback.player().new_terminal(),
+            shell: None,
+            synchronized_output: SynchronizedOutput::default(),
+            recorder: None,
+            playback: Some(playback),
+        })
     }
 
     /// Process shell output and update terminal state
@@ -46,8 +109,17 @@ impl TerminalSession {
     /// Should be called regularly (e.g., in the event loop) to keep the
     /// terminal display synchronized with shell output.
     pub fn process_output(&mut self) -> bool {
+        if let Some(playback) = &mut self.playback {
+            let now = Instant::now();
+            if playback.update(&mut self.terminal, now) {
+                self.terminal.state_mut().grid.viewport_to_end();
+                self.synchronized_output.update(self.terminal.state(), now);
+            }
+        }
+
         if let Some(ref mut shell) = self.shell {
             let mut has_data = false;
+            shell.clear_notification();
 
             // Drain all available messages from the channel
             loop {
@@ -56,6 +128,9 @@ impl TerminalSession {
                         has_data = true;
                         // Process bytes through the terminal (VTE parser + state updates)
                         self.terminal.process_bytes(&data);
+                        if let Some(recorder) = &mut self.recorder {
+                            record(recorder.output(&data), &mut self.recorder);
+                        }
                     }
                     Err(std::sync::mpsc::TryRecvError::Empty) => {
                         // No more data available right now
@@ -71,6 +146,8 @@ impl TerminalSession {
 
             if has_data {
                 self.terminal.state_mut().grid.viewport_to_end();
+                self.synchronized_output
+                    .update(self.terminal.state(), Instant::now());
             }
 
             // Send any pending responses back to the shell
@@ -90,6 +167,9 @@ impl TerminalSession {
     pub fn write_input(&mut self, bytes: &[u8]) -> Result<()> {
         if let Some(shell) = &mut self.shell {
             shell.write(bytes)?;
+            if let Some(recorder) = &mut self.recorder {
+                record(recorder.input(bytes), &mut self.recorder);
+            }
         }
         Ok(())
     }
@@ -99,8 +179,15 @@ impl TerminalSession {
     /// Updates both the terminal grid size and the PTY window size.
     /// The terminal grid preserves existing content and clamps the cursor.
     pub fn resize(&mut self, cols: usize, rows: usize) {
+        if self.playback.is_some() {
+            return;
+        }
+
         // Resize terminal (preserves existing content and clamps cursor)
         self.terminal.resize(cols, rows);
+        if let Some(recorder) = &mut self.recorder {
+            record(recorder.resize(cols, rows), &mut self.recorder);
+        }
 
         // Update shell PTY size
         if let Some(shell) = &mut self.shell
@@ -124,4 +211,163 @@ impl TerminalSession {
     pub fn has_shell(&self) -> bool {
         self.shell.is_some()
     }
+
+    /// Run `notify` from the shell's reader thread when output is waiting
+    ///
+    /// Call `process_output` in response, e.g. after waking an event loop.
+    /// Bursts of output notify once until the next `process_output`.
+    pub fn set_output_notifier(&mut self, notify: impl Fn() + Send + 'static) {
+        if let Some(shell) = &mut self.shell {
+            shell.set_output_notifier(notify);
+        }
+    }
+
+    /// Record output, input and resizes to an asciicast v2 file at `path`
+    ///
+    /// Replaces any recording in progress. Recording stops by itself when
+    /// writing fails.
+    pub fn start_recording(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let grid = &self.terminal.state().grid;
+        self.recorder = Some(Recorder::create(path, grid.width, grid.viewport_height)?);
+        Ok(())
+    }
+
+    /// Finish the recording in progress, if any
+    pub fn stop_recording(&mut self) -> Result<()> {
+        match self.recorder.take() {
+            Some(mut recorder) => recorder.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Check whether output is being recorded
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Check whether the session plays a recording
+    pub fn is_playback(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Jump by `delta` seconds in the recording being played, back when negative
+    pub fn seek_playback(&mut self, delta: f64) {
+        if let Some(playback) = &mut self.playback {
+            playback.seek_by(&mut self.terminal, delta, Instant::now());
+            self.terminal.state_mut().grid.damage_all();
+        }
+    }
+
+    /// When the next event of the recording being played is due
+    pub fn playback_deadline(&self) -> Option<Instant> {
+        self.playback.as_ref()?.next_deadline()
+    }
+
+    /// Check whether the screen is fit to be shown
+    ///
+    /// While an app holds a synchronized update (mode 2026) open, the screen
+    /// may be half redrawn and frames should wait, up to
+    /// `SYNCHRONIZED_OUTPUT_TIMEOUT`. Once the update ends, the next frame
+    /// shows all of it.
+    pub fn frame_ready(&self) -> bool {
+        self.synchronized_output.frame_ready(Instant::now())
+    }
+
+    /// When a frame held back by a synchronized update is due anyway, if one is
+    pub fn frame_deadline(&self) -> Option<Instant> {
+        self.synchronized_output.deadline()
+    }
+
+    /// Render the current screen to an image, e.g. to save with `RgbaImage::save_png`
+    ///
+    /// Draws with the default fonts at the default size, without a window.
+    #[cfg(feature = "headless")]
+    pub fn screenshot(&self) -> Result<crate::renderer::RgbaImage> {
+        use crate::renderer::{DEFAULT_FONT_SIZE, FontCollection, HeadlessRenderer, Renderer};
+
+        let fonts = FontCollection::load(&FontCollection::default_families())?;
+        let grid = &self.state().grid;
+        let mut renderer = HeadlessRenderer::with_grid_size(
+            fonts,
+            DEFAULT_FONT_SIZE,
+            grid.width,
+            grid.viewport_height,
+        );
+        renderer.render(self.state())?;
+        Ok(renderer.image())
+    }
+}
+
+/// Stop recording after a failed write, reporting the error
+fn record(result: Result<()>, recorder: &mut Option<Recorder>) {
+    if let Err(e) = result {
+        eprintln!("Recording stopped: {:#}", e);
+        *recorder = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_wait_for_synchronized_update() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut sync = SynchronizedOutput::default();
+        let start = Instant::now();
+        assert!(sync.frame_ready(start));
+
+        terminal.process_bytes(b"\x1b[?2026hhalf");
+        sync.update(terminal.state(), start);
+        assert!(!sync.frame_ready(start));
+
+        // More output of the same update doesn't extend the wait
+        let later = start + Duration::from_millis(100);
+        terminal.process_bytes(b"\x1b[?2026hway");
+        sync.update(terminal.state(), later);
+        assert!(!sync.frame_ready(later));
+
+        terminal.process_bytes(b"\x1b[?2026l");
+        sync.update(terminal.state(), later);
+        assert!(sync.frame_ready(later));
+    }
+
+    #[test]
+    fn test_synchronized_update_times_out() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut sync = SynchronizedOutput::default();
+        let start = Instant::now();
+        terminal.process_bytes(b"\x1b[?2026h");
+        sync.update(terminal.state(), start);
+
+        assert_eq!(sync.deadline(), Some(start + SYNCHRONIZED_OUTPUT_TIMEOUT));
+        assert!(sync.frame_ready(start + SYNCHRONIZED_OUTPUT_TIMEOUT));
+
+        // A new update after the old one ended waits again
+        terminal.process_bytes(b"\x1b[?2026l\x1b[?2026h");
+        let later = start + Duration::from_secs(1);
+        sync.update(terminal.state(), later);
+        assert!(!sync.frame_ready(later));
+    }
+
+    #[test]
+    fn test_playback_session() {
+        let recording = Recording::parse(
+            "{\"version\": 2, \"width\": 10, \"height\": 3}\n[1.0, \"o\", \"played\"]",
+        )
+        .unwrap();
+        // Fast enough for the event to be due right away
+        let mut session = TerminalSession::playback(recording, 1e9).unwrap();
+        assert!(!session.has_shell());
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(session.process_output());
+        assert_eq!(
+            session.state().grid.screen_text(crate::TextFormat::Plain),
+            "played"
+        );
+
+        // The recording decides the size
+        session.resize(40, 10);
+        assert_eq!(session.state().grid.width, 10);
+    }
 }
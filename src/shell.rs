@@ -9,10 +9,31 @@ use nix::libc;
 use nix::pty::{Winsize, openpty};
 use nix::unistd::{ForkResult, fork};
 use std::os::fd::{AsRawFd, OwnedFd};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, channel};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
+/// Callback run on the reader thread when output arrives
+type OutputNotifier = Box<dyn Fn() + Send>;
+
+/// Wakes the consumer of the output, once until it clears the notification
+#[derive(Clone, Default)]
+struct OutputWaker {
+    notifier: Arc<Mutex<Option<OutputNotifier>>>,
+    notified: Arc<AtomicBool>,
+}
+
+impl OutputWaker {
+    fn wake(&self) {
+        if !self.notified.swap(true, Ordering::AcqRel)
+            && let Some(notify) = &*self.notifier.lock().unwrap()
+        {
+            notify();
+        }
+    }
+}
+
 /// Shell process with PTY and background I/O
 ///
 /// Manages a shell process running in a pseudo-terminal, including
@@ -22,6 +43,7 @@ pub struct Shell {
     master: Arc<OwnedFd>,
     /// Receiver for shell output from the background reader thread
     pub receiver: Receiver<Vec<u8>>,
+    waker: OutputWaker,
 }
 
 /// Iterator for reading from PTY in a background thread.
@@ -81,6 +103,8 @@ impl Shell {
                 let reader = PtyReader {
                     master: Arc::clone(&master),
                 };
+                let waker = OutputWaker::default();
+                let reader_waker = waker.clone();
 
                 // Spawn reader thread with iterator pattern
                 thread::spawn(move || {
@@ -89,14 +113,19 @@ impl Shell {
                             // Main thread has dropped the receiver, exit
                             break;
                         }
+                        reader_waker.wake();
                     }
-                    // Reader iterator ended (EOF or error)
+                    // Reader iterator ended (EOF or error); close the channel
+                    // before waking so the consumer sees the exit
                     // Arc cleanup happens automatically when reader is dropped
+                    drop(tx);
+                    reader_waker.wake();
                 });
 
                 Ok(Shell {
                     master,
                     receiver: rx,
+                    waker,
                 })
             }
             ForkResult::Child => {
@@ -133,6 +162,21 @@ impl Shell {
         }
     }
 
+    /// Run `notify` on the reader thread when output arrives or the shell exits
+    ///
+    /// Lets an event loop sleep until there is output instead of polling
+    /// `receiver`. Bursts of output only notify once: the next notification
+    /// comes after `clear_notification`, which the consumer calls before
+    /// draining the receiver.
+    pub fn set_output_notifier(&mut self, notify: impl Fn() + Send + 'static) {
+        *self.waker.notifier.lock().unwrap() = Some(Box::new(notify));
+    }
+
+    /// Re-arm the output notifier
+    pub fn clear_notification(&self) {
+        self.waker.notified.store(false, Ordering::Release);
+    }
+
     /// Write data to the shell's input
     pub fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         nix::unistd::write(self.master.as_ref(), buf).map_err(|e| e.into())
@@ -158,3 +202,27 @@ impl Shell {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_output_notifies_once_until_cleared() {
+        let waker = OutputWaker::default();
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+        *waker.notifier.lock().unwrap() = Some(Box::new(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }));
+
+        waker.wake();
+        waker.wake();
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+
+        waker.notified.store(false, Ordering::Release);
+        waker.wake();
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+    }
+}
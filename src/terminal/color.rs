@@ -18,6 +18,18 @@ impl Color {
         Self::new(255, 255, 255)
     }
 
+    /// Background of cells without a background color: palette color 0
+    ///
+    /// Renderers leave such cells to the window background, which may be translucent.
+    pub fn default_background() -> Self {
+        Self::from_ansi_index(0)
+    }
+
+    /// Lowest palette index with exactly this color, if any
+    pub fn to_ansi_index(self) -> Option<u8> {
+        (0..=255).find(|&index| Self::from_ansi_index(index) == self)
+    }
+
     // ANSI 256 color palette
     pub fn from_ansi_index(index: u8) -> Self {
         match index {
@@ -277,4 +289,13 @@ mod tests {
         assert_eq!(c1.g, c2.g);
         assert_eq!(c1.b, c2.b);
     }
+
+    #[test]
+    fn test_to_ansi_index() {
+        assert_eq!(Color::new(205, 49, 49).to_ansi_index(), Some(1));
+        assert_eq!(Color::from_ansi_index(196).to_ansi_index(), Some(196));
+        // Bright white and white share a color; the lower index wins
+        assert_eq!(Color::from_ansi_index(15).to_ansi_index(), Some(7));
+        assert_eq!(Color::new(1, 2, 3).to_ansi_index(), None);
+    }
 }
@@ -154,6 +154,24 @@ pub enum CsiCommand {
     /// Deletes n characters at cursor, shifting remaining chars left
     DeleteCharacter { n: u16 },
 
+    /// Insert Character (ICH)
+    /// ESC[{n}@
+    /// Default: n=1
+    /// Inserts n blank characters at cursor, shifting remaining chars right
+    InsertCharacter { n: u16 },
+
+    /// Set Left and Right Margins (DECSLRM)
+    /// ESC[{left};{right}s
+    /// Default: left=1, right=width
+    /// Only valid while DECLRMM (mode 69) is set; otherwise `ESC[s` is
+    /// Save Cursor (SCOSC) and is handled as such
+    SetLeftRightMargins { left: u16, right: u16 },
+
+    /// Restore Cursor (SCORC)
+    /// ESC[u
+    /// Restores the cursor position saved by `ESC[s`
+    RestoreCursor,
+
     /// Reset Mode (RM)
     /// ESC[{mode}l
     /// Default: mode=0
@@ -272,6 +290,15 @@ impl CsiCommand {
             'P' => Ok(Self::DeleteCharacter {
                 n: Self::param_or(params, 0, 1),
             }),
+            '@' => Ok(Self::InsertCharacter {
+                n: Self::param_or(params, 0, 1),
+            }),
+            's' => {
+                let left = Self::param_or(params, 0, 1);
+                let right = Self::param_or(params, 1, 0);
+                Ok(Self::SetLeftRightMargins { left, right })
+            }
+            'u' => Ok(Self::RestoreCursor),
             'l' => Ok(Self::ResetMode {
                 mode: Self::param_or(params, 0, 0),
             }),
@@ -329,6 +356,10 @@ pub enum DecPrivateMode {
     /// Mode 25
     ShowCursor,
 
+    /// Left/Right Margin Mode (DECLRMM)
+    /// Mode 69
+    LeftRightMarginMode,
+
     /// Mouse tracking (X11 mouse protocol)
     /// Mode 1000
     MouseTracking,
@@ -398,6 +429,7 @@ impl DecPrivateMode {
             12 => Self::CursorBlink,
             25 => Self::ShowCursor,
             47 => Self::AlternateScreenBuffer,
+            69 => Self::LeftRightMarginMode,
             1000 => Self::MouseTracking,
             1001 => Self::MouseHiliteTracking,
             1002 => Self::MouseCellMotion,
@@ -448,6 +480,9 @@ pub enum SgrParameter {
     /// Crossed-out (strikethrough)
     CrossedOut,
 
+    /// Doubly underlined
+    DoubleUnderline,
+
     /// Normal intensity (not bold or faint)
     NormalIntensity,
 
@@ -469,6 +504,12 @@ pub enum SgrParameter {
     /// Not crossed out
     NotCrossedOut,
 
+    /// Overlined
+    Overline,
+
+    /// Not overlined
+    NotOverlined,
+
     /// Set foreground color (basic 8 colors)
     /// Colors 30-37: black, red, green, yellow, blue, magenta, cyan, white
     ForegroundColor(u8),
@@ -524,6 +565,7 @@ impl SgrParameter {
             7 => Self::ReverseVideo,
             8 => Self::Conceal,
             9 => Self::CrossedOut,
+            21 => Self::DoubleUnderline,
             22 => Self::NormalIntensity,
             23 => Self::NotItalic,
             24 => Self::NotUnderlined,
@@ -537,6 +579,8 @@ impl SgrParameter {
             40..=47 => Self::BackgroundColor((code - 40) as u8),
             48 => Self::ExtendedBackground,
             49 => Self::DefaultBackground,
+            53 => Self::Overline,
+            55 => Self::NotOverlined,
             58 => Self::ExtendedUnderlineColor,
             59 => Self::DefaultUnderlineColor,
             90..=97 => Self::BrightForegroundColor((code - 90) as u8),
@@ -594,6 +638,11 @@ mod tests {
             DecPrivateMode::from_mode(47),
             DecPrivateMode::AlternateScreenBuffer
         );
+        assert_eq!(
+            DecPrivateMode::from_mode(69),
+            DecPrivateMode::LeftRightMarginMode
+        );
+        assert_eq!(DecPrivateMode::from_mode(6), DecPrivateMode::OriginMode);
 
         match DecPrivateMode::from_mode(9999) {
             DecPrivateMode::Unknown(9999) => {}
@@ -608,6 +657,9 @@ mod tests {
         assert_eq!(SgrParameter::from_code(3), SgrParameter::Italic);
         assert_eq!(SgrParameter::from_code(4), SgrParameter::Underline);
         assert_eq!(SgrParameter::from_code(7), SgrParameter::ReverseVideo);
+        assert_eq!(SgrParameter::from_code(21), SgrParameter::DoubleUnderline);
+        assert_eq!(SgrParameter::from_code(53), SgrParameter::Overline);
+        assert_eq!(SgrParameter::from_code(55), SgrParameter::NotOverlined);
         assert_eq!(
             SgrParameter::from_code(38),
             SgrParameter::ExtendedForeground
@@ -0,0 +1,414 @@
+//! Export of the screen or scrollback to HTML, ANSI text and SVG
+//!
+//! Exports keep colors, text attributes and the cursor, for pasting terminal
+//! output into bug reports and documentation. Rows are exported as they
+//! appear on screen, without the empty rows below the content.
+
+use super::color::Color;
+use super::grid::{Cell, UnderlineStyle};
+use super::state::TerminalState;
+use super::text::{Style, TextFormat, TextWriter};
+
+/// Width of a cell in exported SVG, in pixels
+const SVG_CELL_WIDTH: usize = 9;
+/// Height of a cell in exported SVG, in pixels
+const SVG_CELL_HEIGHT: usize = 18;
+/// Font size of exported SVG; monospace fonts are about 0.6 em wide
+const SVG_FONT_SIZE: usize = 15;
+/// Distance from the top of a cell to the text baseline in exported SVG
+const SVG_BASELINE: usize = 14;
+
+/// Blinking text in HTML and SVG, at the speed of the terminal's blink timer
+const BLINK_CSS: &str = concat!(
+    ".blink { animation: blink 1.06s step-end infinite; }\n",
+    "@keyframes blink { 50% { opacity: 0; } }"
+);
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Self-contained HTML page with colored spans
+    Html,
+    /// Text with SGR sequences that reproduces the colors when printed to a terminal
+    Ansi,
+    /// SVG image of the cells
+    Svg,
+}
+
+/// Rows to export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportScope {
+    /// The active screen
+    #[default]
+    Screen,
+    /// The scrollback and the screen below it
+    Scrollback,
+}
+
+impl ExportFormat {
+    /// File name extension for the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Html => "html",
+            ExportFormat::Ansi => "ans",
+            ExportFormat::Svg => "svg",
+        }
+    }
+
+    /// Export the rows in `scope`
+    ///
+    /// A visible cursor is exported as a block in inverted colors.
+    pub fn export(self, state: &TerminalState, scope: ExportScope) -> String {
+        let rows = export_rows(state, scope);
+        match self {
+            ExportFormat::Html => html(&rows),
+            ExportFormat::Ansi => ansi(&rows),
+            ExportFormat::Svg => svg(&rows, state.grid.width),
+        }
+    }
+}
+
+/// Copies of the rows in `scope` with the cursor drawn in, trimmed of
+/// trailing empty rows
+fn export_rows(state: &TerminalState, scope: ExportScope) -> Vec<Vec<Cell>> {
+    let grid = &state.grid;
+    let start = match scope {
+        ExportScope::Screen => grid.screen_start(),
+        ExportScope::Scrollback => 0,
+    };
+    let end = (grid.screen_start() + grid.viewport_height).min(grid.cells.len());
+    let mut rows = grid.cells[start..end].to_vec();
+
+    let cursor_row = (grid.screen_start() + state.cursor.row).checked_sub(start);
+    if state.show_cursor
+        && let Some(line) = cursor_row.and_then(|row| rows.get_mut(row))
+    {
+        let col = state.cursor.col;
+        let cols = if line.get(col).is_some_and(|cell| cell.wide) {
+            col..col + 2
+        } else {
+            col..col + 1
+        };
+        for cell in line.iter_mut().take(cols.end).skip(cols.start) {
+            std::mem::swap(&mut cell.fg, &mut cell.bg);
+            cell.default_bg = false;
+        }
+    }
+
+    let writer = TextWriter::new(TextFormat::Sgr);
+    while rows.last().is_some_and(|row| writer.trimmed_len(row) == 0) {
+        rows.pop();
+    }
+    rows
+}
+
+fn ansi(rows: &[Vec<Cell>]) -> String {
+    let mut writer = TextWriter::new(TextFormat::Sgr);
+    for (index, row) in rows.iter().enumerate() {
+        if index > 0 {
+            writer.newline();
+        }
+        writer.trimmed(row);
+    }
+    let mut out = writer.finish();
+    if !rows.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+fn html(rows: &[Vec<Cell>]) -> String {
+    let plain = Style::plain();
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>rustty</title>\n<style>\n\
+         pre {{ background: {}; color: {}; font-family: monospace; line-height: 1.2; padding: 1em; }}\n\
+         {}\n</style>\n</head>\n<body>\n<pre>",
+        hex(plain.bg),
+        hex(plain.fg),
+        BLINK_CSS
+    );
+    for (index, row) in rows.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        for (style, _, cells) in runs(trimmed(row)) {
+            let mut css = css(&style, "color");
+            if !style.default_bg {
+                css.insert(0, format!("background:{}", hex(style.bg)));
+            }
+            let styled = style.blink || !css.is_empty();
+            if styled {
+                out.push_str("<span");
+                if style.blink {
+                    out.push_str(" class=\"blink\"");
+                }
+                if !css.is_empty() {
+                    out.push_str(&format!(" style=\"{}\"", css.join(";")));
+                }
+                out.push('>');
+            }
+            for cell in cells.iter().filter(|cell| !cell.wide_spacer) {
+                cell.chars().for_each(|ch| escape(ch, &mut out));
+            }
+            if styled {
+                out.push_str("</span>");
+            }
+        }
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
+
+fn svg(rows: &[Vec<Cell>], cols: usize) -> String {
+    let plain = Style::plain();
+    let (width, height) = (cols * SVG_CELL_WIDTH, rows.len() * SVG_CELL_HEIGHT);
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" \
+         font-family=\"monospace\" font-size=\"{2}\">\n<style>\n{3}\n</style>\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"{4}\"/>\n",
+        width,
+        height,
+        SVG_FONT_SIZE,
+        BLINK_CSS,
+        hex(plain.bg)
+    );
+
+    for (row, line) in rows.iter().enumerate() {
+        let y = row * SVG_CELL_HEIGHT;
+        let runs = runs(trimmed(line));
+        for (style, col, cells) in &runs {
+            if !style.default_bg {
+                out.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                    col * SVG_CELL_WIDTH,
+                    y,
+                    cells.len() * SVG_CELL_WIDTH,
+                    SVG_CELL_HEIGHT,
+                    hex(style.bg)
+                ));
+            }
+        }
+
+        let mut text = String::new();
+        for (style, col, cells) in &runs {
+            let decorated =
+                style.underline != UnderlineStyle::None || style.strikethrough || style.overline;
+            if !decorated && cells.iter().all(|cell| cell.ch == ' ') {
+                continue;
+            }
+
+            // Every character is placed in its cell; combining characters
+            // follow the character they belong to
+            let mut xs = Vec::new();
+            let mut chars = String::new();
+            for (offset, cell) in cells.iter().enumerate() {
+                if cell.wide_spacer {
+                    continue;
+                }
+                let x = (col + offset) * SVG_CELL_WIDTH;
+                let end = x + if cell.wide { 2 } else { 1 } * SVG_CELL_WIDTH;
+                for (index, ch) in cell.chars().enumerate() {
+                    xs.push(if index == 0 { x } else { end }.to_string());
+                    escape(ch, &mut chars);
+                }
+            }
+
+            text.push_str(&format!("<tspan x=\"{}\"", xs.join(" ")));
+            if style.blink {
+                text.push_str(" class=\"blink\"");
+            }
+            let css = css(style, "fill");
+            if !css.is_empty() {
+                text.push_str(&format!(" style=\"{}\"", css.join(";")));
+            }
+            text.push_str(&format!(">{}</tspan>", chars));
+        }
+        if !text.is_empty() {
+            out.push_str(&format!(
+                "<text y=\"{}\" fill=\"{}\" xml:space=\"preserve\">{}</text>\n",
+                y + SVG_BASELINE,
+                hex(plain.fg),
+                text
+            ));
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// A row without the blanks at its end
+fn trimmed(row: &[Cell]) -> &[Cell] {
+    &row[..TextWriter::new(TextFormat::Sgr).trimmed_len(row)]
+}
+
+/// Split a row into runs of cells with the same style, each with the
+/// column it starts at
+fn runs(cells: &[Cell]) -> Vec<(Style, usize, &[Cell])> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for col in 1..=cells.len() {
+        if col == cells.len() || Style::of(&cells[col]) != Style::of(&cells[start]) {
+            runs.push((Style::of(&cells[start]), start, &cells[start..col]));
+            start = col;
+        }
+    }
+    runs
+}
+
+/// CSS declarations for a style, except its background; `color` names the
+/// property taking the foreground color
+fn css(style: &Style, color: &str) -> Vec<String> {
+    let plain = Style::plain();
+    let mut css = Vec::new();
+
+    // Concealed text is drawn in the background color, so it can still be copied
+    let fg = if style.conceal { style.bg } else { style.fg };
+    if fg != plain.fg {
+        css.push(format!("{}:{}", color, hex(fg)));
+    }
+    if style.bold {
+        css.push("font-weight:bold".to_string());
+    }
+    if style.italic {
+        css.push("font-style:italic".to_string());
+    }
+    if style.faint {
+        css.push("opacity:0.5".to_string());
+    }
+
+    let lines: Vec<&str> = [
+        (style.underline != UnderlineStyle::None, "underline"),
+        (style.strikethrough, "line-through"),
+        (style.overline, "overline"),
+    ]
+    .into_iter()
+    .filter_map(|(set, line)| set.then_some(line))
+    .collect();
+    if !lines.is_empty() {
+        css.push(format!("text-decoration-line:{}", lines.join(" ")));
+    }
+    let line_style = match style.underline {
+        UnderlineStyle::Double => Some("double"),
+        UnderlineStyle::Curly => Some("wavy"),
+        UnderlineStyle::Dotted => Some("dotted"),
+        UnderlineStyle::Dashed => Some("dashed"),
+        UnderlineStyle::None | UnderlineStyle::Single => None,
+    };
+    if let Some(line_style) = line_style {
+        css.push(format!("text-decoration-style:{}", line_style));
+    }
+    if let Some(underline_color) = style.underline_color {
+        css.push(format!("text-decoration-color:{}", hex(underline_color)));
+    }
+    css
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// Append a character, escaped for HTML and XML text
+fn escape(ch: char, out: &mut String) {
+    match ch {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        _ => out.push(ch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Terminal;
+    use std::path::Path;
+
+    /// Reference exports, regenerated with `RUSTTY_UPDATE_GOLDEN=1 cargo test`
+    const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/golden");
+
+    /// A screen with every attribute, palette and RGB colors, wide and
+    /// combining characters, markup to escape and the cursor after a prompt
+    fn terminal() -> Terminal {
+        let mut terminal = Terminal::new(40, 6);
+        terminal.process_bytes(
+            "\x1b[1mbold\x1b[0m \x1b[2mfaint\x1b[0m \x1b[3mitalic\x1b[0m \x1b[4:3;58;5;1mcurly\x1b[0m \
+             \x1b[9mstrike\x1b[0m \x1b[53mover\x1b[0m\r\n\
+             \x1b[31mred\x1b[0m \x1b[38;5;208m208\x1b[0m \x1b[38;2;1;2;3;48;2;200;200;0mrgb\x1b[0m \
+             \x1b[5mblink\x1b[0m \x1b[7mreverse\x1b[0m \x1b[8mhidden\x1b[0m\r\n\
+             <a & \"b\"> \u{4e2d}\u{6587} e\u{301}\r\n\
+             $ "
+                .as_bytes(),
+        );
+        terminal
+    }
+
+    /// Compare an export with `testdata/golden/<name>`
+    fn assert_golden(name: &str, export: &str) {
+        let path = Path::new(GOLDEN_DIR).join(name);
+        if std::env::var_os("RUSTTY_UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(GOLDEN_DIR).unwrap();
+            std::fs::write(&path, export).unwrap();
+            return;
+        }
+
+        let golden = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+            panic!(
+                "{}: {}; set RUSTTY_UPDATE_GOLDEN=1 to create it",
+                path.display(),
+                e
+            );
+        });
+        assert_eq!(export, golden, "export differs from {}", path.display());
+    }
+
+    #[test]
+    fn test_golden_html_export() {
+        let terminal = terminal();
+        let html = ExportFormat::Html.export(terminal.state(), ExportScope::Screen);
+        assert_golden("export.html", &html);
+    }
+
+    #[test]
+    fn test_golden_ansi_export() {
+        let terminal = terminal();
+        let ansi = ExportFormat::Ansi.export(terminal.state(), ExportScope::Screen);
+        assert_golden("export.ans", &ansi);
+    }
+
+    #[test]
+    fn test_golden_svg_export() {
+        let terminal = terminal();
+        let svg = ExportFormat::Svg.export(terminal.state(), ExportScope::Screen);
+        assert_golden("export.svg", &svg);
+    }
+
+    #[test]
+    fn test_ansi_export_reproduces_the_screen() {
+        let mut terminal = terminal();
+        terminal.process_bytes(b"\x1b[?25l");
+        let ansi = ExportFormat::Ansi.export(terminal.state(), ExportScope::Screen);
+
+        let mut copy = Terminal::new(40, 6);
+        copy.process_bytes(ansi.replace('\n', "\r\n").as_bytes());
+        assert_eq!(
+            copy.state().grid.screen_text(TextFormat::Sgr),
+            terminal.state().grid.screen_text(TextFormat::Sgr)
+        );
+    }
+
+    #[test]
+    fn test_scrollback_export() {
+        let mut terminal = Terminal::new(10, 2);
+        terminal.process_bytes(b"\x1b[?25lone\r\ntwo\r\nthree");
+        let state = terminal.state();
+        assert_eq!(
+            ExportFormat::Ansi.export(state, ExportScope::Screen),
+            "two\nthree\n"
+        );
+        assert_eq!(
+            ExportFormat::Ansi.export(state, ExportScope::Scrollback),
+            "one\ntwo\nthree\n"
+        );
+    }
+}
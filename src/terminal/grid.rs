@@ -1,7 +1,42 @@
 use super::color::Color;
+use super::command::EraseMode;
+
+/// Underline style of a cell (SGR 4, 4:x and 21)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnderlineStyle {
+    /// Not underlined
+    #[default]
+    None,
+    /// Single straight line
+    Single,
+    /// Two straight lines
+    Double,
+    /// Wavy line (undercurl)
+    Curly,
+    /// Dotted line
+    Dotted,
+    /// Dashed line
+    Dashed,
+}
+
+impl UnderlineStyle {
+    /// Parse the subparameter of `CSI 4:x m`
+    pub fn from_subparam(param: u16) -> Self {
+        match param {
+            0 => Self::None,
+            2 => Self::Double,
+            3 => Self::Curly,
+            4 => Self::Dotted,
+            5 => Self::Dashed,
+            _ => Self::Single,
+        }
+    }
+}
 
 /// Terminal cell with character, colors, and text attributes
-/// Note: bold is rendered (brightens color), italic is rendered (cyan tint), underline is rendered (line below text)
+/// Note: bold and italic select the matching font face (synthesized when the
+/// font has none); with `bold_is_bright` set, bold text in one of the eight
+/// basic colors also uses its bright variant
 #[derive(Clone, Copy)]
 pub struct Cell {
     pub ch: char,
@@ -9,8 +44,32 @@ pub struct Cell {
     pub bg: Color,
     pub bold: bool,
     pub italic: bool,
-    pub underline: bool,
+    pub underline: UnderlineStyle,
     pub reverse: bool,
+    /// Faint/dim intensity (SGR 2)
+    pub faint: bool,
+    /// Blinking text (SGR 5/6), hidden during the off phase of the blink timer
+    pub blink: bool,
+    /// Concealed text (SGR 8), glyph is not drawn
+    pub conceal: bool,
+    /// Crossed-out text (SGR 9)
+    pub strikethrough: bool,
+    /// Overlined text (SGR 53)
+    pub overline: bool,
+    /// Underline color (SGR 58), `None` draws the underline in the foreground color
+    pub underline_color: Option<Color>,
+    /// First half of a double-width character (CJK, emoji); the next cell is its spacer
+    pub wide: bool,
+    /// Second half of a double-width character, holds no character of its own
+    pub wide_spacer: bool,
+    /// Zero-width characters following `ch` (combining accents, variation
+    /// selectors, joiners); more than two are dropped
+    pub combining: [Option<char>; 2],
+    /// Last cell of a line that auto-wrap continued onto the next line
+    pub wrapped: bool,
+    /// `bg` is the terminal's default background (SGR 49) rather than a set
+    /// color, even one that looks the same; only it follows the background opacity
+    pub default_bg: bool,
 }
 
 impl Cell {
@@ -20,10 +79,8 @@ impl Cell {
             ch,
             fg,
             bg,
-            bold: false,
-            italic: false,
-            underline: false,
-            reverse: false,
+            default_bg: false,
+            ..Self::default()
         }
     }
 
@@ -33,7 +90,7 @@ impl Cell {
         bg: Color,
         bold: bool,
         italic: bool,
-        underline: bool,
+        underline: UnderlineStyle,
         reverse: bool,
     ) -> Self {
         Self {
@@ -44,8 +101,22 @@ impl Cell {
             italic,
             underline,
             reverse,
+            default_bg: false,
+            ..Self::default()
+        }
+    }
+
+    /// Attach a zero-width character to the cell's character
+    pub fn push_combining(&mut self, c: char) {
+        if let Some(slot) = self.combining.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(c);
         }
     }
+
+    /// The cell's character followed by its combining characters
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        std::iter::once(self.ch).chain(self.combining.iter().flatten().copied())
+    }
 }
 
 impl Default for Cell {
@@ -53,15 +124,27 @@ impl Default for Cell {
         Self {
             ch: ' ',
             fg: Color::white(),
-            bg: Color::black(),
+            bg: Color::default_background(),
             bold: false,
             italic: false,
-            underline: false,
+            underline: UnderlineStyle::None,
             reverse: false,
+            faint: false,
+            blink: false,
+            conceal: false,
+            strikethrough: false,
+            overline: false,
+            underline_color: None,
+            wide: false,
+            wide_spacer: false,
+            combining: [None; 2],
+            wrapped: false,
+            default_bg: true,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct TerminalGrid {
     pub width: usize,
     pub cells: Vec<Vec<Cell>>,
@@ -75,6 +158,13 @@ pub struct TerminalGrid {
     // Scrolling region support (DECSTBM)
     pub scroll_top: usize,    // Top margin (0-indexed, inclusive)
     pub scroll_bottom: usize, // Bottom margin (0-indexed, inclusive)
+    // Left/right margin support (DECSLRM, only settable while DECLRMM is enabled)
+    pub scroll_left: usize,  // Left margin (0-indexed, inclusive)
+    pub scroll_right: usize, // Right margin (0-indexed, inclusive)
+    // Viewport rows changed since the renderer last cleared the damage
+    damaged_rows: Vec<bool>,
+    // Lines dropped from the front of the main screen's scrollback so far
+    lines_dropped: usize,
 }
 
 impl TerminalGrid {
@@ -90,6 +180,80 @@ impl TerminalGrid {
             use_alternate_screen: false,
             scroll_top: 0,
             scroll_bottom: viewport_height.saturating_sub(1),
+            scroll_left: 0,
+            scroll_right: width.saturating_sub(1),
+            damaged_rows: vec![true; viewport_height],
+            lines_dropped: 0,
+        }
+    }
+
+    /// Mark an absolute row as changed, if it is visible
+    fn damage_row(&mut self, row: usize) {
+        if let Some(damaged) = row
+            .checked_sub(self.viewport_start)
+            .and_then(|row| self.damaged_rows.get_mut(row))
+        {
+            *damaged = true;
+        }
+    }
+
+    /// Mark screen rows top..=bottom as changed
+    fn damage_screen_rows(&mut self, top: usize, bottom: usize) {
+        let start = self.screen_start();
+        for row in start + top..=start + bottom {
+            self.damage_row(row);
+        }
+    }
+
+    /// Mark every visible row as changed
+    ///
+    /// Used when the viewport moves or whole screens are swapped or cleared.
+    pub fn damage_all(&mut self) {
+        self.damaged_rows.fill(true);
+    }
+
+    /// Check whether a viewport row changed since the damage was last cleared
+    pub fn is_row_damaged(&self, row: usize) -> bool {
+        self.damaged_rows.get(row).copied().unwrap_or(false)
+    }
+
+    /// Check whether any viewport row changed since the damage was last cleared
+    pub fn has_damage(&self) -> bool {
+        self.damaged_rows.contains(&true)
+    }
+
+    /// Forget the damage once the renderer has drawn it
+    pub fn clear_damage(&mut self) {
+        self.damaged_rows.fill(false);
+    }
+
+    /// Absolute row index of the first row of the active screen
+    ///
+    /// Cursor rows and scrolling margins are relative to this row. It differs
+    /// from `viewport_start` when the user has scrolled back into history.
+    pub fn screen_start(&self) -> usize {
+        self.cells.len().saturating_sub(self.viewport_height)
+    }
+
+    /// Position of the viewport in the main screen's output, counting lines
+    /// dropped from the scrollback
+    ///
+    /// Unlike `viewport_start`, it keeps growing as output scrolls once the
+    /// scrollback is full, so renderers can follow scrolling between frames.
+    pub fn scroll_position(&self) -> usize {
+        self.lines_dropped + self.viewport_start
+    }
+
+    /// Move the viewport `delta` lines down (positive) or up into the
+    /// scrollback (negative), stopping at either end
+    pub fn scroll_viewport(&mut self, delta: isize) {
+        let viewport_start = self
+            .viewport_start
+            .saturating_add_signed(delta)
+            .min(self.screen_start());
+        if self.viewport_start != viewport_start {
+            self.viewport_start = viewport_start;
+            self.damage_all();
         }
     }
 
@@ -99,17 +263,61 @@ impl TerminalGrid {
         }
 
         if col < self.width {
+            self.damage_row(row);
+            self.break_wide_pair(row, col);
             self.cells[row][col] = cell;
+
+            // A double-width character also claims the next column
+            if cell.wide && col + 1 < self.width {
+                self.break_wide_pair(row, col + 1);
+                self.cells[row][col + 1] = Cell {
+                    ch: ' ',
+                    wide: false,
+                    wide_spacer: true,
+                    combining: [None; 2],
+                    ..cell
+                };
+            }
         }
 
         if self.cells.len() > self.max_scrollback {
             let excess = self.cells.len() - self.max_scrollback;
             self.cells.drain(0..excess);
             self.viewport_start = self.viewport_start.saturating_sub(excess);
+            self.lines_dropped += excess;
+            self.damage_all();
+        }
+    }
+
+    /// Attach a zero-width character to the character at `row`, `col`
+    pub fn push_combining(&mut self, row: usize, col: usize, c: char) {
+        if let Some(cell) = self.cells.get_mut(row).and_then(|line| line.get_mut(col)) {
+            cell.push_combining(c);
+            self.damage_row(row);
+        }
+    }
+
+    /// Mark `row` as continuing onto the next row through auto-wrap
+    pub fn set_wrapped(&mut self, row: usize) {
+        if let Some(cell) = self.cells.get_mut(row).and_then(|line| line.last_mut()) {
+            cell.wrapped = true;
+        }
+    }
+
+    /// Blank the other half of a double-width character about to be overwritten
+    fn break_wide_pair(&mut self, row: usize, col: usize) {
+        let line = &mut self.cells[row];
+        if line[col].wide_spacer && col > 0 && line[col - 1].wide {
+            line[col - 1].ch = ' ';
+            line[col - 1].wide = false;
+        }
+        if line[col].wide && col + 1 < line.len() {
+            line[col + 1].wide_spacer = false;
         }
     }
 
     pub fn clear_viewport(&mut self) {
+        self.damage_all();
         let end = (self.viewport_start + self.viewport_height).min(self.cells.len());
         for row in self.viewport_start..end {
             for cell in &mut self.cells[row] {
@@ -120,17 +328,106 @@ impl TerminalGrid {
 
     pub fn clear_line(&mut self, row: usize) {
         if row < self.cells.len() {
+            self.damage_row(row);
             for cell in &mut self.cells[row] {
                 *cell = Cell::default();
             }
         }
     }
 
-    pub fn viewport_to_end(&mut self) {
-        if self.cells.len() > self.viewport_height {
-            self.viewport_start = self.cells.len() - self.viewport_height;
+    /// Erase part of the screen (ED), filling erased cells with `blank`
+    ///
+    /// `row` and `col` are the screen-relative cursor position. `AllWithScrollback`
+    /// (ED 3) only drops the scrollback history and leaves the screen intact, as xterm does.
+    pub fn erase_in_display(&mut self, row: usize, col: usize, mode: EraseMode, blank: Cell) {
+        let start = self.screen_start();
+        let end = (start + self.viewport_height).min(self.cells.len());
+
+        match mode {
+            EraseMode::ToEnd => {
+                self.erase_in_line(row, col, EraseMode::ToEnd, blank);
+                self.damage_screen_rows(row, self.viewport_height.saturating_sub(1));
+                for line in &mut self.cells[(start + row + 1).min(end)..end] {
+                    line.fill(blank);
+                }
+            }
+            EraseMode::ToBeginning => {
+                self.damage_screen_rows(0, row);
+                for line in &mut self.cells[start..(start + row).min(end)] {
+                    line.fill(blank);
+                }
+                self.erase_in_line(row, col, EraseMode::ToBeginning, blank);
+            }
+            EraseMode::All => {
+                self.damage_all();
+                for line in &mut self.cells[start..end] {
+                    line.fill(blank);
+                }
+            }
+            EraseMode::AllWithScrollback => {
+                self.clear_scrollback();
+            }
+        }
+    }
+
+    /// Erase part of a screen line (EL), filling erased cells with `blank`
+    ///
+    /// `ToEnd` and `ToBeginning` both include the cursor column.
+    pub fn erase_in_line(&mut self, row: usize, col: usize, mode: EraseMode, blank: Cell) {
+        let abs_row = self.screen_start() + row;
+        self.damage_row(abs_row);
+        let Some(line) = self.cells.get_mut(abs_row) else {
+            return;
+        };
+        let col = col.min(line.len().saturating_sub(1));
+
+        match mode {
+            EraseMode::ToEnd => line[col..].fill(blank),
+            EraseMode::ToBeginning => line[..=col].fill(blank),
+            EraseMode::All | EraseMode::AllWithScrollback => line.fill(blank),
+        }
+    }
+
+    /// Erase n cells starting at a screen position (ECH), without shifting
+    pub fn erase_chars(&mut self, row: usize, col: usize, count: usize, blank: Cell) {
+        let abs_row = self.screen_start() + row;
+        self.damage_row(abs_row);
+        let Some(line) = self.cells.get_mut(abs_row) else {
+            return;
+        };
+        let end = col.saturating_add(count).min(line.len());
+
+        if col < end {
+            line[col..end].fill(blank);
+        }
+    }
+
+    /// Drop all scrollback history above the main screen (ED 3)
+    ///
+    /// The scrollback belongs to the main screen, so it is cleared even while
+    /// the alternate screen is active.
+    pub fn clear_scrollback(&mut self) {
+        let (cells, viewport_start) = if self.use_alternate_screen {
+            (
+                &mut self.alternate_cells,
+                &mut self.alternate_viewport_start,
+            )
         } else {
-            self.viewport_start = 0;
+            (&mut self.cells, &mut self.viewport_start)
+        };
+
+        let history = cells.len().saturating_sub(self.viewport_height);
+        cells.drain(0..history);
+        *viewport_start = 0;
+        self.lines_dropped += history;
+        self.damage_all();
+    }
+
+    pub fn viewport_to_end(&mut self) {
+        let viewport_start = self.screen_start();
+        if self.viewport_start != viewport_start {
+            self.viewport_start = viewport_start;
+            self.damage_all();
         }
     }
 
@@ -140,6 +437,13 @@ impl TerminalGrid {
         &self.cells[start..end]
     }
 
+    /// Check whether any visible cell has the blink attribute
+    pub fn has_blinking_cells(&self) -> bool {
+        self.get_viewport()
+            .iter()
+            .any(|line| line.iter().any(|cell| cell.blink))
+    }
+
     /// Switch to the alternate screen buffer
     pub fn use_alternate_screen(&mut self) {
         if !self.use_alternate_screen {
@@ -147,6 +451,7 @@ impl TerminalGrid {
             std::mem::swap(&mut self.cells, &mut self.alternate_cells);
             std::mem::swap(&mut self.viewport_start, &mut self.alternate_viewport_start);
             self.use_alternate_screen = true;
+            self.damage_all();
         }
     }
 
@@ -157,12 +462,14 @@ impl TerminalGrid {
             std::mem::swap(&mut self.cells, &mut self.alternate_cells);
             std::mem::swap(&mut self.viewport_start, &mut self.alternate_viewport_start);
             self.use_alternate_screen = false;
+            self.damage_all();
         }
     }
 
     pub fn resize(&mut self, new_width: usize, new_viewport_height: usize) {
         // Update viewport height
         self.viewport_height = new_viewport_height;
+        self.damaged_rows = vec![true; new_viewport_height];
 
         // If width changed, resize all existing rows in BOTH buffers
         if new_width != self.width {
@@ -189,6 +496,8 @@ impl TerminalGrid {
         // Reset scrolling region to full screen on resize
         self.scroll_top = 0;
         self.scroll_bottom = self.viewport_height.saturating_sub(1);
+        self.scroll_left = 0;
+        self.scroll_right = self.width.saturating_sub(1);
     }
 
     /// Set scrolling region margins (DECSTBM)
@@ -207,31 +516,79 @@ impl TerminalGrid {
         self.scroll_bottom = self.viewport_height.saturating_sub(1);
     }
 
-    /// Insert n blank lines at the given row within scrolling region
-    /// Lines below are pushed down, lines pushed past bottom margin are deleted
-    pub fn insert_lines(&mut self, row: usize, count: usize) {
-        // Only operate within scrolling region
-        if row < self.scroll_top || row > self.scroll_bottom {
-            return;
+    /// Set left/right margins (DECSLRM)
+    pub fn set_lr_margins(&mut self, left: usize, right: usize) {
+        // Validate margins (0-indexed, inclusive)
+        if left < right && right < self.width {
+            self.scroll_left = left;
+            self.scroll_right = right;
         }
+        // If invalid, ignore the command (keep current margins)
+    }
 
-        let count = count.min(self.scroll_bottom - row + 1);
+    /// Reset left/right margins to full width
+    pub fn reset_lr_margins(&mut self) {
+        self.scroll_left = 0;
+        self.scroll_right = self.width.saturating_sub(1);
+    }
 
-        // Calculate absolute row index
-        let abs_row = self.viewport_start + row;
+    /// Check whether a column lies within the left/right margins
+    pub fn in_lr_margins(&self, col: usize) -> bool {
+        col >= self.scroll_left && col <= self.scroll_right
+    }
 
-        // Delete lines at the bottom of the scrolling region
-        for _ in 0..count {
-            if abs_row + self.scroll_bottom - row < self.cells.len() {
-                self.cells.remove(abs_row + self.scroll_bottom - row);
+    /// Check whether the margins cover every column
+    fn is_full_width(&self) -> bool {
+        self.scroll_left == 0 && self.scroll_right + 1 >= self.width
+    }
+
+    /// Scroll the scrolling region up by n lines (SU, or LF at the bottom margin)
+    ///
+    /// When the region covers the whole main screen, lines scrolled off the top
+    /// are kept in the scrollback buffer. Otherwise they are discarded.
+    pub fn scroll_up(&mut self, count: usize) {
+        let full_screen = self.scroll_top == 0
+            && self.scroll_bottom + 1 >= self.viewport_height
+            && self.is_full_width();
+
+        if full_screen && !self.use_alternate_screen {
+            self.damage_all();
+            let at_end = self.viewport_start >= self.screen_start();
+            let count = count.min(self.viewport_height);
+            for _ in 0..count {
+                self.cells.push(vec![Cell::default(); self.width]);
+            }
+
+            if self.cells.len() > self.max_scrollback {
+                let excess = self.cells.len() - self.max_scrollback;
+                self.cells.drain(0..excess);
+                self.viewport_start = self.viewport_start.saturating_sub(excess);
+                self.lines_dropped += excess;
             }
+
+            // Keep following the output unless the user scrolled back
+            if at_end {
+                self.viewport_to_end();
+            }
+        } else {
+            self.scroll_rows_up(self.scroll_top, self.scroll_bottom, count);
         }
+    }
 
-        // Insert blank lines at cursor position
-        for _ in 0..count {
-            self.cells
-                .insert(abs_row, vec![Cell::default(); self.width]);
+    /// Scroll the scrolling region down by n lines (SD, or RI at the top margin)
+    pub fn scroll_down(&mut self, count: usize) {
+        self.scroll_rows_down(self.scroll_top, self.scroll_bottom, count);
+    }
+
+    /// Insert n blank lines at the given row within scrolling region
+    /// Lines below are pushed down, lines pushed past bottom margin are deleted
+    pub fn insert_lines(&mut self, row: usize, count: usize) {
+        // Only operate within scrolling region
+        if row < self.scroll_top || row > self.scroll_bottom {
+            return;
         }
+
+        self.scroll_rows_down(row, self.scroll_bottom, count);
     }
 
     /// Delete n lines at the given row within scrolling region
@@ -242,24 +599,104 @@ impl TerminalGrid {
             return;
         }
 
-        let count = count.min(self.scroll_bottom - row + 1);
+        self.scroll_rows_up(row, self.scroll_bottom, count);
+    }
 
-        // Calculate absolute row index
-        let abs_row = self.viewport_start + row;
+    /// Insert n blank cells at the given screen position (ICH)
+    /// Cells right of the cursor shift right, cells pushed past the right margin are lost
+    pub fn insert_chars(&mut self, row: usize, col: usize, count: usize) {
+        if !self.in_lr_margins(col) {
+            return;
+        }
+
+        let right = self.scroll_right.min(self.width.saturating_sub(1));
+        let abs_row = self.screen_start() + row;
+        self.damage_row(abs_row);
+        let Some(line) = self.cells.get_mut(abs_row) else {
+            return;
+        };
+
+        let count = count.min(right - col + 1);
+        line[col..=right].rotate_right(count);
+        for cell in &mut line[col..col + count] {
+            *cell = Cell::default();
+        }
+    }
+
+    /// Delete n cells at the given screen position (DCH)
+    /// Cells right of the cursor shift left, blanks are added at the right margin
+    pub fn delete_chars(&mut self, row: usize, col: usize, count: usize) {
+        if !self.in_lr_margins(col) {
+            return;
+        }
 
-        // Delete lines at cursor position
-        for _ in 0..count {
-            if abs_row < self.cells.len() && abs_row + self.scroll_bottom - row < self.cells.len() {
-                self.cells.remove(abs_row);
+        let right = self.scroll_right.min(self.width.saturating_sub(1));
+        let abs_row = self.screen_start() + row;
+        self.damage_row(abs_row);
+        let Some(line) = self.cells.get_mut(abs_row) else {
+            return;
+        };
+
+        let count = count.min(right - col + 1);
+        line[col..=right].rotate_left(count);
+        for cell in &mut line[right + 1 - count..=right] {
+            *cell = Cell::default();
+        }
+    }
+
+    /// Shift screen rows top..=bottom up by count within the left/right margins
+    fn scroll_rows_up(&mut self, top: usize, bottom: usize, count: usize) {
+        if top > bottom || bottom >= self.viewport_height {
+            return;
+        }
+
+        let count = count.min(bottom - top + 1);
+        let start = self.screen_start();
+        self.damage_screen_rows(top, bottom);
+
+        if self.is_full_width() {
+            // Whole rows move, so rotate the row vectors themselves
+            self.cells[start + top..=start + bottom].rotate_left(count);
+            for row in start + bottom + 1 - count..=start + bottom {
+                self.cells[row] = vec![Cell::default(); self.width];
+            }
+        } else {
+            let (left, right) = (self.scroll_left, self.scroll_right);
+            for row in start + top..=start + bottom {
+                if row + count <= start + bottom {
+                    let (head, tail) = self.cells.split_at_mut(row + count);
+                    head[row][left..=right].copy_from_slice(&tail[0][left..=right]);
+                } else {
+                    self.cells[row][left..=right].fill(Cell::default());
+                }
             }
         }
+    }
+
+    /// Shift screen rows top..=bottom down by count within the left/right margins
+    fn scroll_rows_down(&mut self, top: usize, bottom: usize, count: usize) {
+        if top > bottom || bottom >= self.viewport_height {
+            return;
+        }
+
+        let count = count.min(bottom - top + 1);
+        let start = self.screen_start();
+        self.damage_screen_rows(top, bottom);
 
-        // Insert blank lines at bottom of scrolling region
-        for _ in 0..count {
-            let insert_pos = abs_row + self.scroll_bottom - row;
-            if insert_pos <= self.cells.len() {
-                self.cells
-                    .insert(insert_pos, vec![Cell::default(); self.width]);
+        if self.is_full_width() {
+            self.cells[start + top..=start + bottom].rotate_right(count);
+            for row in start + top..start + top + count {
+                self.cells[row] = vec![Cell::default(); self.width];
+            }
+        } else {
+            let (left, right) = (self.scroll_left, self.scroll_right);
+            for row in (start + top..=start + bottom).rev() {
+                if row >= start + top + count {
+                    let (head, tail) = self.cells.split_at_mut(row);
+                    tail[0][left..=right].copy_from_slice(&head[row - count][left..=right]);
+                } else {
+                    self.cells[row][left..=right].fill(Cell::default());
+                }
             }
         }
     }
@@ -541,4 +978,231 @@ mod tests {
             assert_eq!(grid.cells[i][0].ch, (i as u8 + b'A') as char);
         }
     }
+
+    #[test]
+    fn test_scroll_up_full_screen_keeps_scrollback() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.put_cell(Cell::new('A', Color::white(), Color::black()), 0, 0);
+
+        grid.scroll_up(1);
+
+        assert_eq!(grid.cells.len(), 4);
+        assert_eq!(grid.screen_start(), 1);
+        assert_eq!(grid.viewport_start, 1);
+        assert_eq!(grid.cells[0][0].ch, 'A');
+    }
+
+    #[test]
+    fn test_scroll_up_alternate_screen_discards() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.use_alternate_screen();
+        grid.put_cell(Cell::new('A', Color::white(), Color::black()), 0, 0);
+        grid.put_cell(Cell::new('B', Color::white(), Color::black()), 1, 0);
+
+        grid.scroll_up(1);
+
+        assert_eq!(grid.cells.len(), 3);
+        assert_eq!(grid.cells[0][0].ch, 'B');
+        assert_eq!(grid.cells[2][0].ch, ' ');
+    }
+
+    #[test]
+    fn test_scroll_down_within_region() {
+        let mut grid = TerminalGrid::new(10, 4);
+        for row in 0..4 {
+            grid.put_cell(
+                Cell::new((row as u8 + b'0') as char, Color::white(), Color::black()),
+                row,
+                0,
+            );
+        }
+        grid.set_scroll_region(1, 2);
+
+        grid.scroll_down(1);
+
+        assert_eq!(grid.cells[0][0].ch, '0');
+        assert_eq!(grid.cells[1][0].ch, ' ');
+        assert_eq!(grid.cells[2][0].ch, '1');
+        assert_eq!(grid.cells[3][0].ch, '3');
+    }
+
+    #[test]
+    fn test_set_lr_margins_validation() {
+        let mut grid = TerminalGrid::new(80, 24);
+
+        grid.set_lr_margins(10, 5); // left >= right: ignored
+        assert_eq!((grid.scroll_left, grid.scroll_right), (0, 79));
+
+        grid.set_lr_margins(10, 80); // right out of bounds: ignored
+        assert_eq!((grid.scroll_left, grid.scroll_right), (0, 79));
+
+        grid.set_lr_margins(10, 20);
+        assert_eq!((grid.scroll_left, grid.scroll_right), (10, 20));
+        assert!(grid.in_lr_margins(15));
+        assert!(!grid.in_lr_margins(21));
+
+        grid.resize(100, 24);
+        assert_eq!((grid.scroll_left, grid.scroll_right), (0, 99));
+    }
+
+    #[test]
+    fn test_erase_in_display_stays_within_screen() {
+        let mut grid = TerminalGrid::new(4, 2);
+        grid.cells
+            .insert(0, vec![Cell::new('h', Color::white(), Color::black()); 4]);
+        grid.cells[1][0].ch = 'a';
+        grid.cells[2][0].ch = 'b';
+
+        grid.erase_in_display(0, 0, EraseMode::All, Cell::default());
+
+        assert_eq!(grid.cells[0][0].ch, 'h');
+        assert_eq!(grid.cells[1][0].ch, ' ');
+        assert_eq!(grid.cells[2][0].ch, ' ');
+    }
+
+    #[test]
+    fn test_erase_chars_clamps_to_line() {
+        let mut grid = TerminalGrid::new(4, 1);
+        for (col, ch) in "abcd".chars().enumerate() {
+            grid.cells[0][col].ch = ch;
+        }
+
+        grid.erase_chars(0, 2, 100, Cell::default());
+
+        let text: String = grid.cells[0].iter().map(|cell| cell.ch).collect();
+        assert_eq!(text, "ab  ");
+    }
+
+    #[test]
+    fn test_clear_scrollback_keeps_screen() {
+        let mut grid = TerminalGrid::new(4, 2);
+        grid.cells.insert(0, vec![Cell::default(); 4]);
+        grid.cells.insert(0, vec![Cell::default(); 4]);
+        grid.viewport_start = 1;
+
+        grid.clear_scrollback();
+
+        assert_eq!(grid.cells.len(), 2);
+        assert_eq!(grid.viewport_start, 0);
+        assert_eq!(grid.screen_start(), 0);
+    }
+
+    #[test]
+    fn test_scroll_position_counts_dropped_lines() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.max_scrollback = 5;
+
+        grid.scroll_up(2);
+        assert_eq!(grid.viewport_start, 2);
+        assert_eq!(grid.scroll_position(), 2);
+
+        // The scrollback is full, so the viewport stays put while output scrolls
+        grid.scroll_up(2);
+        assert_eq!(grid.viewport_start, 2);
+        assert_eq!(grid.scroll_position(), 4);
+    }
+
+    #[test]
+    fn test_scroll_viewport_stops_at_either_end() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.scroll_up(3);
+        grid.clear_damage();
+
+        grid.scroll_viewport(-2);
+        assert_eq!(grid.viewport_start, 1);
+        assert!(grid.has_damage());
+
+        grid.scroll_viewport(-5);
+        assert_eq!(grid.viewport_start, 0);
+        grid.scroll_viewport(10);
+        assert_eq!(grid.viewport_start, grid.screen_start());
+    }
+
+    fn damaged_rows(grid: &TerminalGrid) -> Vec<usize> {
+        (0..grid.viewport_height)
+            .filter(|&row| grid.is_row_damaged(row))
+            .collect()
+    }
+
+    #[test]
+    fn test_new_grid_is_fully_damaged() {
+        let mut grid = TerminalGrid::new(4, 3);
+        assert_eq!(damaged_rows(&grid), vec![0, 1, 2]);
+
+        grid.clear_damage();
+        assert!(!grid.has_damage());
+    }
+
+    #[test]
+    fn test_put_cell_damages_its_row() {
+        let mut grid = TerminalGrid::new(4, 3);
+        grid.clear_damage();
+
+        grid.put_cell(Cell::new('a', Color::white(), Color::black()), 1, 2);
+
+        assert_eq!(damaged_rows(&grid), vec![1]);
+    }
+
+    #[test]
+    fn test_erase_damages_erased_rows() {
+        let mut grid = TerminalGrid::new(4, 4);
+        grid.clear_damage();
+        grid.erase_in_line(2, 0, EraseMode::ToEnd, Cell::default());
+        assert_eq!(damaged_rows(&grid), vec![2]);
+
+        grid.clear_damage();
+        grid.erase_in_display(1, 0, EraseMode::ToEnd, Cell::default());
+        assert_eq!(damaged_rows(&grid), vec![1, 2, 3]);
+
+        grid.clear_damage();
+        grid.erase_in_display(1, 0, EraseMode::ToBeginning, Cell::default());
+        assert_eq!(damaged_rows(&grid), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_region_scroll_damages_region_only() {
+        let mut grid = TerminalGrid::new(4, 5);
+        grid.set_scroll_region(1, 3);
+        grid.clear_damage();
+
+        grid.scroll_up(1);
+
+        assert_eq!(damaged_rows(&grid), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_full_screen_scroll_damages_everything() {
+        let mut grid = TerminalGrid::new(4, 3);
+        grid.clear_damage();
+
+        grid.scroll_up(1);
+
+        assert_eq!(damaged_rows(&grid), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_screen_switch_damages_everything() {
+        let mut grid = TerminalGrid::new(4, 3);
+        grid.clear_damage();
+
+        grid.use_alternate_screen();
+        assert_eq!(damaged_rows(&grid), vec![0, 1, 2]);
+
+        grid.clear_damage();
+        grid.use_main_screen();
+        assert_eq!(damaged_rows(&grid), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_changes_outside_viewport_are_not_damage() {
+        let mut grid = TerminalGrid::new(4, 2);
+        grid.cells.insert(0, vec![Cell::default(); 4]);
+        grid.viewport_to_end();
+        grid.clear_damage();
+
+        // Row 0 is scrollback history above the viewport
+        grid.put_cell(Cell::new('a', Color::white(), Color::black()), 0, 0);
+
+        assert!(!grid.has_damage());
+    }
 }
@@ -12,16 +12,21 @@
 pub mod color;
 pub mod command;
 pub mod cursor;
+pub mod export;
 pub mod grid;
 pub mod state;
+pub mod text;
 
 // Re-export commonly used types
 pub use color::Color;
 pub use command::{AnsiParseError, CsiCommand, DecPrivateMode, EraseMode, SgrParameter};
 pub use cursor::{Cursor, CursorStyle};
-pub use grid::{Cell, TerminalGrid};
+pub use export::{ExportFormat, ExportScope};
+pub use grid::{Cell, TerminalGrid, UnderlineStyle};
 pub use state::TerminalState;
+pub use text::{TextFormat, TextRange};
 
+use unicode_width::UnicodeWidthChar;
 use vte::{Params, Parser, Perform};
 
 /// Terminal emulator
@@ -35,6 +40,9 @@ pub struct Terminal {
     parser: Parser,
     /// Pending responses to be sent back to the shell
     pending_responses: Vec<Vec<u8>>,
+    /// The last byte completed a character or sequence, leaving the parser
+    /// between sequences
+    at_boundary: bool,
 }
 
 impl Terminal {
@@ -44,9 +52,23 @@ impl Terminal {
             state: TerminalState::new(cols, rows),
             parser: Parser::new(),
             pending_responses: Vec::new(),
+            at_boundary: true,
         }
     }
 
+    /// Copy the terminal, unless it is in the middle of an escape sequence
+    ///
+    /// The parser can't be copied, so the copy starts with a fresh one. That
+    /// is only right between sequences; `None` otherwise.
+    pub fn try_clone(&self) -> Option<Self> {
+        self.at_boundary.then(|| Self {
+            state: self.state.clone(),
+            parser: Parser::new(),
+            pending_responses: Vec::new(),
+            at_boundary: true,
+        })
+    }
+
     /// Drain pending responses that need to be sent to the shell
     ///
     /// Returns a vector of byte sequences to be written to the shell.
@@ -62,6 +84,8 @@ impl Terminal {
         // Temporarily take ownership of the parser to avoid borrow checker issues
         let mut parser = std::mem::replace(&mut self.parser, Parser::new());
         for &byte in bytes {
+            // Printing and dispatching a sequence set it again
+            self.at_boundary = false;
             parser.advance(self, byte);
         }
         self.parser = parser;
@@ -86,6 +110,117 @@ impl Terminal {
         // Clamp cursor to valid position
         self.state.cursor.row = self.state.cursor.row.min(rows.saturating_sub(1));
         self.state.cursor.col = self.state.cursor.col.min(cols.saturating_sub(1));
+        self.state.pending_wrap = false;
+    }
+
+    /// Absolute grid row of the cursor
+    #[inline]
+    fn cursor_abs_row(&self) -> usize {
+        self.state.grid.screen_start() + self.state.cursor.row
+    }
+
+    /// Last column the cursor can print to before wrapping
+    ///
+    /// The right margin only applies when the cursor is inside it.
+    fn right_edge(&self) -> usize {
+        let grid = &self.state.grid;
+        if self.state.cursor.col <= grid.scroll_right {
+            grid.scroll_right
+        } else {
+            grid.width - 1
+        }
+    }
+
+    /// Wrap to the left margin of the next line (scrolls at the bottom margin)
+    ///
+    /// Lines wrapped at the right edge of the screen are marked as continuing,
+    /// so text extraction can join them again.
+    fn wrap_line(&mut self) {
+        if self.right_edge() + 1 >= self.state.grid.width {
+            let row = self.cursor_abs_row();
+            self.state.grid.set_wrapped(row);
+        }
+        self.state.cursor.col = if self.state.grid.in_lr_margins(self.state.cursor.col) {
+            self.state.grid.scroll_left
+        } else {
+            0
+        };
+        self.linefeed();
+    }
+
+    /// Attach a zero-width character to the character before the cursor
+    ///
+    /// That is the cell under the cursor while a wrap is pending. Without a
+    /// character before it on the line, the zero-width character is dropped.
+    fn print_combining(&mut self, c: char) {
+        let col = if self.state.pending_wrap {
+            Some(self.state.cursor.col)
+        } else {
+            self.state.cursor.col.checked_sub(1)
+        };
+        let Some(mut col) = col else {
+            return;
+        };
+        let row = self.cursor_abs_row();
+        if let Some(line) = self.state.grid.cells.get(row)
+            && col > 0
+            && line.get(col).is_some_and(|cell| cell.wide_spacer)
+        {
+            col -= 1;
+        }
+        self.state.grid.push_combining(row, col, c);
+    }
+
+    /// Blank cell used by erase operations
+    ///
+    /// Carries the current background color (background color erase, BCE)
+    /// but no other attributes.
+    fn erase_cell(&self) -> Cell {
+        Cell {
+            default_bg: self.state.default_bg,
+            ..Cell::new(' ', self.state.fg, self.state.bg)
+        }
+    }
+
+    /// Move the cursor to a 0-indexed row, relative to the top margin in origin mode
+    fn set_cursor_row(&mut self, row: usize) {
+        let grid = &self.state.grid;
+        let (top, bottom) = if self.state.origin_mode {
+            (grid.scroll_top, grid.scroll_bottom)
+        } else {
+            (0, grid.viewport_height.saturating_sub(1))
+        };
+        self.state.cursor.row = (top + row).min(bottom);
+    }
+
+    /// Move the cursor to a 0-indexed column, relative to the left margin in origin mode
+    fn set_cursor_col(&mut self, col: usize) {
+        let grid = &self.state.grid;
+        let (left, right) = if self.state.origin_mode {
+            (grid.scroll_left, grid.scroll_right)
+        } else {
+            (0, grid.width.saturating_sub(1))
+        };
+        self.state.cursor.col = (left + col).min(right);
+    }
+
+    /// Move the cursor to the home position (top-left margin in origin mode)
+    fn home_cursor(&mut self) {
+        self.set_cursor_row(0);
+        self.set_cursor_col(0);
+    }
+
+    /// Move the cursor down one line, scrolling the region at the bottom margin
+    fn linefeed(&mut self) {
+        let grid = &mut self.state.grid;
+        if self.state.cursor.row == grid.scroll_bottom {
+            // Only scroll when the cursor is inside the left/right margins
+            if grid.in_lr_margins(self.state.cursor.col) {
+                grid.scroll_up(1);
+            }
+        } else if self.state.cursor.row + 1 < grid.viewport_height {
+            self.state.cursor.row += 1;
+        }
     }
 
     /// Get a parameter from a CSI sequence, with a default value if not present
@@ -117,37 +252,39 @@ impl Terminal {
         (r, g, b)
     }
 
-    /// Handle extended color sequences (38/48 SGR codes)
-    fn handle_extended_color<'a>(
+    /// Parse an extended color (38/48/58 SGR codes)
+    ///
+    /// Accepts both the colon form, where the color is carried in subparameters
+    /// (`38:5:n`, `38:2::r:g:b` or `38:2:r:g:b`), and the legacy semicolon form,
+    /// where it is read from the following parameters (`38;5;n`, `38;2;r;g;b`).
+    fn parse_extended_color<'a>(
+        param: &[u16],
         iter: &mut impl Iterator<Item = &'a [u16]>,
-        is_foreground: bool,
-        fg: &mut Color,
-        bg: &mut Color,
-    ) {
-        if let Some(next_param) = iter.next() {
-            match next_param.first().copied().unwrap_or(0) {
-                2 => {
-                    // RGB color
-                    let (r, g, b) = Self::extract_rgb(iter);
-                    let color = Color::new(r, g, b);
-                    if is_foreground {
-                        *fg = color;
-                    } else {
-                        *bg = color;
-                    }
-                }
-                5 => {
-                    // 256-color palette (full 0-255 range)
-                    let idx = Self::next_param(iter, 0) as u8;
-                    let color = Color::from_ansi_index(idx);
-                    if is_foreground {
-                        *fg = color;
-                    } else {
-                        *bg = color;
-                    }
-                }
-                _ => {}
+    ) -> Option<Color> {
+        if param.len() > 1 {
+            return match param[1] {
+                // The optional color space id makes this either 5 or 6 values long
+                2 => match param[2..] {
+                    [_, r, g, b, ..] | [r, g, b] => Some(Color::new(r as u8, g as u8, b as u8)),
+                    _ => None,
+                },
+                5 => param.get(2).map(|&idx| Color::from_ansi_index(idx as u8)),
+                _ => None,
+            };
+        }
+
+        match Self::next_param(iter, 0) {
+            2 => {
+                // RGB color
+                let (r, g, b) = Self::extract_rgb(iter);
+                Some(Color::new(r, g, b))
             }
+            5 => {
+                // 256-color palette (full 0-255 range)
+                let idx = Self::next_param(iter, 0) as u8;
+                Some(Color::from_ansi_index(idx))
+            }
+            _ => None,
         }
     }
 
@@ -255,6 +392,20 @@ impl Terminal {
                     2
                 }
             }
+            DecPrivateMode::OriginMode => {
+                if self.state.origin_mode {
+                    1
+                } else {
+                    2
+                }
+            }
+            DecPrivateMode::LeftRightMarginMode => {
+                if self.state.left_right_margin_mode {
+                    1
+                } else {
+                    2
+                }
+            }
             _ => 0, // Not recognized/implemented
         };
 
@@ -322,9 +473,21 @@ impl Terminal {
                 self.state.mouse_urxvt = true;
             }
             DecPrivateMode::SynchronizedOutput => {
-                // Enable synchronized output mode
+                // Enable synchronized output mode; repeating it doesn't begin a new update
+                if !self.state.synchronized_output {
+                    self.state.synchronized_updates += 1;
+                }
                 self.state.synchronized_output = true;
             }
+            DecPrivateMode::OriginMode => {
+                // Address the cursor relative to the margins
+                self.state.origin_mode = true;
+                self.home_cursor();
+            }
+            DecPrivateMode::LeftRightMarginMode => {
+                // Allow DECSLRM (CSI s) to set left/right margins
+                self.state.left_right_margin_mode = true;
+            }
             DecPrivateMode::Unknown(mode) => {
                 eprintln!("[ANSI] Unknown DEC private mode (set): {}", mode);
             }
@@ -395,6 +558,16 @@ impl Terminal {
                 // Disable synchronized output mode
                 self.state.synchronized_output = false;
             }
+            DecPrivateMode::OriginMode => {
+                // Address the cursor relative to the whole screen
+                self.state.origin_mode = false;
+                self.home_cursor();
+            }
+            DecPrivateMode::LeftRightMarginMode => {
+                // Disable DECSLRM and drop any left/right margins
+                self.state.left_right_margin_mode = false;
+                self.state.grid.reset_lr_margins();
+            }
             DecPrivateMode::Unknown(mode) => {
                 eprintln!("[ANSI] Unknown DEC private mode (reset): {}", mode);
             }
@@ -407,15 +580,29 @@ impl Terminal {
         }
     }
 
+    /// Reset all graphic rendition attributes (SGR 0)
+    fn reset_sgr(&mut self) {
+        self.state.fg = Color::white();
+        self.state.fg_index = None;
+        self.state.bg = Color::default_background();
+        self.state.default_bg = true;
+        self.state.bold = false;
+        self.state.italic = false;
+        self.state.underline = UnderlineStyle::None;
+        self.state.reverse = false;
+        self.state.faint = false;
+        self.state.blink = false;
+        self.state.conceal = false;
+        self.state.strikethrough = false;
+        self.state.overline = false;
+        self.state.underline_color = None;
+    }
+
     /// Handle SGR (Select Graphic Rendition) parameters
     fn handle_sgr(&mut self, params: &Params) {
         // If no parameters, default to reset (0)
         if params.is_empty() {
-            self.state.fg = Color::white();
-            self.state.bg = Color::black();
-            self.state.bold = false;
-            self.state.italic = false;
-            self.state.underline = false;
+            self.reset_sgr();
             return;
         }
 
@@ -426,64 +613,101 @@ impl Terminal {
 
             match sgr {
                 SgrParameter::Reset => {
-                    self.state.fg = Color::white();
-                    self.state.bg = Color::black();
-                    self.state.bold = false;
-                    self.state.italic = false;
-                    self.state.underline = false;
-                    self.state.reverse = false;
+                    self.reset_sgr();
                 }
                 SgrParameter::Bold => {
                     self.state.bold = true;
                 }
+                SgrParameter::Faint => {
+                    self.state.faint = true;
+                }
                 SgrParameter::Italic => {
                     self.state.italic = true;
                 }
                 SgrParameter::Underline => {
-                    self.state.underline = true;
+                    // `4:x` selects the underline style, plain `4` is a single line
+                    self.state.underline = match param.get(1) {
+                        Some(&style) => UnderlineStyle::from_subparam(style),
+                        None => UnderlineStyle::Single,
+                    };
+                }
+                SgrParameter::DoubleUnderline => {
+                    self.state.underline = UnderlineStyle::Double;
+                }
+                SgrParameter::SlowBlink | SgrParameter::RapidBlink => {
+                    self.state.blink = true;
+                }
+                SgrParameter::Conceal => {
+                    self.state.conceal = true;
+                }
+                SgrParameter::CrossedOut => {
+                    self.state.strikethrough = true;
+                }
+                SgrParameter::Overline => {
+                    self.state.overline = true;
                 }
                 SgrParameter::NormalIntensity => {
                     self.state.bold = false;
+                    self.state.faint = false;
                 }
                 SgrParameter::NotItalic => {
                     self.state.italic = false;
                 }
                 SgrParameter::NotUnderlined => {
-                    self.state.underline = false;
+                    self.state.underline = UnderlineStyle::None;
+                }
+                SgrParameter::NotBlinking => {
+                    self.state.blink = false;
+                }
+                SgrParameter::NotConcealed => {
+                    self.state.conceal = false;
+                }
+                SgrParameter::NotCrossedOut => {
+                    self.state.strikethrough = false;
+                }
+                SgrParameter::NotOverlined => {
+                    self.state.overline = false;
                 }
                 SgrParameter::ForegroundColor(idx) => {
                     self.state.fg = Color::from_ansi_index(idx);
+                    self.state.fg_index = Some(idx);
                 }
                 SgrParameter::BackgroundColor(idx) => {
                     self.state.bg = Color::from_ansi_index(idx);
+                    self.state.default_bg = false;
                 }
                 SgrParameter::BrightForegroundColor(idx) => {
                     self.state.fg = Color::from_ansi_index(idx + 8);
+                    self.state.fg_index = None;
                 }
                 SgrParameter::BrightBackgroundColor(idx) => {
                     self.state.bg = Color::from_ansi_index(idx + 8);
+                    self.state.default_bg = false;
                 }
                 SgrParameter::DefaultForeground => {
                     self.state.fg = Color::white();
+                    self.state.fg_index = None;
                 }
                 SgrParameter::DefaultBackground => {
-                    self.state.bg = Color::black();
+                    self.state.bg = Color::default_background();
+                    self.state.default_bg = true;
                 }
                 SgrParameter::ExtendedForeground => {
-                    Self::handle_extended_color(
-                        &mut iter,
-                        true,
-                        &mut self.state.fg,
-                        &mut self.state.bg,
-                    );
+                    if let Some(color) = Self::parse_extended_color(param, &mut iter) {
+                        self.state.fg = color;
+                        self.state.fg_index = None;
+                    }
                 }
                 SgrParameter::ExtendedBackground => {
-                    Self::handle_extended_color(
-                        &mut iter,
-                        false,
-                        &mut self.state.fg,
-                        &mut self.state.bg,
-                    );
+                    if let Some(color) = Self::parse_extended_color(param, &mut iter) {
+                        self.state.bg = color;
+                        self.state.default_bg = false;
+                    }
+                }
+                SgrParameter::ExtendedUnderlineColor => {
+                    if let Some(color) = Self::parse_extended_color(param, &mut iter) {
+                        self.state.underline_color = Some(color);
+                    }
                 }
                 SgrParameter::ReverseVideo => {
                     self.state.reverse = true;
@@ -492,15 +716,11 @@ impl Terminal {
                     self.state.reverse = false;
                 }
                 SgrParameter::DefaultUnderlineColor => {
-                    // Default underline color - no-op (not yet implemented)
-                    // We don't support separate underline colors yet
+                    self.state.underline_color = None;
                 }
                 SgrParameter::Unknown(code) => {
                     eprintln!("[ANSI] Unknown SGR parameter: {}", code);
                 }
-                _ => {
-                    eprintln!("[ANSI] Not yet implemented SGR: {:?}", sgr);
-                }
             }
         }
     }
@@ -508,13 +728,32 @@ impl Terminal {
 
 impl Perform for Terminal {
     fn print(&mut self, c: char) {
+        self.at_boundary = true;
+
+        // Zero-width characters combine with the previous character
+        if c.width() == Some(0) {
+            self.print_combining(c);
+            return;
+        }
+
+        // Optionally render bold text in the bright variant of colors 0-7
+        let fg = match self.state.fg_index {
+            Some(idx) if self.state.bold && self.state.bold_is_bright && idx < 8 => {
+                Color::from_ansi_index(idx + 8)
+            }
+            _ => self.state.fg,
+        };
+
         // Swap colors if reverse video is enabled
         let (fg, bg) = if self.state.reverse {
-            (self.state.bg, self.state.fg)
+            (self.state.bg, fg)
         } else {
-            (self.state.fg, self.state.bg)
+            (fg, self.state.bg)
         };
 
+        // Double-width characters (CJK, emoji) take this cell and the next
+        let wide = c.width() == Some(2) && self.state.grid.width >= 2;
+
         // Create cell with current attributes
         let cell = Cell {
             ch: c,
@@ -524,57 +763,84 @@ impl Perform for Terminal {
             italic: self.state.italic,
             underline: self.state.underline,
             reverse: self.state.reverse,
+            faint: self.state.faint,
+            blink: self.state.blink,
+            conceal: self.state.conceal,
+            strikethrough: self.state.strikethrough,
+            overline: self.state.overline,
+            underline_color: self.state.underline_color,
+            wide,
+            wide_spacer: false,
+            combining: [None; 2],
+            wrapped: false,
+            default_bg: self.state.default_bg && !self.state.reverse,
         };
 
-        // Check if we need to wrap to next line
-        if self.state.cursor.col >= self.state.grid.width {
+        // Perform a wrap deferred from the previous character
+        if self.state.pending_wrap {
+            self.state.pending_wrap = false;
             if self.state.auto_wrap {
-                // Wrap to next line
-                self.state.cursor.col = 0;
-                self.state.cursor.row += 1;
+                self.wrap_line();
+            }
+        }
 
-                // If at bottom, scroll the viewport down
-                if self.state.cursor.row >= self.state.grid.viewport_height {
-                    self.state.cursor.row = self.state.grid.viewport_height - 1;
-                    // TODO: Actual scrolling logic
-                }
+        let width = self.state.grid.width;
+        self.state.cursor.col = self.state.cursor.col.min(width - 1);
+
+        // A double-width character that doesn't fit before the edge wraps first
+        if wide && self.state.cursor.col >= self.right_edge() {
+            if self.state.auto_wrap {
+                self.wrap_line();
             } else {
-                // No wrap: stay at right edge (overwrite last position)
-                self.state.cursor.col = self.state.grid.width - 1;
+                self.state.cursor.col = self.right_edge().saturating_sub(1);
             }
         }
 
         // Put the cell at cursor position
-        self.state
-            .grid
-            .put_cell(cell, self.state.cursor.row, self.state.cursor.col);
+        let row = self.cursor_abs_row();
+        self.state.grid.put_cell(cell, row, self.state.cursor.col);
 
-        // Move cursor forward
-        self.state.cursor.col += 1;
+        let last_col = if wide {
+            self.state.cursor.col + 1
+        } else {
+            self.state.cursor.col
+        };
+        let right_edge = self.right_edge();
+        if last_col >= right_edge {
+            // At the edge: wrap on the next character, or keep overwriting the last column
+            self.state.cursor.col = last_col.min(right_edge);
+            self.state.pending_wrap = self.state.auto_wrap;
+        } else {
+            // Move cursor forward
+            self.state.cursor.col = last_col + 1;
+        }
     }
 
     fn execute(&mut self, byte: u8) {
         match byte {
             b'\n' => {
-                // Line Feed (LF) - move down one line
-                self.state.cursor.row += 1;
-                if self.state.cursor.row >= self.state.grid.viewport_height {
-                    self.state.cursor.row = self.state.grid.viewport_height - 1;
-                    // TODO: Actual scrolling logic
-                }
+                // Line Feed (LF) - move down one line, scrolling at the bottom margin
+                self.state.pending_wrap = false;
+                self.linefeed();
             }
             b'\r' => {
-                // Carriage Return (CR) - move to start of line
-                self.state.cursor.col = 0;
+                // Carriage Return (CR) - move to left margin (or start of line outside it)
+                self.state.pending_wrap = false;
+                let left = self.state.grid.scroll_left;
+                self.state.cursor.col = if self.state.cursor.col >= left {
+                    left
+                } else {
+                    0
+                };
             }
             b'\x08' => {
                 // Backspace
-                if self.state.cursor.col > 0 {
-                    self.state.cursor.col -= 1;
-                }
+                self.state.pending_wrap = false;
+                self.state.cursor.col = self.state.cursor.col.saturating_sub(1);
             }
             b'\t' => {
                 // Tab - move to next tab stop (every 8 columns)
+                self.state.pending_wrap = false;
                 let next_tab = ((self.state.cursor.col / 8) + 1) * 8;
                 self.state.cursor.col = next_tab.min(self.state.grid.width - 1);
             }
@@ -585,6 +851,8 @@ impl Perform for Terminal {
     }
 
     fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        self.at_boundary = true;
+
         // Check if this is a DEC private mode sequence (starts with '?')
         let is_dec_private = intermediates.first() == Some(&b'?');
         let has_gt = intermediates.first() == Some(&b'>');
@@ -622,6 +890,11 @@ impl Perform for Terminal {
             return;
         }
 
+        // CSI > u, CSI < u, CSI = u belong to the kitty keyboard protocol, not SCORC
+        if matches!(action, 's' | 'u') && !intermediates.is_empty() {
+            return;
+        }
+
         // Parse CSI command with parameters
         let command = match CsiCommand::parse(action, params, is_dec_private) {
             Ok(cmd) => cmd,
@@ -637,133 +910,138 @@ impl Perform for Terminal {
             return;
         }
 
+        // Any other control sequence cancels a deferred wrap
+        self.state.pending_wrap = false;
+
         // Execute command by pattern matching on enum variants
         match command {
             // Cursor positioning
             CsiCommand::CursorPosition { row, col } => {
-                self.state.cursor.row =
-                    (row.saturating_sub(1) as usize).min(self.state.grid.viewport_height - 1);
-                self.state.cursor.col =
-                    (col.saturating_sub(1) as usize).min(self.state.grid.width - 1);
+                self.set_cursor_row(row.saturating_sub(1) as usize);
+                self.set_cursor_col(col.saturating_sub(1) as usize);
             }
 
-            // Cursor movement
+            // Cursor movement (stops at a margin when starting inside it)
             CsiCommand::CursorUp { n } => {
-                self.state.cursor.row = self.state.cursor.row.saturating_sub(n as usize);
+                let grid = &self.state.grid;
+                let top = if self.state.cursor.row >= grid.scroll_top {
+                    grid.scroll_top
+                } else {
+                    0
+                };
+                self.state.cursor.row = self.state.cursor.row.saturating_sub(n as usize).max(top);
             }
 
             CsiCommand::CursorDown { n } => {
-                self.state.cursor.row =
-                    (self.state.cursor.row + n as usize).min(self.state.grid.viewport_height - 1);
+                let grid = &self.state.grid;
+                let bottom = if self.state.cursor.row <= grid.scroll_bottom {
+                    grid.scroll_bottom
+                } else {
+                    grid.viewport_height - 1
+                };
+                self.state.cursor.row = (self.state.cursor.row + n as usize).min(bottom);
             }
 
             CsiCommand::CursorForward { n } => {
-                self.state.cursor.col =
-                    (self.state.cursor.col + n as usize).min(self.state.grid.width - 1);
+                let grid = &self.state.grid;
+                let right = if self.state.cursor.col <= grid.scroll_right {
+                    grid.scroll_right
+                } else {
+                    grid.width - 1
+                };
+                self.state.cursor.col = (self.state.cursor.col + n as usize).min(right);
             }
 
             CsiCommand::CursorBack { n } => {
-                self.state.cursor.col = self.state.cursor.col.saturating_sub(n as usize);
+                let grid = &self.state.grid;
+                let left = if self.state.cursor.col >= grid.scroll_left {
+                    grid.scroll_left
+                } else {
+                    0
+                };
+                self.state.cursor.col = self.state.cursor.col.saturating_sub(n as usize).max(left);
             }
 
             CsiCommand::CursorHorizontalAbsolute { col } => {
-                self.state.cursor.col =
-                    (col.saturating_sub(1) as usize).min(self.state.grid.width - 1);
-            }
-
-            // Erase operations
-            CsiCommand::EraseInDisplay { mode } => match mode {
-                EraseMode::ToEnd => {
-                    // Clear from cursor to end of current line
-                    for col in self.state.cursor.col..self.state.grid.width {
-                        self.state
-                            .grid
-                            .put_cell(Cell::default(), self.state.cursor.row, col);
-                    }
-                    // Clear all lines below cursor to end of viewport
-                    let viewport_end =
-                        self.state.grid.viewport_start + self.state.grid.viewport_height;
-                    for row in (self.state.cursor.row + 1)..viewport_end {
-                        self.state.grid.clear_line(row);
-                    }
-                }
-                EraseMode::All => {
-                    self.state.grid.clear_viewport();
-                    self.state.cursor.row = 0;
-                    self.state.cursor.col = 0;
-                }
-                EraseMode::ToBeginning => {
-                    // Clear from beginning to cursor
-                    for row in 0..self.state.cursor.row {
-                        self.state.grid.clear_line(row);
-                    }
-                    // Clear current line up to cursor
-                    for col in 0..=self.state.cursor.col {
-                        self.state
-                            .grid
-                            .put_cell(Cell::default(), self.state.cursor.row, col);
-                    }
-                }
-                EraseMode::AllWithScrollback => {
-                    // Clear scrollback history
-                    // This would require additional grid methods
-                    // For now, just clear viewport
-                    self.state.grid.clear_viewport();
-                }
-            },
-
-            CsiCommand::EraseInLine { mode } => match mode {
-                EraseMode::ToEnd => {
-                    for col in self.state.cursor.col..self.state.grid.width {
-                        self.state
-                            .grid
-                            .put_cell(Cell::default(), self.state.cursor.row, col);
-                    }
-                }
-                EraseMode::All => {
-                    self.state.grid.clear_line(self.state.cursor.row);
-                }
-                EraseMode::ToBeginning => {
-                    for col in 0..=self.state.cursor.col {
-                        self.state
-                            .grid
-                            .put_cell(Cell::default(), self.state.cursor.row, col);
-                    }
-                }
-                EraseMode::AllWithScrollback => {
-                    // Not applicable to EraseInLine
-                }
-            },
+                self.set_cursor_col(col.saturating_sub(1) as usize);
+            }
+
+            // Erase operations (erased cells take the current background, BCE)
+            CsiCommand::EraseInDisplay { mode } => {
+                let blank = self.erase_cell();
+                let cursor = self.state.cursor;
+                self.state
+                    .grid
+                    .erase_in_display(cursor.row, cursor.col, mode, blank);
+            }
+
+            CsiCommand::EraseInLine { mode } => {
+                let blank = self.erase_cell();
+                let cursor = self.state.cursor;
+                self.state
+                    .grid
+                    .erase_in_line(cursor.row, cursor.col, mode, blank);
+            }
 
             // Scrolling region (DECSTBM)
             CsiCommand::SetScrollingRegion { top, bottom } => {
                 // Convert from 1-indexed to 0-indexed
-                // top=0, bottom=0 means reset to full screen
-                if top == 0 && bottom == 0 {
-                    self.state.grid.reset_scroll_region();
-                } else if top > 0 && bottom > 0 {
-                    let top_idx = (top as usize).saturating_sub(1);
-                    let bottom_idx = (bottom as usize).saturating_sub(1);
-                    self.state.grid.set_scroll_region(top_idx, bottom_idx);
-                }
+                // A missing bottom margin means the last line of the screen
+                let bottom = if bottom == 0 {
+                    self.state.grid.viewport_height
+                } else {
+                    bottom as usize
+                };
+                let top_idx = (top as usize).saturating_sub(1);
+                let bottom_idx = bottom.saturating_sub(1);
+                self.state.grid.set_scroll_region(top_idx, bottom_idx);
                 // Move cursor to home position (required by VT100 spec)
-                self.state.cursor.row = self.state.grid.viewport_start;
-                self.state.cursor.col = 0;
+                self.home_cursor();
             }
 
-            // Line manipulation (IL/DL)
+            // Left/right margins (DECSLRM), or save cursor (SCOSC) without DECLRMM
+            CsiCommand::SetLeftRightMargins { left, right } => {
+                if self.state.left_right_margin_mode {
+                    // A missing right margin means the last column
+                    let right = if right == 0 {
+                        self.state.grid.width
+                    } else {
+                        right as usize
+                    };
+                    let left_idx = (left as usize).saturating_sub(1);
+                    let right_idx = right.saturating_sub(1);
+                    self.state.grid.set_lr_margins(left_idx, right_idx);
+                    self.home_cursor();
+                } else {
+                    self.state.saved_cursor = Some(self.state.cursor);
+                }
+            }
+
+            CsiCommand::RestoreCursor => {
+                if let Some(saved) = self.state.saved_cursor {
+                    let grid = &self.state.grid;
+                    self.state.cursor.row = saved.row.min(grid.viewport_height - 1);
+                    self.state.cursor.col = saved.col.min(grid.width - 1);
+                }
+            }
+
+            // Line manipulation (IL/DL), only inside the left/right margins
             CsiCommand::InsertLines { n } => {
                 // Insert n blank lines at cursor position within scrolling region
                 let count = n.max(1) as usize;
-                let cursor_row = self.state.cursor.row - self.state.grid.viewport_start;
-                self.state.grid.insert_lines(cursor_row, count);
+                if self.state.grid.in_lr_margins(self.state.cursor.col) {
+                    self.state.grid.insert_lines(self.state.cursor.row, count);
+                    self.state.cursor.col = self.state.grid.scroll_left;
+                }
             }
 
             CsiCommand::DeleteLines { n } => {
                 // Delete n lines at cursor position within scrolling region
                 let count = n.max(1) as usize;
-                let cursor_row = self.state.cursor.row - self.state.grid.viewport_start;
-                self.state.grid.delete_lines(cursor_row, count);
+                if self.state.grid.in_lr_margins(self.state.cursor.col) {
+                    self.state.grid.delete_lines(self.state.cursor.row, count);
+                    self.state.cursor.col = self.state.grid.scroll_left;
+                }
             }
 
             // Already handled above
@@ -776,8 +1054,14 @@ impl Perform for Terminal {
                     6 => {
                         // CPR - Cursor Position Report
                         // Report cursor position as ESC[{row};{col}R
-                        let row = self.state.cursor.row + 1; // 1-based
-                        let col = self.state.cursor.col + 1; // 1-based
+                        // Relative to the margins in origin mode, 1-based
+                        let (top, left) = if self.state.origin_mode {
+                            (self.state.grid.scroll_top, self.state.grid.scroll_left)
+                        } else {
+                            (0, 0)
+                        };
+                        let row = self.state.cursor.row.saturating_sub(top) + 1;
+                        let col = self.state.cursor.col.saturating_sub(left) + 1;
                         let response = format!("\x1b[{};{}R", row, col);
                         self.pending_responses.push(response.into_bytes());
                     }
@@ -810,7 +1094,7 @@ impl Perform for Terminal {
                 use crate::terminal::cursor::CursorStyle;
 
                 let new_style = match style {
-                    0 | 1 | 2 => CursorStyle::Block,
+                    0..=2 => CursorStyle::Block,
                     3 | 4 => CursorStyle::Underline,
                     5 | 6 => CursorStyle::Bar,
                     _ => CursorStyle::Block, // Unknown values default to block
@@ -827,91 +1111,44 @@ impl Perform for Terminal {
 
             CsiCommand::VerticalPositionAbsolute { row } => {
                 // Move cursor to absolute row, column unchanged
-                self.state.cursor.row =
-                    (row.saturating_sub(1) as usize).min(self.state.grid.viewport_height - 1);
+                self.set_cursor_row(row.saturating_sub(1) as usize);
             }
 
             CsiCommand::EraseCharacter { n } => {
                 // Erase n characters at cursor position
-                let start_col = self.state.cursor.col;
-                let end_col = (start_col + n as usize).min(self.state.grid.width);
-                for col in start_col..end_col {
-                    self.state
-                        .grid
-                        .put_cell(Cell::default(), self.state.cursor.row, col);
-                }
+                let blank = self.erase_cell();
+                let cursor = self.state.cursor;
+                self.state
+                    .grid
+                    .erase_chars(cursor.row, cursor.col, n as usize, blank);
             }
 
             CsiCommand::ScrollDown { n } => {
-                // Scroll viewport down by n lines (insert blank lines at top)
-                let viewport_start = self.state.grid.viewport_start;
-                for _ in 0..n {
-                    let blank_row = vec![Cell::default(); self.state.grid.width];
-                    self.state.grid.cells.insert(viewport_start, blank_row);
-                }
-
-                // Enforce scrollback limit
-                if self.state.grid.cells.len() > self.state.grid.max_scrollback {
-                    let excess = self.state.grid.cells.len() - self.state.grid.max_scrollback;
-                    self.state.grid.cells.drain(0..excess);
-                    self.state.grid.viewport_start =
-                        self.state.grid.viewport_start.saturating_sub(excess);
-                }
+                // Scroll region content down by n lines (blank lines at top margin)
+                self.state.grid.scroll_down(n as usize);
             }
 
             CsiCommand::ScrollUp { n } => {
-                // Scroll viewport up by n lines (remove lines from top, add blank at bottom)
-                let viewport_start = self.state.grid.viewport_start;
-
-                // Remove n lines from viewport_start
-                let lines_to_remove = (n as usize).min(self.state.grid.viewport_height);
-                if viewport_start + lines_to_remove <= self.state.grid.cells.len() {
-                    self.state
-                        .grid
-                        .cells
-                        .drain(viewport_start..viewport_start + lines_to_remove);
-
-                    // Add blank lines at the end of viewport
-                    for _ in 0..lines_to_remove {
-                        let blank_row = vec![Cell::default(); self.state.grid.width];
-                        let insert_pos = (viewport_start + self.state.grid.viewport_height
-                            - lines_to_remove)
-                            .min(self.state.grid.cells.len());
-                        self.state.grid.cells.insert(insert_pos, blank_row);
-                    }
-                }
+                // Scroll region content up by n lines (blank lines at bottom margin)
+                self.state.grid.scroll_up(n as usize);
             }
 
             CsiCommand::DeleteCharacter { n } => {
                 // Delete n characters at cursor, shifting remaining chars left
-                let row = self.state.cursor.row;
-                let start_col = self.state.cursor.col;
-                let width = self.state.grid.width;
-
-                if start_col < width {
-                    // Get the current row
-                    let viewport_start = self.state.grid.viewport_start;
-                    let absolute_row = viewport_start + row;
-
-                    // Ensure row exists
-                    while absolute_row >= self.state.grid.cells.len() {
-                        self.state.grid.cells.push(vec![Cell::default(); width]);
-                    }
-
-                    let n_chars = (n as usize).min(width - start_col);
-
-                    // Shift characters left by removing n chars at cursor position
-                    for _ in 0..n_chars {
-                        if start_col < self.state.grid.cells[absolute_row].len() {
-                            self.state.grid.cells[absolute_row].remove(start_col);
-                        }
-                    }
+                self.state.grid.delete_chars(
+                    self.state.cursor.row,
+                    self.state.cursor.col,
+                    n as usize,
+                );
+            }
 
-                    // Add blank cells at the end to maintain width
-                    while self.state.grid.cells[absolute_row].len() < width {
-                        self.state.grid.cells[absolute_row].push(Cell::default());
-                    }
-                }
+            CsiCommand::InsertCharacter { n } => {
+                // Insert n blank characters at cursor, shifting remaining chars right
+                self.state.grid.insert_chars(
+                    self.state.cursor.row,
+                    self.state.cursor.col,
+                    n as usize,
+                );
             }
 
             CsiCommand::ResetMode { mode: _ } => {
@@ -924,6 +1161,18 @@ impl Perform for Terminal {
             }
         }
     }
+
+    fn esc_dispatch(&mut self, _intermediates: &[u8], _ignore: bool, _byte: u8) {
+        self.at_boundary = true;
+    }
+
+    fn osc_dispatch(&mut self, _params: &[&[u8]], _bell_terminated: bool) {
+        self.at_boundary = true;
+    }
+
+    fn unhook(&mut self) {
+        self.at_boundary = true;
+    }
 }
 
 #[cfg(test)]
@@ -1290,6 +1539,61 @@ mod tests {
         assert_eq!(viewport[1][0].ch, ' '); // No wrap
     }
 
+    // Double-width characters
+
+    #[test]
+    fn test_wide_char_takes_two_cells() {
+        let mut terminal = Terminal::new(10, 5);
+        terminal.process_bytes("a😀b".as_bytes());
+
+        let viewport = terminal.state().grid.get_viewport();
+        assert_eq!(viewport[0][1].ch, '😀');
+        assert!(viewport[0][1].wide);
+        assert!(viewport[0][2].wide_spacer);
+        assert_eq!(viewport[0][3].ch, 'b');
+        assert_eq!(terminal.state().cursor.col, 4);
+    }
+
+    #[test]
+    fn test_wide_char_wraps_when_it_does_not_fit() {
+        let mut terminal = Terminal::new(5, 5);
+        terminal.process_bytes("abcd中".as_bytes());
+
+        let viewport = terminal.state().grid.get_viewport();
+        assert_eq!(viewport[0][3].ch, 'd');
+        assert_eq!(viewport[0][4].ch, ' ');
+        assert_eq!(viewport[1][0].ch, '中');
+        assert!(viewport[1][1].wide_spacer);
+    }
+
+    #[test]
+    fn test_wide_char_in_last_columns_defers_wrap() {
+        let mut terminal = Terminal::new(5, 5);
+        terminal.process_bytes("abc中x".as_bytes());
+
+        let viewport = terminal.state().grid.get_viewport();
+        assert_eq!(viewport[0][3].ch, '中');
+        assert!(viewport[0][4].wide_spacer);
+        assert_eq!(viewport[1][0].ch, 'x');
+    }
+
+    #[test]
+    fn test_overwriting_half_of_wide_char_clears_other_half() {
+        let mut terminal = Terminal::new(10, 5);
+        terminal.process_bytes("中文".as_bytes());
+
+        // Overwrite the spacer of the first and the leader of the second
+        terminal.process_bytes(b"\x1b[1;2Hxy");
+
+        let viewport = terminal.state().grid.get_viewport();
+        assert_eq!(viewport[0][0].ch, ' ');
+        assert!(!viewport[0][0].wide);
+        assert_eq!(viewport[0][1].ch, 'x');
+        assert!(!viewport[0][1].wide_spacer);
+        assert_eq!(viewport[0][2].ch, 'y');
+        assert!(!viewport[0][3].wide_spacer);
+    }
+
     #[test]
     fn test_auto_wrap_default() {
         let terminal = Terminal::new(80, 24);
@@ -1467,9 +1771,9 @@ mod tests {
         assert_eq!(viewport[1][0].ch, ' ');
         assert_eq!(viewport[2][0].ch, ' ');
 
-        // Cursor should be at home position
-        assert_eq!(terminal.state().cursor.row, 0);
-        assert_eq!(terminal.state().cursor.col, 0);
+        // ED 2 does not move the cursor (xterm behavior)
+        assert_eq!(terminal.state().cursor.row, 2);
+        assert_eq!(terminal.state().cursor.col, 6);
     }
 
     #[test]
@@ -1674,4 +1978,434 @@ mod tests {
         assert_eq!(viewport[0][2].ch, 'S');
         assert_eq!(viewport[0][3].ch, 'S');
     }
+
+    /// Collect the characters of a viewport row as a string
+    fn row_text(terminal: &Terminal, row: usize) -> String {
+        terminal.state().grid.get_viewport()[row]
+            .iter()
+            .map(|cell| cell.ch)
+            .collect()
+    }
+
+    #[test]
+    fn test_linefeed_at_bottom_scrolls() {
+        let mut terminal = Terminal::new(10, 3);
+
+        terminal.process_bytes(b"one\r\ntwo\r\nthree\r\nfour");
+
+        assert_eq!(terminal.state().cursor.row, 2);
+        assert_eq!(row_text(&terminal, 0).trim_end(), "two");
+        assert_eq!(row_text(&terminal, 2).trim_end(), "four");
+        // Scrolled-off line is kept in scrollback
+        assert_eq!(terminal.state().grid.cells.len(), 4);
+        assert_eq!(terminal.state().grid.cells[0][0].ch, 'o');
+    }
+
+    #[test]
+    fn test_csi_s_saves_cursor_without_declrmm() {
+        let mut terminal = Terminal::new(80, 24);
+
+        terminal.process_bytes(b"\x1b[5;10H\x1b[s\x1b[1;1H\x1b[u");
+
+        assert_eq!(terminal.state().cursor.row, 4);
+        assert_eq!(terminal.state().cursor.col, 9);
+        // Margins are untouched
+        assert_eq!(terminal.state().grid.scroll_left, 0);
+        assert_eq!(terminal.state().grid.scroll_right, 79);
+    }
+
+    #[test]
+    fn test_decslrm_sets_margins_with_declrmm() {
+        let mut terminal = Terminal::new(80, 24);
+
+        terminal.process_bytes(b"\x1b[?69h\x1b[10;20s");
+
+        assert_eq!(terminal.state().grid.scroll_left, 9);
+        assert_eq!(terminal.state().grid.scroll_right, 19);
+        assert!(terminal.state().saved_cursor.is_none());
+
+        // Resetting DECLRMM drops the margins
+        terminal.process_bytes(b"\x1b[?69l");
+        assert_eq!(terminal.state().grid.scroll_left, 0);
+        assert_eq!(terminal.state().grid.scroll_right, 79);
+    }
+
+    #[test]
+    fn test_kitty_keyboard_query_is_not_restore_cursor() {
+        let mut terminal = Terminal::new(80, 24);
+
+        terminal.process_bytes(b"\x1b[2;2H\x1b[s\x1b[5;5H\x1b[>1u");
+
+        assert_eq!(terminal.state().cursor.row, 4);
+        assert_eq!(terminal.state().cursor.col, 4);
+    }
+
+    #[test]
+    fn test_decrqm_reports_declrmm() {
+        let mut terminal = Terminal::new(80, 24);
+
+        terminal.process_bytes(b"\x1b[?69$p");
+        terminal.process_bytes(b"\x1b[?69h\x1b[?69$p");
+
+        let responses = terminal.drain_responses();
+        assert_eq!(responses[0], b"\x1b[?69;2$y");
+        assert_eq!(responses[1], b"\x1b[?69;1$y");
+    }
+
+    #[test]
+    fn test_autowrap_at_right_margin() {
+        let mut terminal = Terminal::new(10, 5);
+
+        // Margins at columns 3-6 (1-indexed), cursor inside them
+        terminal.process_bytes(b"\x1b[?69h\x1b[3;6s\x1b[1;3HABCDEF");
+
+        assert_eq!(row_text(&terminal, 0), "  ABCD    ");
+        assert_eq!(row_text(&terminal, 1), "  EF      ");
+    }
+
+    #[test]
+    fn test_scroll_up_within_lr_margins() {
+        let mut terminal = Terminal::new(6, 3);
+
+        terminal.process_bytes(b"aaaaaa\r\nbbbbbb\r\ncccccc");
+        terminal.process_bytes(b"\x1b[?69h\x1b[2;4s\x1b[S");
+
+        // Only columns 1-3 scroll, the rest stays in place
+        assert_eq!(row_text(&terminal, 0), "abbbaa");
+        assert_eq!(row_text(&terminal, 1), "bcccbb");
+        assert_eq!(row_text(&terminal, 2), "c   cc");
+        // Rectangular scrolls never touch the scrollback
+        assert_eq!(terminal.state().grid.cells.len(), 3);
+    }
+
+    #[test]
+    fn test_insert_delete_lines_respect_lr_margins() {
+        let mut terminal = Terminal::new(6, 3);
+
+        terminal.process_bytes(b"aaaaaa\r\nbbbbbb\r\ncccccc");
+        terminal.process_bytes(b"\x1b[?69h\x1b[2;4s\x1b[1;2H\x1b[L");
+
+        assert_eq!(row_text(&terminal, 0), "a   aa");
+        assert_eq!(row_text(&terminal, 1), "baaabb");
+        assert_eq!(row_text(&terminal, 2), "cbbbcc");
+        assert_eq!(terminal.state().cursor.col, 1);
+
+        terminal.process_bytes(b"\x1b[M");
+
+        assert_eq!(row_text(&terminal, 0), "aaaaaa");
+        assert_eq!(row_text(&terminal, 1), "bbbbbb");
+        assert_eq!(row_text(&terminal, 2), "c   cc");
+    }
+
+    #[test]
+    fn test_insert_delete_characters_respect_right_margin() {
+        let mut terminal = Terminal::new(8, 3);
+
+        terminal.process_bytes(b"ABCDEFGH\x1b[?69h\x1b[1;5s\x1b[1;2H\x1b[2@");
+        assert_eq!(row_text(&terminal, 0), "A  BCFGH");
+
+        terminal.process_bytes(b"\x1b[3P");
+        assert_eq!(row_text(&terminal, 0), "AC   FGH");
+    }
+
+    #[test]
+    fn test_insert_character_basic() {
+        let mut terminal = Terminal::new(80, 24);
+
+        terminal.process_bytes(b"HELLO\x1b[1;2H\x1b[@");
+
+        assert_eq!(row_text(&terminal, 0).trim_end(), "H ELLO");
+    }
+
+    #[test]
+    fn test_origin_mode_addresses_relative_to_margins() {
+        let mut terminal = Terminal::new(80, 24);
+
+        terminal.process_bytes(b"\x1b[5;10r\x1b[?69h\x1b[11;40s\x1b[?6h");
+        // Origin mode homes the cursor to the margin corner
+        assert_eq!(terminal.state().cursor.row, 4);
+        assert_eq!(terminal.state().cursor.col, 10);
+
+        terminal.process_bytes(b"\x1b[2;3H");
+        assert_eq!(terminal.state().cursor.row, 5);
+        assert_eq!(terminal.state().cursor.col, 12);
+
+        // Positions are clamped to the margins
+        terminal.process_bytes(b"\x1b[99;99H");
+        assert_eq!(terminal.state().cursor.row, 9);
+        assert_eq!(terminal.state().cursor.col, 39);
+
+        // CPR reports origin-relative coordinates
+        terminal.process_bytes(b"\x1b[6n");
+        assert_eq!(terminal.drain_responses()[0], b"\x1b[6;30R");
+    }
+
+    #[test]
+    fn test_decstbm_without_params_resets_region() {
+        let mut terminal = Terminal::new(80, 24);
+
+        terminal.process_bytes(b"\x1b[5;10r");
+        assert_eq!(terminal.state().grid.scroll_top, 4);
+        assert_eq!(terminal.state().grid.scroll_bottom, 9);
+
+        terminal.process_bytes(b"\x1b[r");
+        assert_eq!(terminal.state().grid.scroll_top, 0);
+        assert_eq!(terminal.state().grid.scroll_bottom, 23);
+    }
+
+    // xterm erase conformance
+
+    #[test]
+    fn test_erase_in_display_uses_screen_rows_when_scrolled() {
+        let mut terminal = Terminal::new(6, 3);
+
+        // Push two rows into scrollback so the screen no longer starts at 0
+        terminal.process_bytes(b"aa\r\nbb\r\ncc\r\ndd\r\nee");
+        assert_eq!(terminal.state().grid.screen_start(), 2);
+
+        terminal.process_bytes(b"\x1b[2;1H\x1b[J");
+
+        assert_eq!(row_text(&terminal, 0), "cc    ");
+        assert_eq!(row_text(&terminal, 1), "      ");
+        assert_eq!(row_text(&terminal, 2), "      ");
+        assert_eq!(terminal.state().grid.cells[0][0].ch, 'a');
+    }
+
+    #[test]
+    fn test_erase_in_display_to_beginning_includes_cursor() {
+        let mut terminal = Terminal::new(6, 3);
+        terminal.process_bytes(b"aaaaaa\r\nbbbbbb\r\ncccccc");
+
+        terminal.process_bytes(b"\x1b[2;3H\x1b[1J");
+
+        assert_eq!(row_text(&terminal, 0), "      ");
+        assert_eq!(row_text(&terminal, 1), "   bbb");
+        assert_eq!(row_text(&terminal, 2), "cccccc");
+    }
+
+    #[test]
+    fn test_erase_in_line_modes() {
+        let mut terminal = Terminal::new(6, 3);
+        terminal.process_bytes(b"aaaaaa\r\nbbbbbb\r\ncccccc");
+
+        terminal.process_bytes(b"\x1b[1;3H\x1b[K");
+        terminal.process_bytes(b"\x1b[2;3H\x1b[1K");
+        terminal.process_bytes(b"\x1b[3;3H\x1b[2K");
+
+        assert_eq!(row_text(&terminal, 0), "aa    ");
+        assert_eq!(row_text(&terminal, 1), "   bbb");
+        assert_eq!(row_text(&terminal, 2), "      ");
+    }
+
+    #[test]
+    fn test_erase_uses_current_background() {
+        let mut terminal = Terminal::new(6, 2);
+        terminal.process_bytes(b"aaaaaa");
+
+        // Red background, bold: erased cells take the background only
+        terminal.process_bytes(b"\x1b[1;41m\x1b[1;4H\x1b[K");
+
+        let red = Color::from_ansi_index(1);
+        let viewport = terminal.state().grid.get_viewport();
+        assert_eq!(viewport[0][2].bg, Color::black());
+        assert_eq!(viewport[0][3].bg, red);
+        assert_eq!(viewport[0][5].bg, red);
+        assert!(!viewport[0][3].bold);
+    }
+
+    #[test]
+    fn test_erase_character_uses_current_background() {
+        let mut terminal = Terminal::new(6, 2);
+        terminal.process_bytes(b"abcdef");
+
+        terminal.process_bytes(b"\x1b[44m\x1b[1;2H\x1b[3X");
+
+        let blue = Color::from_ansi_index(4);
+        assert_eq!(row_text(&terminal, 0), "a   ef");
+        let viewport = terminal.state().grid.get_viewport();
+        assert_eq!(viewport[0][1].bg, blue);
+        assert_eq!(viewport[0][4].bg, Color::black());
+        assert_eq!(terminal.state().cursor.col, 1);
+    }
+
+    #[test]
+    fn test_erase_in_display_3_clears_scrollback_only() {
+        let mut terminal = Terminal::new(6, 3);
+        terminal.process_bytes(b"aa\r\nbb\r\ncc\r\ndd\r\nee");
+        assert_eq!(terminal.state().grid.cells.len(), 5);
+
+        terminal.process_bytes(b"\x1b[3J");
+
+        assert_eq!(terminal.state().grid.cells.len(), 3);
+        assert_eq!(terminal.state().grid.viewport_start, 0);
+        assert_eq!(row_text(&terminal, 0), "cc    ");
+        assert_eq!(row_text(&terminal, 2), "ee    ");
+    }
+
+    #[test]
+    fn test_erase_in_display_3_on_alternate_screen_clears_main_scrollback() {
+        let mut terminal = Terminal::new(6, 3);
+        terminal.process_bytes(b"aa\r\nbb\r\ncc\r\ndd\r\nee");
+
+        terminal.process_bytes(b"\x1b[?1049h\x1b[3J\x1b[?1049l");
+
+        assert_eq!(terminal.state().grid.cells.len(), 3);
+        assert_eq!(row_text(&terminal, 0), "cc    ");
+    }
+
+    // Extended SGR attributes
+
+    #[test]
+    fn test_sgr_extended_attributes_stored_per_cell() {
+        let mut terminal = Terminal::new(20, 2);
+        terminal.process_bytes(b"\x1b[2;5;8;9;53mA\x1b[21mB");
+
+        let viewport = terminal.state().grid.get_viewport();
+        let a = &viewport[0][0];
+        assert!(a.faint && a.blink && a.conceal && a.strikethrough && a.overline);
+        assert_eq!(a.underline, UnderlineStyle::None);
+
+        let b = &viewport[0][1];
+        assert_eq!(b.underline, UnderlineStyle::Double);
+    }
+
+    #[test]
+    fn test_sgr_extended_attributes_reset_individually() {
+        let mut terminal = Terminal::new(20, 2);
+        terminal.process_bytes(b"\x1b[1;2;6;8;9;21;53m");
+
+        terminal.process_bytes(b"\x1b[22;25;28;29;24;55m");
+
+        let state = terminal.state();
+        assert!(!state.bold && !state.faint);
+        assert!(!state.blink);
+        assert!(!state.conceal);
+        assert!(!state.strikethrough);
+        assert_eq!(state.underline, UnderlineStyle::None);
+        assert!(!state.overline);
+    }
+
+    #[test]
+    fn test_sgr_reset_and_empty_params_are_equivalent() {
+        let mut explicit = Terminal::new(20, 2);
+        let mut empty = Terminal::new(20, 2);
+
+        let attributes = b"\x1b[1;2;3;4;5;7;8;9;53;31;44m";
+        explicit.process_bytes(attributes);
+        empty.process_bytes(attributes);
+
+        explicit.process_bytes(b"\x1b[0mX");
+        empty.process_bytes(b"\x1b[mX");
+
+        let explicit_cell = explicit.state().grid.get_viewport()[0][0];
+        let empty_cell = empty.state().grid.get_viewport()[0][0];
+        for cell in [explicit_cell, empty_cell] {
+            assert!(!cell.reverse && !cell.bold && !cell.faint && !cell.italic);
+            assert_eq!(cell.underline, UnderlineStyle::None);
+            assert!(!cell.blink && !cell.conceal);
+            assert!(!cell.strikethrough && !cell.overline);
+            assert_eq!(cell.fg, Color::white());
+            assert_eq!(cell.bg, Color::black());
+        }
+    }
+
+    #[test]
+    fn test_sgr_underline_styles_from_subparams() {
+        let mut terminal = Terminal::new(20, 2);
+        terminal.process_bytes(b"\x1b[4:3mA\x1b[4:4mB\x1b[4:5mC\x1b[4:2mD\x1b[4mE\x1b[4:0mF");
+
+        let styles: Vec<UnderlineStyle> = terminal.state().grid.get_viewport()[0][..6]
+            .iter()
+            .map(|cell| cell.underline)
+            .collect();
+        assert_eq!(
+            styles,
+            vec![
+                UnderlineStyle::Curly,
+                UnderlineStyle::Dotted,
+                UnderlineStyle::Dashed,
+                UnderlineStyle::Double,
+                UnderlineStyle::Single,
+                UnderlineStyle::None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sgr_underline_subparam_does_not_consume_next_param() {
+        let mut terminal = Terminal::new(20, 2);
+        terminal.process_bytes(b"\x1b[4:3;31m");
+
+        assert_eq!(terminal.state().underline, UnderlineStyle::Curly);
+        assert_eq!(terminal.state().fg, Color::from_ansi_index(1));
+    }
+
+    #[test]
+    fn test_sgr_underline_color_colon_forms() {
+        let mut terminal = Terminal::new(20, 2);
+
+        // Colon form with empty color space id, as sent by Neovim
+        terminal.process_bytes(b"\x1b[58:2::255:128:0m");
+        assert_eq!(
+            terminal.state().underline_color,
+            Some(Color::new(255, 128, 0))
+        );
+
+        // Colon form without color space id
+        terminal.process_bytes(b"\x1b[58:2:1:2:3m");
+        assert_eq!(terminal.state().underline_color, Some(Color::new(1, 2, 3)));
+
+        terminal.process_bytes(b"\x1b[58:5:196m");
+        assert_eq!(
+            terminal.state().underline_color,
+            Some(Color::new(255, 0, 0))
+        );
+
+        terminal.process_bytes(b"\x1b[59m");
+        assert_eq!(terminal.state().underline_color, None);
+    }
+
+    #[test]
+    fn test_sgr_underline_color_semicolon_form() {
+        let mut terminal = Terminal::new(20, 2);
+        terminal.process_bytes(b"\x1b[58;2;10;20;30;4mX");
+
+        let cell = terminal.state().grid.get_viewport()[0][0];
+        assert_eq!(cell.underline_color, Some(Color::new(10, 20, 30)));
+        assert_eq!(cell.underline, UnderlineStyle::Single);
+    }
+
+    #[test]
+    fn test_sgr_extended_colors_colon_form() {
+        let mut terminal = Terminal::new(20, 2);
+        terminal.process_bytes(b"\x1b[38:2::1:2:3;48:5:21m");
+
+        assert_eq!(terminal.state().fg, Color::new(1, 2, 3));
+        assert_eq!(terminal.state().bg, Color::new(0, 0, 255));
+    }
+
+    #[test]
+    fn test_bold_keeps_palette_color_by_default() {
+        let mut terminal = Terminal::new(20, 2);
+        terminal.process_bytes(b"\x1b[1;31mX");
+
+        let cell = terminal.state().grid.get_viewport()[0][0];
+        assert!(cell.bold);
+        assert_eq!(cell.fg, Color::from_ansi_index(1));
+    }
+
+    #[test]
+    fn test_bold_is_bright_brightens_basic_colors() {
+        let mut terminal = Terminal::new(20, 2);
+        terminal.state_mut().bold_is_bright = true;
+
+        // Basic palette color is brightened, truecolor and bright colors are not
+        terminal.process_bytes(b"\x1b[1;31mA\x1b[38;2;1;2;3mB\x1b[91mC\x1b[22;31mD");
+
+        let viewport = terminal.state().grid.get_viewport();
+        assert_eq!(viewport[0][0].fg, Color::from_ansi_index(9));
+        assert_eq!(viewport[0][1].fg, Color::new(1, 2, 3));
+        assert_eq!(viewport[0][2].fg, Color::from_ansi_index(9));
+        assert_eq!(viewport[0][3].fg, Color::from_ansi_index(1));
+    }
 }
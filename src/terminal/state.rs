@@ -5,13 +5,14 @@
 
 use super::color::Color;
 use super::cursor::Cursor;
-use super::grid::TerminalGrid;
+use super::grid::{TerminalGrid, UnderlineStyle};
 
 /// Terminal state
 ///
 /// Contains all mutable state for the terminal including the grid,
 /// cursor position, and text attributes. This is a pure data structure
 /// without any behavior - the parsing logic lives in Terminal.
+#[derive(Clone)]
 pub struct TerminalState {
     /// The terminal grid (cells, scrollback, alternate screen)
     pub grid: TerminalGrid,
@@ -22,24 +23,66 @@ pub struct TerminalState {
     /// Foreground color
     pub fg: Color,
 
+    /// Palette index of the foreground color when set by SGR 30-37
+    pub fg_index: Option<u8>,
+
     /// Background color
     pub bg: Color,
 
+    /// Background is the default color (SGR 49), not one set by SGR
+    pub default_bg: bool,
+
     /// Bold attribute
     pub bold: bool,
 
+    /// Render bold text with colors 0-7 in their bright variants (8-15)
+    ///
+    /// Off by default: bold only selects the bold font face.
+    pub bold_is_bright: bool,
+
     /// Italic attribute
     pub italic: bool,
 
-    /// Underline attribute
-    pub underline: bool,
+    /// Underline style attribute
+    pub underline: UnderlineStyle,
 
     /// Reverse video attribute (swap fg/bg colors)
     pub reverse: bool,
 
+    /// Faint attribute (decreased intensity)
+    pub faint: bool,
+
+    /// Blink attribute (slow and rapid blink are treated alike)
+    pub blink: bool,
+
+    /// Conceal attribute (hidden text)
+    pub conceal: bool,
+
+    /// Strikethrough attribute (crossed-out text)
+    pub strikethrough: bool,
+
+    /// Overline attribute
+    pub overline: bool,
+
+    /// Underline color (SGR 58), `None` follows the foreground color
+    pub underline_color: Option<Color>,
+
     /// Auto wrap mode - whether text wraps to next line at right margin
     pub auto_wrap: bool,
 
+    /// Pending wrap - a character was printed in the last column and the
+    /// next printable character wraps to the next line first
+    pub pending_wrap: bool,
+
+    /// Origin mode (DECOM) - cursor addressing is relative to the margins
+    pub origin_mode: bool,
+
+    /// Left/right margin mode (DECLRMM) - enables DECSLRM (mode 69)
+    pub left_right_margin_mode: bool,
+
+    /// Cursor saved by SCOSC (`CSI s`), restored by SCORC (`CSI u`)
+    pub saved_cursor: Option<Cursor>,
+
     /// Bracketed paste mode - wraps pasted text with markers
     pub bracketed_paste: bool,
 
@@ -72,6 +115,10 @@ pub struct TerminalState {
 
     /// Synchronized output mode - batches output updates (mode 2026)
     pub synchronized_output: bool,
+
+    /// Number of synchronized updates begun so far, so renderers can tell
+    /// whether one happened between two frames
+    pub synchronized_updates: u64,
 }
 
 impl TerminalState {
@@ -81,12 +128,25 @@ impl TerminalState {
             grid: TerminalGrid::new(cols, rows),
             cursor: Cursor::at_origin(),
             fg: Color::white(),
-            bg: Color::black(),
+            fg_index: None,
+            bg: Color::default_background(),
+            default_bg: true,
             bold: false,
+            bold_is_bright: false,
             italic: false,
-            underline: false,
+            underline: UnderlineStyle::None,
             reverse: false,
+            faint: false,
+            blink: false,
+            conceal: false,
+            strikethrough: false,
+            overline: false,
+            underline_color: None,
             auto_wrap: true, // VT100 default
+            pending_wrap: false,
+            origin_mode: false,
+            left_right_margin_mode: false,
+            saved_cursor: None,
             bracketed_paste: false,
             application_cursor_keys: false,
             show_cursor: true,   // Cursor visible by default
@@ -98,6 +158,7 @@ impl TerminalState {
             mouse_all_motion: false,
             mouse_urxvt: false,
             synchronized_output: false,
+            synchronized_updates: 0,
         }
     }
 }
@@ -0,0 +1,399 @@
+//! Text extraction from the grid
+//!
+//! Turns cells back into text for copying, searching, exporting and test
+//! assertions. Lines broken by auto-wrap are joined again, trailing blanks are
+//! trimmed, double-width characters appear once and combining characters stay
+//! with the character they belong to.
+
+use super::color::Color;
+use super::grid::{Cell, TerminalGrid, UnderlineStyle};
+
+/// Cells to extract text from
+///
+/// Points are `(row, col)` with absolute rows, counting from the oldest
+/// scrollback line: add `screen_start()` to cursor rows or `viewport_start` to
+/// viewport rows. Both points are included and may come in either order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextRange {
+    /// From one point to the other in reading order, like a mouse selection
+    Linear {
+        start: (usize, usize),
+        end: (usize, usize),
+    },
+    /// The rectangle with the two points as corners, like a block selection
+    Block {
+        start: (usize, usize),
+        end: (usize, usize),
+    },
+}
+
+/// How extracted text is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextFormat {
+    /// Characters only
+    #[default]
+    Plain,
+    /// Characters with SGR sequences reproducing colors and attributes; lines
+    /// that set any end with a reset
+    Sgr,
+}
+
+impl TerminalGrid {
+    /// Text of the cells in `range`
+    ///
+    /// Rows end in a newline, except the last one and rows that auto-wrap
+    /// continued onto the next row of a linear range. Blanks at the end of
+    /// rows are trimmed; with `TextFormat::Sgr`, only those without a
+    /// background color or attributes.
+    pub fn text(&self, range: TextRange, format: TextFormat) -> String {
+        let mut writer = TextWriter::new(format);
+        let Some(last_row) = self.cells.len().checked_sub(1) else {
+            return String::new();
+        };
+
+        match range {
+            TextRange::Linear { start, end } => {
+                let (start, end) = if start <= end {
+                    (start, end)
+                } else {
+                    (end, start)
+                };
+                let end_row = end.0.min(last_row);
+                for row in start.0..=end_row {
+                    let line = &self.cells[row];
+                    let first = if row == start.0 { start.1 } else { 0 };
+                    let last = if row == end.0 { end.1 } else { usize::MAX };
+                    let cells = span(line, first, last);
+
+                    let joins = row < end_row
+                        && last >= line.len() - 1
+                        && line.last().is_some_and(|cell| cell.wrapped);
+                    if !joins {
+                        writer.trimmed(cells);
+                        if row < end_row {
+                            writer.newline();
+                        }
+                        continue;
+                    }
+
+                    // A double-width character that didn't fit wrapped early,
+                    // leaving a blank in the last column
+                    let wrapped_early = self.cells[row + 1].first().is_some_and(|cell| cell.wide)
+                        && cells.last().is_some_and(|cell| writer.is_blank(cell));
+                    if wrapped_early {
+                        writer.cells(&cells[..cells.len() - 1]);
+                    } else {
+                        writer.cells(cells);
+                    }
+                }
+            }
+            TextRange::Block { start, end } => {
+                let (first, last) = (start.1.min(end.1), start.1.max(end.1));
+                let end_row = start.0.max(end.0).min(last_row);
+                for row in start.0.min(end.0)..=end_row {
+                    writer.trimmed(span(&self.cells[row], first, last));
+                    if row < end_row {
+                        writer.newline();
+                    }
+                }
+            }
+        }
+        writer.finish()
+    }
+
+    /// Text of the active screen, without the empty lines below its content
+    pub fn screen_text(&self, format: TextFormat) -> String {
+        let start = self.screen_start();
+        let end = (start + self.viewport_height).saturating_sub(1);
+        self.lines_text(start, end, format)
+    }
+
+    /// Text of the scrollback and the screen below it, without the empty
+    /// lines below the screen's content
+    pub fn scrollback_text(&self, format: TextFormat) -> String {
+        self.lines_text(0, self.cells.len().saturating_sub(1), format)
+    }
+
+    fn lines_text(&self, start: usize, end: usize, format: TextFormat) -> String {
+        let mut text = self.text(
+            TextRange::Linear {
+                start: (start, 0),
+                end: (end, usize::MAX),
+            },
+            format,
+        );
+        text.truncate(text.trim_end_matches('\n').len());
+        text
+    }
+}
+
+/// Columns `first..=last` of a line, widened to the whole of a double-width
+/// character whose second half is `first`
+fn span(line: &[Cell], first: usize, last: usize) -> &[Cell] {
+    let end = last.saturating_add(1).min(line.len());
+    let mut start = first.min(end);
+    if start > 0 && start < end && line[start].wide_spacer && line[start - 1].wide {
+        start -= 1;
+    }
+    &line[start..end]
+}
+
+/// Colors and attributes written as SGR parameters
+///
+/// Reverse video isn't among them: cells store their colors already swapped.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) struct Style {
+    pub(super) fg: Color,
+    pub(super) bg: Color,
+    pub(super) default_bg: bool,
+    pub(super) bold: bool,
+    pub(super) faint: bool,
+    pub(super) italic: bool,
+    pub(super) underline: UnderlineStyle,
+    pub(super) blink: bool,
+    pub(super) conceal: bool,
+    pub(super) strikethrough: bool,
+    pub(super) overline: bool,
+    pub(super) underline_color: Option<Color>,
+}
+
+impl Style {
+    pub(super) fn of(cell: &Cell) -> Self {
+        Self {
+            fg: cell.fg,
+            bg: cell.bg,
+            default_bg: cell.default_bg,
+            bold: cell.bold,
+            faint: cell.faint,
+            italic: cell.italic,
+            underline: cell.underline,
+            blink: cell.blink,
+            conceal: cell.conceal,
+            strikethrough: cell.strikethrough,
+            overline: cell.overline,
+            underline_color: cell.underline_color,
+        }
+    }
+
+    pub(super) fn plain() -> Self {
+        Self::of(&Cell::default())
+    }
+
+    /// SGR sequence switching any style to this one
+    fn write_sgr(&self, out: &mut String) {
+        let plain = Self::plain();
+        out.push_str("\x1b[0");
+        for (set, code) in [
+            (self.bold, "1"),
+            (self.faint, "2"),
+            (self.italic, "3"),
+            (self.blink, "5"),
+            (self.conceal, "8"),
+            (self.strikethrough, "9"),
+            (self.overline, "53"),
+        ] {
+            if set {
+                out.push(';');
+                out.push_str(code);
+            }
+        }
+        match self.underline {
+            UnderlineStyle::None => {}
+            UnderlineStyle::Single => out.push_str(";4"),
+            UnderlineStyle::Double => out.push_str(";4:2"),
+            UnderlineStyle::Curly => out.push_str(";4:3"),
+            UnderlineStyle::Dotted => out.push_str(";4:4"),
+            UnderlineStyle::Dashed => out.push_str(";4:5"),
+        }
+        if self.fg != plain.fg {
+            write_color(out, 38, self.fg);
+        }
+        if !self.default_bg {
+            write_color(out, 48, self.bg);
+        }
+        if let Some(color) = self.underline_color {
+            write_color(out, 58, color);
+        }
+        out.push('m');
+    }
+}
+
+/// Color parameters after `code` (38, 48 or 58): the palette index when the
+/// palette has the color, RGB otherwise
+fn write_color(out: &mut String, code: u8, color: Color) {
+    let params = match color.to_ansi_index() {
+        Some(index) => format!(";{};5;{}", code, index),
+        None => format!(";{};2;{};{};{}", code, color.r, color.g, color.b),
+    };
+    out.push_str(&params);
+}
+
+/// Accumulates extracted text, tracking the style written so far
+pub(super) struct TextWriter {
+    format: TextFormat,
+    out: String,
+    style: Style,
+}
+
+impl TextWriter {
+    pub(super) fn new(format: TextFormat) -> Self {
+        Self {
+            format,
+            out: String::new(),
+            style: Style::plain(),
+        }
+    }
+
+    /// Whether the cell can be trimmed from the end of a line
+    pub(super) fn is_blank(&self, cell: &Cell) -> bool {
+        cell.ch == ' '
+            && cell.combining[0].is_none()
+            && (self.format == TextFormat::Plain || Style::of(cell) == Style::plain())
+    }
+
+    pub(super) fn cells(&mut self, cells: &[Cell]) {
+        for cell in cells.iter().filter(|cell| !cell.wide_spacer) {
+            if self.format == TextFormat::Sgr {
+                let style = Style::of(cell);
+                if style != self.style {
+                    style.write_sgr(&mut self.out);
+                    self.style = style;
+                }
+            }
+            self.out.extend(cell.chars());
+        }
+    }
+
+    pub(super) fn trimmed(&mut self, cells: &[Cell]) {
+        let len = self.trimmed_len(cells);
+        self.cells(&cells[..len]);
+    }
+
+    /// Number of cells left after trimming blanks from the end
+    pub(super) fn trimmed_len(&self, cells: &[Cell]) -> usize {
+        cells
+            .iter()
+            .rposition(|cell| !self.is_blank(cell))
+            .map_or(0, |last| last + 1)
+    }
+
+    fn reset(&mut self) {
+        if self.style != Style::plain() {
+            self.out.push_str("\x1b[0m");
+            self.style = Style::plain();
+        }
+    }
+
+    pub(super) fn newline(&mut self) {
+        self.reset();
+        self.out.push('\n');
+    }
+
+    pub(super) fn finish(mut self) -> String {
+        self.reset();
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Terminal;
+
+    fn run(output: &[u8]) -> Terminal {
+        let mut terminal = Terminal::new(10, 4);
+        terminal.process_bytes(output);
+        terminal
+    }
+
+    #[test]
+    fn test_screen_text_trims_blanks() {
+        let terminal = run(b"one  \r\n\r\n  three");
+        let grid = &terminal.state().grid;
+        assert_eq!(grid.screen_text(TextFormat::Plain), "one\n\n  three");
+    }
+
+    #[test]
+    fn test_wrapped_lines_are_joined() {
+        let terminal = run(b"0123456789abc\r\nhard\r\nbreak");
+        let grid = &terminal.state().grid;
+        assert_eq!(
+            grid.screen_text(TextFormat::Plain),
+            "0123456789abc\nhard\nbreak"
+        );
+
+        // Rewriting the last column breaks the line again
+        let terminal = run(b"0123456789abc\x1b[1;10Hx");
+        let grid = &terminal.state().grid;
+        assert_eq!(grid.screen_text(TextFormat::Plain), "012345678x\nabc");
+    }
+
+    #[test]
+    fn test_wide_and_combining_characters() {
+        // "e" with a combining acute accent, then a double-width character
+        // that wraps early and leaves the last column blank
+        let terminal = run("e\u{301}12345678\u{4e2d}!".as_bytes());
+        let grid = &terminal.state().grid;
+        assert_eq!(
+            grid.screen_text(TextFormat::Plain),
+            "e\u{301}12345678\u{4e2d}!"
+        );
+
+        // A range starting on the second half includes the whole character
+        let range = TextRange::Linear {
+            start: (1, 1),
+            end: (1, 2),
+        };
+        assert_eq!(grid.text(range, TextFormat::Plain), "\u{4e2d}!");
+    }
+
+    #[test]
+    fn test_linear_and_block_ranges() {
+        let terminal = run(b"abcdef\r\nghijkl\r\nmnopqr");
+        let grid = &terminal.state().grid;
+
+        let linear = TextRange::Linear {
+            start: (2, 1),
+            end: (0, 4),
+        };
+        assert_eq!(grid.text(linear, TextFormat::Plain), "ef\nghijkl\nmn");
+
+        let block = TextRange::Block {
+            start: (0, 4),
+            end: (2, 1),
+        };
+        assert_eq!(grid.text(block, TextFormat::Plain), "bcde\nhijk\nnopq");
+    }
+
+    #[test]
+    fn test_scrollback_text() {
+        let terminal = run(b"1\r\n2\r\n3\r\n4\r\n5\r\n6");
+        let grid = &terminal.state().grid;
+        assert_eq!(grid.screen_text(TextFormat::Plain), "3\n4\n5\n6");
+        assert_eq!(grid.scrollback_text(TextFormat::Plain), "1\n2\n3\n4\n5\n6");
+    }
+
+    #[test]
+    fn test_sgr_text() {
+        let terminal = run(
+            b"\x1b[1;31mred\x1b[0m \x1b[4:3;38;2;1;2;3mrgb\x1b[0m\r\n\x1b[44m  \x1b[40m  \x1b[0m",
+        );
+        let grid = &terminal.state().grid;
+        assert_eq!(
+            grid.screen_text(TextFormat::Sgr),
+            "\x1b[0;1;38;5;1mred\x1b[0m \x1b[0;4:3;38;2;1;2;3mrgb\x1b[0m\n\
+             \x1b[0;48;5;4m  \x1b[0;48;5;0m  \x1b[0m"
+        );
+
+        // The output reproduces the screen
+        let copy = terminal_from_text(&grid.screen_text(TextFormat::Sgr));
+        assert_eq!(
+            copy.state().grid.screen_text(TextFormat::Sgr),
+            grid.screen_text(TextFormat::Sgr)
+        );
+    }
+
+    fn terminal_from_text(text: &str) -> Terminal {
+        run(text.replace('\n', "\r\n").as_bytes())
+    }
+}